@@ -0,0 +1,169 @@
+use automerge_frontend::{InvalidChangeRequest, LocalChange, Path, Value};
+use automerge_protocol::{decode_patch, ActorID, Patch};
+use js_sys::Uint8Array;
+use serde::Deserialize;
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+
+use crate::{js_to_rust, rust_to_js, to_js_err};
+
+/// An element of a path, paths are passed from JS as arrays of map keys and
+/// list indices, e.g. `["birds", 3, "name"]`
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PathElement {
+    Key(String),
+    Index(u32),
+}
+
+fn to_path(elements: Vec<PathElement>) -> Path {
+    elements
+        .into_iter()
+        .fold(Path::root(), |path, element| match element {
+            PathElement::Key(key) => path.key(key),
+            PathElement::Index(index) => path.index(index),
+        })
+}
+
+fn js_to_path(path: JsValue) -> Result<Path, JsValue> {
+    let elements: Vec<PathElement> = js_to_rust(path)?;
+    Ok(to_path(elements))
+}
+
+/// A single edit in a call to `Frontend.change`
+#[derive(Deserialize)]
+#[serde(tag = "action", rename_all = "camelCase")]
+enum Edit {
+    Set {
+        path: Vec<PathElement>,
+        value: serde_json::Value,
+    },
+    Insert {
+        path: Vec<PathElement>,
+        value: serde_json::Value,
+    },
+    Delete {
+        path: Vec<PathElement>,
+    },
+    Increment {
+        path: Vec<PathElement>,
+        by: Option<u32>,
+    },
+}
+
+impl From<Edit> for LocalChange {
+    fn from(edit: Edit) -> Self {
+        match edit {
+            Edit::Set { path, value } => LocalChange::set(to_path(path), Value::from_json(&value)),
+            Edit::Insert { path, value } => {
+                LocalChange::insert(to_path(path), Value::from_json(&value))
+            }
+            Edit::Delete { path } => LocalChange::delete(to_path(path)),
+            Edit::Increment { path, by } => {
+                LocalChange::increment_by(to_path(path), by.map_or(1, i64::from))
+            }
+        }
+    }
+}
+
+#[wasm_bindgen]
+pub struct Frontend {
+    frontend: automerge_frontend::Frontend,
+}
+
+#[allow(clippy::new_without_default)]
+#[wasm_bindgen]
+impl Frontend {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Frontend {
+        Frontend {
+            frontend: automerge_frontend::Frontend::new(),
+        }
+    }
+
+    #[wasm_bindgen(js_name = newWithActorId)]
+    pub fn new_with_actor_id(actor_id: JsValue) -> Result<Frontend, JsValue> {
+        let mut frontend = automerge_frontend::Frontend::new();
+        frontend.actor_id = js_to_rust::<ActorID>(actor_id)?;
+        Ok(Frontend { frontend })
+    }
+
+    #[wasm_bindgen(js_name = actorId)]
+    pub fn actor_id(&self) -> Result<JsValue, JsValue> {
+        rust_to_js(&self.frontend.actor_id)
+    }
+
+    /// Apply a list of edits such as `{action: "set", path: ["bird"], value: "magpie"}`
+    /// as a single change. Returns the change request to pass to
+    /// `State.applyLocalChange`, or `undefined` if the edits changed nothing.
+    #[wasm_bindgen]
+    pub fn change(&mut self, message: Option<String>, edits: JsValue) -> Result<JsValue, JsValue> {
+        let edits: Vec<Edit> = js_to_rust(edits)?;
+        let change = self
+            .frontend
+            .change::<_, InvalidChangeRequest>(message, |doc| {
+                for edit in edits {
+                    doc.add_change(edit.into())?;
+                }
+                Ok(())
+            })
+            .map_err(to_js_err)?;
+        match change {
+            Some(change) => rust_to_js(&change),
+            None => Ok(JsValue::UNDEFINED),
+        }
+    }
+
+    #[wasm_bindgen(js_name = applyPatch)]
+    pub fn apply_patch(&mut self, patch: JsValue) -> Result<(), JsValue> {
+        let patch: Patch = js_to_rust(patch)?;
+        self.frontend.apply_patch(patch).map_err(to_js_err)
+    }
+
+    /// Apply a patch in the binary patch encoding, as returned by e.g.
+    /// `State.getPatchBinary`
+    #[wasm_bindgen(js_name = applyPatchBinary)]
+    pub fn apply_patch_binary(&mut self, patch: Uint8Array) -> Result<(), JsValue> {
+        let patch = decode_patch(&patch.to_vec()).map_err(to_js_err)?;
+        self.frontend.apply_patch(patch).map_err(to_js_err)
+    }
+
+    /// The seq of the latest local change whose patch has been applied, to
+    /// pass to `State.ack`
+    #[wasm_bindgen(js_name = ackedSeq)]
+    pub fn acked_seq(&self) -> u32 {
+        self.frontend.acked_seq() as u32
+    }
+
+    #[wasm_bindgen(js_name = getValue)]
+    pub fn get_value(&self, path: JsValue) -> Result<JsValue, JsValue> {
+        match self.frontend.get_value(&js_to_path(path)?) {
+            Some(value) => rust_to_js(value.to_json()),
+            None => Ok(JsValue::UNDEFINED),
+        }
+    }
+
+    /// Returns an object mapping the op ID of each conflicting value at `path`
+    /// to that value
+    #[wasm_bindgen(js_name = getConflicts)]
+    pub fn get_conflicts(&self, path: JsValue) -> Result<JsValue, JsValue> {
+        match self.frontend.get_conflicts(&js_to_path(path)?) {
+            Some(conflicts) => {
+                let conflicts: HashMap<String, serde_json::Value> = conflicts
+                    .into_iter()
+                    .map(|(opid, value)| (opid.to_string(), value.to_json()))
+                    .collect();
+                rust_to_js(&conflicts)
+            }
+            None => Ok(JsValue::UNDEFINED),
+        }
+    }
+
+    #[wasm_bindgen(js_name = getObjectId)]
+    pub fn get_object_id(&self, path: JsValue) -> Result<JsValue, JsValue> {
+        match self.frontend.get_object_id(&js_to_path(path)?) {
+            Some(object_id) => rust_to_js(&object_id),
+            None => Ok(JsValue::UNDEFINED),
+        }
+    }
+}
@@ -1,7 +1,7 @@
 //#![feature(set_stdio)]
 
 use automerge_backend::{Backend, Change};
-use automerge_protocol::{ActorID, ChangeHash, UncompressedChange};
+use automerge_protocol::{encode_patch, ActorID, ChangeHash, UncompressedChange};
 use js_sys::{Array, Uint8Array};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
@@ -9,6 +9,9 @@ use std::fmt::Display;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 
+mod frontend;
+pub use frontend::Frontend;
+
 extern crate web_sys;
 #[allow(unused_macros)]
 macro_rules! log {
@@ -55,6 +58,25 @@ impl State {
         Ok(result)
     }
 
+    /// The same as `applyChanges` but the patch is returned in the binary
+    /// patch encoding
+    #[wasm_bindgen(js_name = applyChangesBinary)]
+    pub fn apply_changes_binary(&mut self, changes: Array) -> Result<Array, JsValue> {
+        let mut ch = Vec::with_capacity(changes.length() as usize);
+        for c in changes.iter() {
+            let bytes = c.dyn_into::<Uint8Array>().unwrap().to_vec();
+            ch.push(Change::from_bytes(bytes).map_err(to_js_err)?);
+        }
+        let patch = self.backend.apply_changes(ch).map_err(to_js_err)?;
+        let heads = self.backend.get_heads();
+        let p: Uint8Array = encode_patch(&patch).as_slice().into();
+        let h = rust_to_js(&heads)?;
+        let result = Array::new();
+        result.push(p.as_ref());
+        result.push(&h);
+        Ok(result)
+    }
+
     #[wasm_bindgen(js_name = loadChanges)]
     pub fn load_changes(&mut self, changes: Array) -> Result<JsValue, JsValue> {
         let mut ch = Vec::with_capacity(changes.length() as usize);
@@ -82,12 +104,36 @@ impl State {
         Ok(result)
     }
 
+    /// The same as `applyLocalChange` but the patch is returned in the
+    /// binary patch encoding
+    #[wasm_bindgen(js_name = applyLocalChangeBinary)]
+    pub fn apply_local_change_binary(&mut self, change: JsValue) -> Result<Array, JsValue> {
+        let c: UncompressedChange = js_to_rust(change)?;
+        let (patch, change) = self.backend.apply_local_change(c).map_err(to_js_err)?;
+        let heads = self.backend.get_heads();
+        let result = Array::new();
+        let bytes: Uint8Array = change.bytes.as_slice().into();
+        let p: Uint8Array = encode_patch(&patch).as_slice().into();
+        let h = rust_to_js(&heads)?;
+        result.push(p.as_ref());
+        result.push(bytes.as_ref());
+        result.push(&h);
+        Ok(result)
+    }
+
     #[wasm_bindgen(js_name = getPatch)]
     pub fn get_patch(&self) -> Result<JsValue, JsValue> {
         let patch = self.backend.get_patch().map_err(to_js_err)?;
         rust_to_js(&patch)
     }
 
+    #[wasm_bindgen(js_name = getPatchBinary)]
+    pub fn get_patch_binary(&self) -> Result<JsValue, JsValue> {
+        let patch = self.backend.get_patch().map_err(to_js_err)?;
+        let bytes: Uint8Array = encode_patch(&patch).as_slice().into();
+        Ok(bytes.into())
+    }
+
     #[wasm_bindgen(js_name = getHeads)]
     pub fn get_heads(&self) -> Result<JsValue, JsValue> {
         let heads = self.backend.get_heads();
@@ -121,6 +167,34 @@ impl State {
         Ok(result)
     }
 
+    /// The binary change most recently applied by `applyLocalChange`, or
+    /// `undefined` if there isn't one
+    #[wasm_bindgen(js_name = getLastLocalChange)]
+    pub fn get_last_local_change(&self) -> JsValue {
+        match self.backend.get_last_local_change() {
+            Some(change) => Uint8Array::from(change.bytes.as_slice()).into(),
+            None => JsValue::UNDEFINED,
+        }
+    }
+
+    /// Record that the frontend for `actorId` has applied the patches for its
+    /// local changes up to and including `seq`, see `Frontend.ackedSeq`
+    #[wasm_bindgen]
+    pub fn ack(&mut self, actor_id: JsValue, seq: u32) -> Result<(), JsValue> {
+        let actor: ActorID = js_to_rust(actor_id)?;
+        self.backend.ack(&actor, seq as u64).map_err(to_js_err)
+    }
+
+    #[wasm_bindgen(js_name = getUnackedChanges)]
+    pub fn get_unacked_changes(&self) -> Array {
+        let result = Array::new();
+        for c in self.backend.get_unacked_changes() {
+            let bytes: Uint8Array = c.bytes.as_slice().into();
+            result.push(bytes.as_ref());
+        }
+        result
+    }
+
     #[wasm_bindgen(js_name = getMissingDeps)]
     pub fn get_missing_deps(&self) -> Result<JsValue, JsValue> {
         let hashes = self.backend.get_missing_deps();
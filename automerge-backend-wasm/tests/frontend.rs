@@ -0,0 +1,120 @@
+use automerge_backend_wasm::{Frontend, State};
+use serde_json::json;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_test::*;
+
+#[allow(deprecated)]
+fn js(value: serde_json::Value) -> JsValue {
+    JsValue::from_serde(&value).unwrap()
+}
+
+#[allow(deprecated)]
+fn rust(value: JsValue) -> serde_json::Value {
+    value.into_serde().unwrap()
+}
+
+/// Apply a change made by `frontend` to `backend` and apply the resulting
+/// patch back to `frontend`
+fn round_trip(frontend: &mut Frontend, backend: &mut State, edits: serde_json::Value) {
+    let change = frontend.change(None, js(edits)).unwrap();
+    let result = backend.apply_local_change(change).unwrap();
+    frontend.apply_patch(result.get(0)).unwrap();
+}
+
+#[wasm_bindgen_test]
+fn frontend_set_and_get_value() {
+    let mut frontend = Frontend::new();
+    let mut backend = State::new();
+    round_trip(
+        &mut frontend,
+        &mut backend,
+        json!([
+            {"action": "set", "path": ["birds"], "value": ["wren"]},
+            {"action": "insert", "path": ["birds", 1], "value": "magpie"},
+            {"action": "set", "path": ["count"], "value": 1},
+        ]),
+    );
+    assert_eq!(
+        rust(frontend.get_value(js(json!([]))).unwrap()),
+        json!({"birds": ["wren", "magpie"], "count": 1.0})
+    );
+    assert_eq!(
+        rust(frontend.get_value(js(json!(["birds", 1]))).unwrap()),
+        json!("magpie")
+    );
+    assert!(frontend
+        .get_value(js(json!(["missing"])))
+        .unwrap()
+        .is_undefined());
+
+    round_trip(
+        &mut frontend,
+        &mut backend,
+        json!([{"action": "delete", "path": ["birds", 0]}]),
+    );
+    assert_eq!(
+        rust(frontend.get_value(js(json!(["birds"]))).unwrap()),
+        json!(["magpie"])
+    );
+}
+
+#[wasm_bindgen_test]
+fn frontend_object_ids_and_conflicts() {
+    let mut frontend = Frontend::new();
+    let mut backend = State::new();
+    round_trip(
+        &mut frontend,
+        &mut backend,
+        json!([{"action": "set", "path": ["birds"], "value": {"wrens": 3}}]),
+    );
+    assert_eq!(
+        rust(frontend.get_object_id(js(json!([]))).unwrap()),
+        json!("_root")
+    );
+    let object_id = rust(frontend.get_object_id(js(json!(["birds"]))).unwrap());
+    assert!(object_id.as_str().unwrap().starts_with("1@"));
+
+    let conflicts = rust(
+        frontend
+            .get_conflicts(js(json!(["birds", "wrens"])))
+            .unwrap(),
+    );
+    assert_eq!(conflicts.as_object().unwrap().len(), 1);
+}
+
+#[wasm_bindgen_test]
+fn frontend_change_without_edits_is_undefined() {
+    let mut frontend = Frontend::new();
+    assert!(frontend.change(None, js(json!([]))).unwrap().is_undefined());
+    assert!(frontend
+        .change(None, js(json!([{"action": "delete", "path": ["missing"]}])))
+        .is_err());
+}
+
+#[wasm_bindgen_test]
+fn frontend_applies_binary_patches() {
+    let mut frontend = Frontend::new();
+    let mut backend = State::new();
+    let change = frontend
+        .change(
+            None,
+            js(json!([{"action": "set", "path": ["bird"], "value": "magpie"}])),
+        )
+        .unwrap();
+    let result = backend.apply_local_change_binary(change).unwrap();
+    frontend
+        .apply_patch_binary(result.get(0).dyn_into().unwrap())
+        .unwrap();
+
+    let mut other = Frontend::new();
+    let patch = backend.get_patch_binary().unwrap();
+    other.apply_patch_binary(patch.dyn_into().unwrap()).unwrap();
+    assert_eq!(
+        rust(other.get_value(js(json!([]))).unwrap()),
+        json!({"bird": "magpie"})
+    );
+    assert_eq!(
+        rust(frontend.get_value(js(json!([]))).unwrap()),
+        json!({"bird": "magpie"})
+    );
+}
@@ -0,0 +1,74 @@
+use automerge_backend::{Backend, Change};
+use automerge_bench_fixtures as fixtures;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+
+fn workloads() -> Vec<(&'static str, Vec<Change>)> {
+    vec![
+        ("typing 2000 keystrokes", fixtures::typing_trace(2_000)),
+        ("map of 10000 keys", fixtures::large_map(10_000, 100)),
+        ("20 concurrent actors", fixtures::concurrent_actors(20, 20)),
+    ]
+}
+
+fn apply_changes(c: &mut Criterion) {
+    let mut group = c.benchmark_group("apply_changes");
+    group.sample_size(10);
+    for (name, changes) in workloads() {
+        group.bench_function(name, |b| {
+            b.iter_batched(
+                || changes.clone(),
+                |changes| Backend::init().apply_changes(changes).unwrap(),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn get_patch(c: &mut Criterion) {
+    let mut group = c.benchmark_group("get_patch");
+    group.sample_size(10);
+    for (name, changes) in workloads() {
+        let backend = fixtures::backend_with(&changes);
+        group.bench_function(name, |b| b.iter(|| backend.get_patch().unwrap()));
+    }
+    group.finish();
+}
+
+fn save(c: &mut Criterion) {
+    let mut group = c.benchmark_group("save");
+    for (name, changes) in workloads() {
+        let backend = fixtures::backend_with(&changes);
+        group.bench_function(name, |b| b.iter(|| backend.save().unwrap()));
+    }
+    group.finish();
+}
+
+fn get_changes(c: &mut Criterion) {
+    let mut group = c.benchmark_group("get_changes with stale heads");
+    for (name, changes) in workloads() {
+        let backend = fixtures::backend_with(&changes);
+        // A head from halfway through the history, which has since been
+        // superseded
+        let stale = [changes[changes.len() / 2].hash];
+        group.bench_function(name, |b| b.iter(|| backend.get_changes(&stale)));
+    }
+    group.finish();
+}
+
+fn load(c: &mut Criterion) {
+    let saved = fixtures::saved(&fixtures::typing_trace(10_000));
+    let mut group = c.benchmark_group("load");
+    group.sample_size(10);
+    group.bench_function("10000 changes", |b| {
+        b.iter_batched(
+            || saved.clone(),
+            |saved| Backend::load(saved).unwrap(),
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, apply_changes, get_patch, save, get_changes, load);
+criterion_main!(benches);
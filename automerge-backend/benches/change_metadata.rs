@@ -0,0 +1,79 @@
+use automerge_backend::{Backend, Change};
+use automerge_protocol as amp;
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use std::str::FromStr;
+
+const OPS_PER_CHANGE: u64 = 100;
+
+/// A linear history of `num_changes` changes by a single actor, each setting
+/// `OPS_PER_CHANGE` keys in the root map
+fn history(num_changes: u64) -> Vec<Change> {
+    let actor = amp::ActorID::from_str("deadbeefdeadbeef").unwrap();
+    let mut changes: Vec<Change> = Vec::new();
+    for seq in 1..=num_changes {
+        let start_op = (seq - 1) * OPS_PER_CHANGE + 1;
+        let operations = (0..OPS_PER_CHANGE)
+            .map(|i| amp::Op {
+                action: amp::OpType::Set(amp::ScalarValue::Uint(i)),
+                obj: amp::ObjectID::Root,
+                key: format!("key{}", i).as_str().into(),
+                insert: false,
+                pred: if seq == 1 {
+                    Vec::new()
+                } else {
+                    vec![amp::OpID::new(start_op + i - OPS_PER_CHANGE, &actor)]
+                },
+            })
+            .collect();
+        let change = Change::from(amp::UncompressedChange {
+            actor_id: actor.clone(),
+            seq,
+            start_op,
+            time: 0,
+            message: None,
+            deps: changes.last().map(|c| vec![c.hash]).unwrap_or_default(),
+            operations,
+            extra_bytes: Vec::new(),
+            unknown_columns: Vec::new(),
+        });
+        changes.push(change);
+    }
+    changes
+}
+
+fn change_metadata(c: &mut Criterion) {
+    let change = history(1).remove(0);
+    let parsed = Change::from_bytes(change.bytes.clone()).unwrap();
+
+    // Counting the ops by decoding them is what `max_op` used to do
+    c.bench_function("op count by decoding ops", |b| {
+        b.iter(|| black_box(&parsed).iter_ops().count())
+    });
+    c.bench_function("op count cached", |b| {
+        b.iter(|| black_box(&parsed).op_count())
+    });
+    c.bench_function("max_op", |b| b.iter(|| black_box(&parsed).max_op()));
+    c.bench_function("parse change", |b| {
+        b.iter_batched(
+            || change.bytes.clone(),
+            Change::from_bytes,
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+fn load_history(c: &mut Criterion) {
+    let mut backend = Backend::init();
+    backend.apply_changes(history(1_000)).unwrap();
+    let saved = backend.save().unwrap();
+
+    let mut group = c.benchmark_group("load history");
+    group.sample_size(10);
+    group.bench_function("load 1000 changes", |b| {
+        b.iter_batched(|| saved.clone(), Backend::load, BatchSize::LargeInput)
+    });
+    group.finish();
+}
+
+criterion_group!(benches, change_metadata, load_history);
+criterion_main!(benches);
@@ -0,0 +1,91 @@
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+
+use automerge_backend::{CountedBTree, OrderedSet};
+
+const SIZES: [usize; 3] = [1_000, 10_000, 100_000];
+
+/// A set of `size` keys built by inserting each key a third of the way into
+/// the set, so that it does not see only appends
+fn build<S: OrderedSet<usize> + Default>(size: usize) -> S {
+    let mut set = S::default();
+    for key in 0..size {
+        set.insert_index(key / 3, key);
+    }
+    set
+}
+
+/// Typing: insert a run of keys at a cursor which moves every so often
+fn typing<S: OrderedSet<usize>>(set: &mut S, size: usize) {
+    let mut cursor = size / 2;
+    for i in 0..1_000 {
+        if i % 100 == 0 {
+            cursor = (cursor * 7 + i) % (size + i);
+        }
+        set.insert_index(cursor, size + i);
+        cursor += 1;
+    }
+}
+
+/// Look up the index of keys and the keys at indexes across the set
+fn lookups<S: OrderedSet<usize>>(set: &S, size: usize) -> usize {
+    (0..size)
+        .step_by(size / 1_000)
+        .map(|i| set.index_of(&i).unwrap() + set.key_of(i).unwrap())
+        .sum()
+}
+
+fn ordered_set_insert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("OrderedSet::insert_index");
+    group.sample_size(10);
+    for size in SIZES.iter() {
+        let btree: CountedBTree<usize> = build(*size);
+        group.bench_with_input(BenchmarkId::new("CountedBTree", size), size, |b, size| {
+            b.iter_batched(
+                || btree.clone(),
+                |mut set| typing(&mut set, *size),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn ordered_set_remove(c: &mut Criterion) {
+    let mut group = c.benchmark_group("OrderedSet::remove_index");
+    group.sample_size(10);
+    for size in SIZES.iter() {
+        let btree: CountedBTree<usize> = build(*size);
+        let remove = |set: &mut dyn OrderedSet<usize>| {
+            for i in 0..1_000 {
+                set.remove_index((i * 7919) % (size - i));
+            }
+        };
+        group.bench_with_input(BenchmarkId::new("CountedBTree", size), size, |b, _| {
+            b.iter_batched(
+                || btree.clone(),
+                |mut set| remove(&mut set),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn ordered_set_lookup(c: &mut Criterion) {
+    let mut group = c.benchmark_group("OrderedSet::index_of and key_of");
+    for size in SIZES.iter() {
+        let btree: CountedBTree<usize> = build(*size);
+        group.bench_with_input(BenchmarkId::new("CountedBTree", size), size, |b, size| {
+            b.iter(|| lookups(&btree, *size))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    ordered_set_insert,
+    ordered_set_remove,
+    ordered_set_lookup
+);
+criterion_main!(benches);
@@ -1,13 +1,21 @@
 use crate::internal::{ActorID, ElementID, InternalOp, InternalOpType, Key, ObjectID, OpID};
 use automerge_protocol as amp;
 use std::cmp::Ordering;
+use std::collections::HashMap;
 
 #[derive(PartialEq, Debug, Clone)]
-pub(crate) struct ActorMap(Vec<amp::ActorID>);
+pub(crate) struct ActorMap {
+    actors: Vec<amp::ActorID>,
+    /// The index of each actor in `actors`
+    indexes: HashMap<amp::ActorID, usize>,
+}
 
 impl ActorMap {
     pub fn new() -> ActorMap {
-        ActorMap(Vec::new())
+        ActorMap {
+            actors: Vec::new(),
+            indexes: HashMap::new(),
+        }
     }
 
     pub fn import_key(&mut self, key: &amp::Key) -> Key {
@@ -18,12 +26,12 @@ impl ActorMap {
     }
 
     pub fn import_actor(&mut self, actor: &amp::ActorID) -> ActorID {
-        if let Some(idx) = self.0.iter().position(|a| a == actor) {
-            ActorID(idx)
-        } else {
-            self.0.push(actor.clone());
-            ActorID(self.0.len() - 1)
-        }
+        ActorID(self.index_of(actor))
+    }
+
+    /// The index of `actor`, if it has been imported
+    pub fn get(&self, actor: &amp::ActorID) -> Option<ActorID> {
+        self.indexes.get(actor).cloned().map(ActorID)
     }
 
     pub fn import_opid(&mut self, opid: &amp::OpID) -> OpID {
@@ -64,11 +72,12 @@ impl ActorMap {
             amp::OpType::Del => InternalOpType::Del,
             amp::OpType::Inc(val) => InternalOpType::Inc(*val),
             amp::OpType::Set(val) => InternalOpType::Set(val.clone()),
+            amp::OpType::Unknown { .. } => InternalOpType::Unknown,
         }
     }
 
     pub fn export_actor(&self, actor: ActorID) -> amp::ActorID {
-        self.0[actor.0].clone()
+        self.actors[actor.0].clone()
     }
 
     pub fn export_opid(&self, opid: &OpID) -> amp::OpID {
@@ -82,18 +91,18 @@ impl ActorMap {
         }
     }
 
-    #[allow(dead_code)]
     pub fn index_of(&mut self, actor: &amp::ActorID) -> usize {
-        if let Some(index) = self.0.iter().position(|a| a == actor) {
-            return index;
+        if let Some(index) = self.indexes.get(actor) {
+            return *index;
         }
-        self.0.push(actor.clone());
-        self.0.len() - 1
+        self.actors.push(actor.clone());
+        self.indexes.insert(actor.clone(), self.actors.len() - 1);
+        self.actors.len() - 1
     }
 
     #[allow(dead_code)]
     pub fn actor_for(&self, index: usize) -> Option<&amp::ActorID> {
-        self.0.get(index)
+        self.actors.get(index)
     }
 
     pub fn cmp(&self, eid1: &ElementID, eid2: &ElementID) -> Ordering {
@@ -127,8 +136,8 @@ impl ActorMap {
         if op1.0 != op2.0 {
             op1.0.cmp(&op2.0)
         } else {
-            let actor1 = &self.0[(op1.1).0];
-            let actor2 = &self.0[(op2.1).0];
+            let actor1 = &self.actors[(op1.1).0];
+            let actor2 = &self.actors[(op2.1).0];
             actor1.cmp(&actor2)
             //op1.1.cmp(&op2.1)
         }
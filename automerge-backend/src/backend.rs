@@ -1,15 +1,32 @@
 use crate::actor_map::ActorMap;
+use crate::clock::Clock;
 use crate::error::AutomergeError;
-use crate::internal::ObjectID;
+use crate::internal::{ObjectID, OpID};
+use crate::loader::{salvage_changes, CorruptChunk, Loader};
 use crate::op_handle::OpHandle;
 use crate::op_set::OpSet;
 use crate::pending_diff::PendingDiff;
 use crate::Change;
 use automerge_protocol as amp;
-use core::cmp::max;
-use std::collections::{HashMap, HashSet};
+use core::cmp::{max, min};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::io::Read;
 use std::rc::Rc;
 
+/// Where a value in a document came from, see `Backend::blame`
+#[derive(Debug, PartialEq, Clone)]
+pub struct Blame {
+    pub value: amp::Value,
+    /// The op which set the value
+    pub op_id: amp::OpID,
+    pub actor: amp::ActorID,
+    /// The change containing the op, along with its time and message
+    pub hash: amp::ChangeHash,
+    pub time: i64,
+    pub message: Option<String>,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct Backend {
     queue: Vec<Rc<Change>>,
@@ -18,6 +35,17 @@ pub struct Backend {
     actors: ActorMap,
     hashes: HashMap<amp::ChangeHash, Rc<Change>>,
     history: Vec<amp::ChangeHash>,
+    /// The position of each change in `history` along with its clock. Every
+    /// change keeps its clock, not just the heads, as peers often ask about
+    /// heads which have since been superseded here.
+    index: HashMap<amp::ChangeHash, (usize, Clock)>,
+    /// The changes which list each change in their deps
+    dependents: HashMap<amp::ChangeHash, Vec<amp::ChangeHash>>,
+    /// The change most recently applied by `apply_local_change`
+    last_local_change: Option<Rc<Change>>,
+    /// For each actor which has made local changes, the seq up to which its
+    /// frontend has acknowledged their patches
+    acked: HashMap<amp::ActorID, u64>,
 }
 
 impl Backend {
@@ -30,6 +58,10 @@ impl Backend {
             states: HashMap::new(),
             history: Vec::new(),
             hashes: HashMap::new(),
+            index: HashMap::new(),
+            dependents: HashMap::new(),
+            last_local_change: None,
+            acked: HashMap::new(),
         }
     }
 
@@ -64,12 +96,80 @@ impl Backend {
         })
     }
 
-    pub fn load_changes(&mut self, mut changes: Vec<Change>) -> Result<(), AutomergeError> {
-        let changes = changes.drain(0..).map(Rc::new).collect();
-        self.apply(changes, None)?;
+    /// Apply `changes` without generating a patch, as when loading a
+    /// document. The changes, along with any already in the queue, are
+    /// sorted up front so that each is applied after its deps. Changes whose
+    /// deps are missing are left in the queue.
+    pub fn load_changes(&mut self, changes: Vec<Change>) -> Result<(), AutomergeError> {
+        let mut queued = std::mem::take(&mut self.queue);
+        queued.extend(changes.into_iter().map(Rc::new));
+        let (ready, blocked) = self.sort_causally(queued);
+        self.queue = blocked;
+        for change in ready {
+            self.apply_change(change, None)?;
+        }
         Ok(())
     }
 
+    /// Split `changes` into those which can be applied, ordered so that each
+    /// comes after the changes it depends on, and those which depend on a
+    /// change we don't have. The order of `changes` is kept where possible
+    /// and changes which have already been applied are dropped.
+    fn sort_causally(&self, changes: Vec<Rc<Change>>) -> (Vec<Rc<Change>>, Vec<Rc<Change>>) {
+        let mut index_of: HashMap<amp::ChangeHash, usize> = HashMap::new();
+        let changes: Vec<Rc<Change>> = changes
+            .into_iter()
+            .filter(|change| {
+                if self.hashes.contains_key(&change.hash) || index_of.contains_key(&change.hash) {
+                    false
+                } else {
+                    index_of.insert(change.hash, index_of.len());
+                    true
+                }
+            })
+            .collect();
+
+        // The number of unapplied deps of each change, a dep we don't have at
+        // all means the change can never be applied
+        let mut waiting_on = vec![0; changes.len()];
+        let mut dependents = vec![Vec::new(); changes.len()];
+        for (index, change) in changes.iter().enumerate() {
+            for dep in change.deps.iter() {
+                if let Some(dep_index) = index_of.get(dep) {
+                    dependents[*dep_index].push(index);
+                    waiting_on[index] += 1;
+                } else if !self.hashes.contains_key(dep) {
+                    waiting_on[index] += 1;
+                }
+            }
+        }
+
+        let mut ready: BinaryHeap<Reverse<usize>> = waiting_on
+            .iter()
+            .enumerate()
+            .filter(|(_, waiting)| **waiting == 0)
+            .map(|(index, _)| Reverse(index))
+            .collect();
+        let mut order = Vec::with_capacity(changes.len());
+        while let Some(Reverse(index)) = ready.pop() {
+            order.push(index);
+            for dependent in dependents[index].iter() {
+                waiting_on[*dependent] -= 1;
+                if waiting_on[*dependent] == 0 {
+                    ready.push(Reverse(*dependent));
+                }
+            }
+        }
+
+        let mut changes: Vec<Option<Rc<Change>>> = changes.into_iter().map(Some).collect();
+        let sorted = order
+            .into_iter()
+            .filter_map(|index| changes[index].take())
+            .collect();
+        let blocked = changes.into_iter().flatten().collect();
+        (sorted, blocked)
+    }
+
     pub fn apply_changes(
         &mut self,
         mut changes: Vec<Change>,
@@ -78,6 +178,52 @@ impl Backend {
         self.apply(changes, None)
     }
 
+    /// Work out the patch which applying `changes` would produce, without
+    /// applying them. Only the op set is copied to do this, not the change
+    /// history: the map of objects is copied, as is each object the changes
+    /// touch, except for its sequence index, whose edits are kept as a delta
+    /// on top of the existing index.
+    pub fn preview_changes(&self, changes: Vec<Change>) -> Result<amp::Patch, AutomergeError> {
+        let mut op_set = (*self.op_set).clone();
+        op_set.preview = true;
+        let mut actors = self.actors.clone();
+        let mut queue = self.queue.clone();
+        queue.extend(changes.into_iter().map(Rc::new));
+        let mut clock: HashMap<amp::ActorID, u64> = self
+            .states
+            .iter()
+            .map(|(k, v)| (k.clone(), v.len() as u64))
+            .collect();
+        let mut applied = HashSet::new();
+        let mut pending_diffs = HashMap::new();
+
+        let known = |applied: &HashSet<amp::ChangeHash>, hash: &amp::ChangeHash| {
+            self.hashes.contains_key(hash) || applied.contains(hash)
+        };
+        while let Some(index) = queue
+            .iter()
+            .position(|change| change.deps.iter().all(|dep| known(&applied, dep)))
+        {
+            let change = queue.remove(index);
+            if known(&applied, &change.hash) {
+                continue;
+            }
+            applied.insert(change.hash);
+            *clock.entry(change.actor_id().clone()).or_default() += 1;
+            Self::apply_ops(&mut op_set, &mut actors, change, Some(&mut pending_diffs))?;
+        }
+
+        let diffs = op_set.finalize_diffs(pending_diffs, &actors)?;
+        Ok(amp::Patch {
+            diffs,
+            deps: op_set.heads(),
+            max_op: op_set.max_op,
+            clock,
+            actor: None,
+            seq: None,
+        })
+    }
+
     pub fn get_heads(&self) -> Vec<amp::ChangeHash> {
         self.op_set.heads()
     }
@@ -123,9 +269,40 @@ impl Backend {
         let bin_change: Rc<Change> = Rc::new(change.into());
         let patch: amp::Patch = self.apply(vec![bin_change.clone()], Some(actor_seq))?;
 
+        self.last_local_change = Some(bin_change.clone());
+        self.acked
+            .entry(bin_change.actor_id().clone())
+            .or_insert(bin_change.seq - 1);
+
         Ok((patch, bin_change))
     }
 
+    /// The change most recently applied by `apply_local_change`, for
+    /// broadcasting to other peers
+    pub fn get_last_local_change(&self) -> Option<&Change> {
+        self.last_local_change.as_deref()
+    }
+
+    /// Record that the frontend for `actor` has applied the patches for its
+    /// local changes up to and including `seq`, i.e. that `seq` is no longer
+    /// one of its in flight requests. See `Frontend::acked_seq`.
+    pub fn ack(&mut self, actor: &amp::ActorID, seq: u64) -> Result<(), AutomergeError> {
+        let applied = self.states.get(actor).map(|v| v.len() as u64).unwrap_or(0);
+        if applied < seq {
+            return Err(AutomergeError::InvalidSeq(seq));
+        }
+        if let Some(acked) = self.acked.get_mut(actor) {
+            *acked = max(*acked, seq);
+        }
+        Ok(())
+    }
+
+    /// The local changes which have not been acknowledged with `ack`, in the
+    /// order they were applied
+    pub fn get_unacked_changes(&self) -> Vec<&Change> {
+        self.changes_after(|actor| self.acked.get(actor).cloned().unwrap_or(u64::MAX))
+    }
+
     fn check_for_duplicate(&self, change: &amp::UncompressedChange) -> Result<(), AutomergeError> {
         if self
             .states
@@ -150,7 +327,7 @@ impl Backend {
         diffs: &mut HashMap<ObjectID, Vec<PendingDiff>>,
     ) -> Result<(), AutomergeError> {
         if local {
-            self.apply_change(change, diffs)
+            self.apply_change(change, Some(diffs))
         } else {
             self.queue.push(change);
             self.apply_queued_ops(diffs)
@@ -162,7 +339,7 @@ impl Backend {
         diffs: &mut HashMap<ObjectID, Vec<PendingDiff>>,
     ) -> Result<(), AutomergeError> {
         while let Some(next_change) = self.pop_next_causally_ready_change() {
-            self.apply_change(next_change, diffs)?;
+            self.apply_change(next_change, Some(diffs))?;
         }
         Ok(())
     }
@@ -170,30 +347,46 @@ impl Backend {
     fn apply_change(
         &mut self,
         change: Rc<Change>,
-        diffs: &mut HashMap<ObjectID, Vec<PendingDiff>>,
+        diffs: Option<&mut HashMap<ObjectID, Vec<PendingDiff>>>,
     ) -> Result<(), AutomergeError> {
         if self.hashes.contains_key(&change.hash) {
             return Ok(());
         }
 
         self.update_history(&change);
+        Self::apply_ops(
+            Rc::make_mut(&mut self.op_set),
+            &mut self.actors,
+            change,
+            diffs,
+        )
+    }
 
-        let op_set = Rc::make_mut(&mut self.op_set);
-
-        let start_op = change.start_op;
-
+    /// Apply the ops in `change` to `op_set`, leaving the history alone
+    fn apply_ops(
+        op_set: &mut OpSet,
+        actors: &mut ActorMap,
+        change: Rc<Change>,
+        diffs: Option<&mut HashMap<ObjectID, Vec<PendingDiff>>>,
+    ) -> Result<(), AutomergeError> {
         op_set.update_deps(&change);
 
-        let ops = OpHandle::extract(change, &mut self.actors);
+        op_set.max_op = max(op_set.max_op, change.max_op());
 
-        op_set.max_op = max(op_set.max_op, start_op + (ops.len() as u64) - 1);
+        let ops = OpHandle::extract(change, actors);
 
-        op_set.apply_ops(ops, diffs, &self.actors)?;
-
-        Ok(())
+        op_set.apply_ops(ops, diffs, actors)
     }
 
     fn update_history(&mut self, change: &Rc<Change>) {
+        let actor = self.actors.import_actor(change.actor_id());
+        let mut clock = self.clock_of(&change.deps);
+        clock.include(actor, change.seq);
+        self.index.insert(change.hash, (self.history.len(), clock));
+        for dep in change.deps.iter() {
+            self.dependents.entry(*dep).or_default().push(change.hash);
+        }
+
         self.states
             .entry(change.actor_id().clone())
             .or_default()
@@ -222,6 +415,140 @@ impl Backend {
         self.make_patch(Some(diffs), None)
     }
 
+    /// The value of `prop` in the object `object_id`. Map keys only find
+    /// values in maps and indexes only in lists and text. Where there are
+    /// conflicting values this is the one a frontend would show.
+    pub fn get(
+        &self,
+        object_id: &amp::ObjectID,
+        prop: &amp::PathElement,
+    ) -> Result<Option<amp::Value>, AutomergeError> {
+        let object_id = self.internal_obj(object_id)?;
+        self.op_set.get_value(&object_id, prop, &self.actors)
+    }
+
+    /// The value at `path` from the root object, or `None` if some part of
+    /// the path does not exist
+    pub fn get_path(
+        &self,
+        path: &[amp::PathElement],
+    ) -> Result<Option<amp::Value>, AutomergeError> {
+        let mut value = amp::Value::Object {
+            object_id: amp::ObjectID::Root,
+            obj_type: amp::ObjType::map(),
+        };
+        for prop in path {
+            value = match value {
+                amp::Value::Object { object_id, .. } => match self.get(&object_id, prop)? {
+                    Some(value) => value,
+                    None => return Ok(None),
+                },
+                amp::Value::Primitive(_) => return Ok(None),
+            }
+        }
+        Ok(Some(value))
+    }
+
+    /// All of the concurrent values of `prop` in the object `object_id`,
+    /// keyed by the ID of the op which set each of them
+    pub fn get_conflicts(
+        &self,
+        object_id: &amp::ObjectID,
+        prop: &amp::PathElement,
+    ) -> Result<HashMap<amp::OpID, amp::Value>, AutomergeError> {
+        let object_id = self.internal_obj(object_id)?;
+        self.op_set.get_conflicts(&object_id, prop, &self.actors)
+    }
+
+    /// The number of elements in a list or text object, or of keys in a map
+    pub fn length(&self, object_id: &amp::ObjectID) -> Result<usize, AutomergeError> {
+        let object_id = self.internal_obj(object_id)?;
+        self.op_set.length(&object_id)
+    }
+
+    /// The keys of a map in sorted order, or the indexes of a list or text
+    /// object in order, each alongside its value
+    pub fn entries(
+        &self,
+        object_id: &amp::ObjectID,
+    ) -> Result<Vec<(amp::PathElement, amp::Value)>, AutomergeError> {
+        let object_id = self.internal_obj(object_id)?;
+        self.op_set.entries(&object_id, &self.actors)
+    }
+
+    /// The map keys and list indexes which lead from the root object to
+    /// `object_id`, or `None` if the object is no longer reachable because
+    /// it, or an object containing it, has been deleted. Objects which have
+    /// never existed are an error. If the object is one of several
+    /// conflicting values the path leads to the conflict rather than
+    /// necessarily to the value `get_path` returns.
+    pub fn path_of(
+        &self,
+        object_id: &amp::ObjectID,
+    ) -> Result<Option<Vec<amp::PathElement>>, AutomergeError> {
+        let object_id = self.internal_obj(object_id)?;
+        self.op_set.path_of(&object_id)
+    }
+
+    /// Where each of the values at `path` came from, including any
+    /// conflicting values, with the value `get_path` returns first. This is
+    /// empty if there is nothing at `path` or it is the root object.
+    /// Increments to counters are not attributed, only the op which created
+    /// the counter.
+    pub fn blame(&self, path: &[amp::PathElement]) -> Result<Vec<Blame>, AutomergeError> {
+        let (prop, parent) = match path.split_last() {
+            Some(split) => split,
+            None => return Ok(Vec::new()),
+        };
+        let object_id = match self.get_path(parent)? {
+            Some(amp::Value::Object { object_id, .. }) => object_id,
+            _ => return Ok(Vec::new()),
+        };
+        let mut blame = self
+            .get_conflicts(&object_id, prop)?
+            .into_iter()
+            .map(|(op_id, value)| {
+                let change = self
+                    .change_of_op(&op_id)
+                    .ok_or_else(|| AutomergeError::InvalidOpID(op_id.to_string()))?;
+                Ok(Blame {
+                    value,
+                    actor: op_id.1.clone(),
+                    op_id,
+                    hash: change.hash,
+                    time: change.time,
+                    message: change.message(),
+                })
+            })
+            .collect::<Result<Vec<_>, AutomergeError>>()?;
+        blame.sort_by(|a, b| b.op_id.cmp(&a.op_id));
+        Ok(blame)
+    }
+
+    /// The change which contains the op `op_id`
+    fn change_of_op(&self, op_id: &amp::OpID) -> Option<&Change> {
+        let changes = self.states.get(&op_id.1)?;
+        let index = match changes.binary_search_by_key(&op_id.0, |c| c.start_op) {
+            Ok(index) => index,
+            Err(index) => index.checked_sub(1)?,
+        };
+        changes
+            .get(index)
+            .map(|c| c.as_ref())
+            .filter(|c| op_id.0 <= c.max_op())
+    }
+
+    fn internal_obj(&self, object_id: &amp::ObjectID) -> Result<ObjectID, AutomergeError> {
+        match object_id {
+            amp::ObjectID::Root => Ok(ObjectID::Root),
+            amp::ObjectID::ID(amp::OpID(counter, actor)) => self
+                .actors
+                .get(actor)
+                .map(|actor| ObjectID::ID(OpID(*counter, actor)))
+                .ok_or(AutomergeError::MissingObjectError),
+        }
+    }
+
     pub fn get_changes_for_actor_id(
         &self,
         actor_id: &amp::ActorID,
@@ -233,21 +560,123 @@ impl Backend {
             .unwrap_or_default())
     }
 
+    /// The changes which are not `have_deps` or one of their dependencies,
+    /// in the order they were applied. Hashes we don't know are ignored.
     pub fn get_changes(&self, have_deps: &[amp::ChangeHash]) -> Vec<&Change> {
-        let mut stack = have_deps.to_owned();
-        let mut has_seen = HashSet::new();
-        while let Some(hash) = stack.pop() {
-            if let Some(change) = self.hashes.get(&hash) {
-                stack.extend(change.deps.clone());
+        let have = self.clock_of(have_deps);
+        self.changes_after(|actor| {
+            self.actors
+                .get(actor)
+                .map(|actor| have.get(actor))
+                .unwrap_or(0)
+        })
+    }
+
+    /// The changes this backend has applied which `other` has not, in the
+    /// order they were applied
+    pub fn get_changes_added<'a>(&'a self, other: &Backend) -> Vec<&'a Change> {
+        self.changes_after(|actor| {
+            other
+                .states
+                .get(actor)
+                .map(|changes| changes.len() as u64)
+                .unwrap_or(0)
+        })
+    }
+
+    pub fn get_change_by_hash(&self, hash: &amp::ChangeHash) -> Option<&Change> {
+        self.hashes.get(hash).map(|change| change.as_ref())
+    }
+
+    /// Whether `hash` is one of `heads` or one of their ancestors. Use
+    /// `get_heads` for `heads` to ask about the current state.
+    pub fn is_ancestor(&self, hash: &amp::ChangeHash, heads: &[amp::ChangeHash]) -> bool {
+        let clock = self.clock_of(heads);
+        match self.hashes.get(hash) {
+            Some(change) => self.clock_includes(&clock, change),
+            None => false,
+        }
+    }
+
+    /// The heads of the history which `heads1` and `heads2` have in common,
+    /// sorted by hash. This is empty if they have no history in common.
+    pub fn get_common_ancestors(
+        &self,
+        heads1: &[amp::ChangeHash],
+        heads2: &[amp::ChangeHash],
+    ) -> Vec<amp::ChangeHash> {
+        let clock1 = self.clock_of(heads1);
+        let clock2 = self.clock_of(heads2);
+        // The last change from each actor which both include
+        let latest: Vec<&Rc<Change>> = self
+            .states
+            .iter()
+            .filter_map(|(actor, changes)| {
+                let actor = self.actors.get(actor)?;
+                let seq = min(clock1.get(actor), clock2.get(actor));
+                changes.get((seq as usize).checked_sub(1)?)
+            })
+            .collect();
+        let mut common: Vec<amp::ChangeHash> = latest
+            .iter()
+            .filter(|change| {
+                !latest.iter().any(|other| {
+                    other.hash != change.hash
+                        && self.clock_includes(&self.index[&other.hash].1, change)
+                })
+            })
+            .map(|change| change.hash)
+            .collect();
+        common.sort_unstable();
+        common
+    }
+
+    /// The hashes of the changes which list `hash` in their deps, in the
+    /// order they were applied
+    pub fn get_dependents(&self, hash: &amp::ChangeHash) -> Vec<amp::ChangeHash> {
+        self.dependents.get(hash).cloned().unwrap_or_default()
+    }
+
+    /// Every change, each after the changes it depends on. Concurrent
+    /// changes are ordered by hash, so the order depends only on which
+    /// changes have been applied and not on the order they were applied in.
+    pub fn iter_topological(&self) -> impl Iterator<Item = &Change> {
+        TopologicalChanges::new(self)
+    }
+
+    /// The combined clock of the changes in `hashes`, hashes we don't know are
+    /// ignored
+    fn clock_of(&self, hashes: &[amp::ChangeHash]) -> Clock {
+        let mut clock = Clock::default();
+        for hash in hashes {
+            if let Some((_, change_clock)) = self.index.get(hash) {
+                clock.merge(change_clock);
             }
-            has_seen.insert(hash);
         }
-        self.history
+        clock
+    }
+
+    fn clock_includes(&self, clock: &Clock, change: &Change) -> bool {
+        match self.actors.get(change.actor_id()) {
+            Some(actor) => clock.get(actor) >= change.seq,
+            None => false,
+        }
+    }
+
+    /// The changes from each actor with a seq greater than `seen(actor)`, in
+    /// the order they were applied
+    fn changes_after<F>(&self, seen: F) -> Vec<&Change>
+    where
+        F: Fn(&amp::ActorID) -> u64,
+    {
+        let mut changes: Vec<(usize, &Change)> = self
+            .states
             .iter()
-            .filter(|hash| !has_seen.contains(hash))
-            .filter_map(|hash| self.hashes.get(hash))
-            .map(|rc| rc.as_ref())
-            .collect()
+            .flat_map(|(actor, changes)| changes.iter().skip(seen(actor) as usize))
+            .map(|change| (self.index[&change.hash].0, change.as_ref()))
+            .collect();
+        changes.sort_unstable_by_key(|(position, _)| *position);
+        changes.into_iter().map(|(_, change)| change).collect()
     }
 
     pub fn save(&self) -> Result<Vec<u8>, AutomergeError> {
@@ -261,12 +690,31 @@ impl Backend {
     }
 
     pub fn load(data: Vec<u8>) -> Result<Self, AutomergeError> {
-        let changes = Change::parse(&data)?;
+        let changes = Change::parse_owned(data)?;
         let mut backend = Self::init();
         backend.load_changes(changes)?;
         Ok(backend)
     }
 
+    /// Load a saved document, skipping any chunks which are corrupt instead
+    /// of failing. The skipped chunks are returned alongside the backend.
+    /// Changes which depend on a skipped change are held back in the queue,
+    /// see `get_missing_deps`.
+    pub fn load_salvage(data: Vec<u8>) -> Result<(Self, Vec<CorruptChunk>), AutomergeError> {
+        let (changes, corrupt) = salvage_changes(&data);
+        let mut backend = Self::init();
+        backend.load_changes(changes)?;
+        Ok((backend, corrupt))
+    }
+
+    /// Load a saved document from `reader`, applying changes as they are read
+    /// rather than reading the whole document into memory first
+    pub fn load_from_reader<R: Read>(reader: R) -> Result<Self, AutomergeError> {
+        let mut loader = Loader::new();
+        loader.read_from(reader, |_| {})?;
+        loader.finish()
+    }
+
     pub fn get_missing_deps(&self) -> Vec<amp::ChangeHash> {
         let in_queue: Vec<_> = self.queue.iter().map(|change| &change.hash).collect();
         self.queue
@@ -276,3 +724,49 @@ impl Backend {
             .collect()
     }
 }
+
+/// Iterates over the changes in a backend in topological order, taking the
+/// lowest hash whenever more than one change is ready
+struct TopologicalChanges<'a> {
+    backend: &'a Backend,
+    waiting_on: HashMap<amp::ChangeHash, usize>,
+    ready: BinaryHeap<Reverse<amp::ChangeHash>>,
+}
+
+impl<'a> TopologicalChanges<'a> {
+    fn new(backend: &'a Backend) -> TopologicalChanges<'a> {
+        let mut waiting_on = HashMap::new();
+        let mut ready = BinaryHeap::new();
+        for hash in backend.history.iter() {
+            let deps = backend.hashes[hash].deps.len();
+            if deps == 0 {
+                ready.push(Reverse(*hash));
+            } else {
+                waiting_on.insert(*hash, deps);
+            }
+        }
+        TopologicalChanges {
+            backend,
+            waiting_on,
+            ready,
+        }
+    }
+}
+
+impl<'a> Iterator for TopologicalChanges<'a> {
+    type Item = &'a Change;
+
+    fn next(&mut self) -> Option<&'a Change> {
+        let Reverse(hash) = self.ready.pop()?;
+        for dependent in self.backend.dependents.get(&hash).into_iter().flatten() {
+            if let Some(waiting) = self.waiting_on.get_mut(dependent) {
+                *waiting -= 1;
+                if *waiting == 0 {
+                    self.waiting_on.remove(dependent);
+                    self.ready.push(Reverse(*dependent));
+                }
+            }
+        }
+        self.backend.hashes.get(&hash).map(|change| change.as_ref())
+    }
+}
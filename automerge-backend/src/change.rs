@@ -1,8 +1,9 @@
 use crate::columnar;
 use crate::columnar::{
-    ColumnEncoder, KeyIterator, ObjIterator, OperationIterator, PredIterator, ValueIterator,
+    Action, ColumnEncoder, EncodedOps, KeyIterator, ObjIterator, OperationIterator, PredIterator,
+    ValueIterator,
 };
-use crate::encoding::{Decodable, Encodable};
+use crate::encoding::{Decodable, Encodable, RLEDecoder};
 use crate::error::{AutomergeError, InvalidChangeError};
 use automerge_protocol as amp;
 use core::fmt::Debug;
@@ -65,15 +66,20 @@ fn encode(uncompressed_change: &amp::UncompressedChange) -> Change {
     Change {
         bytes,
         hash,
-        body: chunk.body,
         seq: uncompressed_change.seq,
         start_op: uncompressed_change.start_op,
         time: uncompressed_change.time,
-        actors: chunk.actors,
-        message: chunk.message,
         deps,
-        ops: chunk.ops,
-        extra_bytes: chunk.extra_bytes,
+        layout: ChangeLayout {
+            body: chunk.body,
+            op_count: uncompressed_change.operations.len(),
+            actors: chunk.actors,
+            message: chunk.message,
+            ops: chunk.ops,
+            inflated: chunk.inflated,
+            unknown: chunk.unknown,
+            extra_bytes: chunk.extra_bytes,
+        },
     }
 }
 
@@ -83,6 +89,8 @@ struct ChunkIntermediate {
     actors: Vec<amp::ActorID>,
     message: Range<usize>,
     ops: HashMap<u32, Range<usize>>,
+    inflated: HashMap<u32, Vec<u8>>,
+    unknown: Vec<u32>,
     extra_bytes: Range<usize>,
 }
 
@@ -119,8 +127,16 @@ fn encode_chunk(
     let message = message..bytes.len();
 
     // encode ops into a side buffer - collect all other actors
-    let (ops_buf, mut ops) =
-        ColumnEncoder::encode_ops(uncompressed_change.operations.iter(), &mut actors);
+    let EncodedOps {
+        bytes: ops_buf,
+        ranges: mut ops,
+        inflated,
+        unknown,
+    } = ColumnEncoder::encode_ops(
+        uncompressed_change.operations.iter(),
+        &uncompressed_change.unknown_columns,
+        &mut actors,
+    );
 
     // encode all other actors
     actors[1..].encode(&mut bytes).unwrap();
@@ -143,43 +159,331 @@ fn encode_chunk(
         actors,
         message,
         ops,
+        inflated,
+        unknown,
         extra_bytes,
     }
 }
 
+/// The location of each part of a change within its bytes, along with the
+/// actors the ops refer to and the number of ops. Columns are keyed by their
+/// ID without the deflate flag, and compressed columns we know how to
+/// interpret are kept uncompressed in `inflated` as well as in their original
+/// form in the bytes. `unknown` holds the IDs, with the deflate flag, of the
+/// columns we don't know how to interpret.
 #[derive(PartialEq, Debug, Clone)]
-pub struct Change {
-    pub bytes: Vec<u8>,
-    pub hash: amp::ChangeHash,
-    pub seq: u64,
-    pub start_op: u64,
-    pub time: i64,
+struct ChangeLayout {
     body: Range<usize>,
+    op_count: usize,
     message: Range<usize>,
     actors: Vec<amp::ActorID>,
-    pub deps: Vec<amp::ChangeHash>,
     ops: HashMap<u32, Range<usize>>,
+    inflated: HashMap<u32, Vec<u8>>,
+    unknown: Vec<u32>,
     extra_bytes: Range<usize>,
 }
 
-impl Change {
+impl ChangeLayout {
+    fn message(&self, bytes: &[u8]) -> Option<String> {
+        let m = &bytes[self.message.clone()];
+        if m.is_empty() {
+            None
+        } else {
+            str::from_utf8(m).map(|s| s.to_string()).ok()
+        }
+    }
+
+    /// Count the ops in the ops columns of `bytes` by decoding only the
+    /// action column
+    fn count_ops(&self, bytes: &[u8]) -> usize {
+        self.col_iter::<RLEDecoder<Action>>(bytes, columnar::COL_ACTION)
+            .take_while(Option::is_some)
+            .count()
+    }
+
+    fn unknown_columns(&self, bytes: &[u8]) -> Vec<amp::UnknownColumn> {
+        self.unknown
+            .iter()
+            .map(|id| amp::UnknownColumn {
+                id: *id,
+                data: self
+                    .ops
+                    .get(&(id & !columnar::COLUMN_TYPE_DEFLATE))
+                    .map(|r| bytes[r.clone()].to_vec())
+                    .unwrap_or_default(),
+            })
+            .collect()
+    }
+
+    fn col_iter<'a, T>(&'a self, bytes: &'a [u8], col_id: u32) -> T
+    where
+        T: From<&'a [u8]>,
+    {
+        if let Some(data) = self.inflated.get(&col_id) {
+            return T::from(data.as_slice());
+        }
+        self.ops
+            .get(&col_id)
+            .map(|r| T::from(&bytes[r.clone()]))
+            .unwrap_or_else(|| T::from(&[] as &[u8]))
+    }
+
+    fn iter_ops<'a>(&'a self, bytes: &'a [u8]) -> OperationIterator<'a> {
+        OperationIterator {
+            objs: ObjIterator {
+                actors: &self.actors,
+                actor: self.col_iter(bytes, columnar::COL_OBJ_ACTOR),
+                ctr: self.col_iter(bytes, columnar::COL_OBJ_CTR),
+            },
+            keys: KeyIterator {
+                actors: &self.actors,
+                actor: self.col_iter(bytes, columnar::COL_KEY_ACTOR),
+                ctr: self.col_iter(bytes, columnar::COL_KEY_CTR),
+                str: self.col_iter(bytes, columnar::COL_KEY_STR),
+            },
+            value: ValueIterator {
+                val_len: self.col_iter(bytes, columnar::COL_VAL_LEN),
+                val_raw: self.col_iter(bytes, columnar::COL_VAL_RAW),
+            },
+            pred: PredIterator {
+                actors: &self.actors,
+                pred_num: self.col_iter(bytes, columnar::COL_PRED_NUM),
+                pred_actor: self.col_iter(bytes, columnar::COL_PRED_ACTOR),
+                pred_ctr: self.col_iter(bytes, columnar::COL_PRED_CTR),
+            },
+            insert: self.col_iter(bytes, columnar::COL_INSERT),
+            action: self.col_iter(bytes, columnar::COL_ACTION),
+        }
+    }
+
+    fn column_ranges(&self) -> Vec<(u32, Range<usize>)> {
+        let mut columns: Vec<_> = self.ops.iter().map(|(k, v)| (*k, v.clone())).collect();
+        columns.sort_by_key(|(id, _)| *id);
+        columns
+    }
+}
+
+fn checksum(bytes: &[u8]) -> [u8; 4] {
+    let mut checksum = [0; 4];
+    checksum.copy_from_slice(&bytes[HASH_RANGE]);
+    checksum
+}
+
+/// A change along with its encoded bytes. This is used through `Change`,
+/// which owns its bytes, and `ChangeRef`, which borrows them, so that both
+/// share the same accessors.
+#[derive(PartialEq, Debug, Clone)]
+pub struct EncodedChange<B> {
+    pub bytes: B,
+    pub hash: amp::ChangeHash,
+    pub seq: u64,
+    pub start_op: u64,
+    pub time: i64,
+    pub deps: Vec<amp::ChangeHash>,
+    layout: ChangeLayout,
+}
+
+/// A change which owns its bytes
+pub type Change = EncodedChange<Vec<u8>>;
+
+/// A change which borrows its bytes from a larger buffer, such as a memory
+/// mapped file, instead of owning a copy of them. Convert it into a `Change`
+/// only if it needs to outlive the buffer.
+pub type ChangeRef<'a> = EncodedChange<&'a [u8]>;
+
+impl<B: AsRef<[u8]>> EncodedChange<B> {
     pub fn actor_id(&self) -> &amp::ActorID {
-        &self.actors[0]
+        &self.layout.actors[0]
+    }
+
+    /// The number of ops in this change, this is counted when the change is
+    /// created or parsed so it does not decode the ops
+    pub fn op_count(&self) -> usize {
+        self.layout.op_count
+    }
+
+    pub fn max_op(&self) -> u64 {
+        self.start_op + (self.op_count() as u64) - 1
+    }
+
+    pub fn message(&self) -> Option<String> {
+        self.layout.message(self.bytes.as_ref())
+    }
+
+    pub fn decode(&self) -> amp::UncompressedChange {
+        amp::UncompressedChange {
+            start_op: self.start_op,
+            seq: self.seq,
+            time: self.time,
+            message: self.message(),
+            actor_id: self.actor_id().clone(),
+            deps: self.deps.clone(),
+            operations: self.iter_ops().collect(),
+            extra_bytes: self.extra_bytes().into(),
+            unknown_columns: self.layout.unknown_columns(self.bytes.as_ref()),
+        }
+    }
+
+    pub fn iter_ops(&self) -> OperationIterator<'_> {
+        self.layout.iter_ops(self.bytes.as_ref())
+    }
+
+    pub fn extra_bytes(&self) -> &[u8] {
+        &self.bytes.as_ref()[self.layout.extra_bytes.clone()]
+    }
+
+    /// The checksum stored in the header of this change
+    pub fn checksum(&self) -> [u8; 4] {
+        checksum(self.bytes.as_ref())
+    }
+
+    /// Whether the checksum in the header matches the hash of the change. This
+    /// is always true unless the change was decoded with
+    /// `ChangeRef::from_bytes_unverified`, as decoding otherwise checks it.
+    pub fn checksum_is_valid(&self) -> bool {
+        self.checksum() == self.hash.0[0..4]
     }
 
+    /// The IDs of the op columns in this change along with the range of
+    /// `bytes` which each column occupies, ordered by column ID. The IDs do
+    /// not include the flag marking compressed columns, and columns written
+    /// in the legacy layout are given their current IDs.
+    pub fn column_ranges(&self) -> Vec<(u32, Range<usize>)> {
+        self.layout.column_ranges()
+    }
+
+    /// The IDs of the op columns in this change which are compressed with
+    /// DEFLATE, without the flag marking them, in ascending order
+    pub fn compressed_columns(&self) -> Vec<u32> {
+        let mut columns: Vec<_> = self.layout.inflated.keys().cloned().collect();
+        columns.sort_unstable();
+        columns
+    }
+}
+
+impl Change {
     pub fn parse(bytes: &[u8]) -> Result<Vec<Change>, AutomergeError> {
-        let mut changes = Vec::new();
-        let mut cursor = &bytes[..];
-        while !cursor.is_empty() {
-            let (val, len) = read_leb128(&mut &cursor[HEADER_BYTES..])?;
-            let (data, rest) = cursor.split_at(HEADER_BYTES + val + len);
-            changes.push(Self::from_bytes(data.to_vec())?);
-            cursor = rest;
+        Self::parse_chunks(bytes)
+            .into_iter()
+            .map(|(_, change)| change)
+            .collect()
+    }
+
+    /// Split `bytes` into chunks and decode each of them, returning the byte
+    /// offset at which each chunk starts alongside the result of decoding it.
+    /// See `ChangeRef::parse_chunks`.
+    pub fn parse_chunks(bytes: &[u8]) -> Vec<(usize, Result<Change, AutomergeError>)> {
+        ChangeRef::parse_chunks(bytes)
+            .into_iter()
+            .map(|(offset, change)| (offset, change.map(Change::from)))
+            .collect()
+    }
+
+    /// Like `parse`, but takes ownership of `bytes`. When `bytes` hold a
+    /// single chunk the change keeps them without copying, otherwise each
+    /// chunk is copied out once and `bytes` are freed when this returns.
+    pub fn parse_owned(bytes: Vec<u8>) -> Result<Vec<Change>, AutomergeError> {
+        match chunk_len(&bytes) {
+            Ok(Some(len)) if len == bytes.len() => Change::from_bytes(bytes).map(|c| vec![c]),
+            _ => Change::parse(&bytes),
         }
-        Ok(changes)
     }
 
+    /// Decode a single change, taking ownership of `bytes` without copying
+    /// them
     pub fn from_bytes(bytes: Vec<u8>) -> Result<Change, AutomergeError> {
+        let ChangeRef {
+            bytes: _,
+            hash,
+            seq,
+            start_op,
+            time,
+            deps,
+            layout,
+        } = ChangeRef::from_bytes(&bytes)?;
+        Ok(Change {
+            bytes,
+            hash,
+            seq,
+            start_op,
+            time,
+            deps,
+            layout,
+        })
+    }
+}
+
+impl<'a> ChangeRef<'a> {
+    pub fn parse(bytes: &'a [u8]) -> Result<Vec<ChangeRef<'a>>, AutomergeError> {
+        Self::parse_chunks(bytes)
+            .into_iter()
+            .map(|(_, change)| change)
+            .collect()
+    }
+
+    /// Split `bytes` into chunks and decode each of them, returning the byte
+    /// offset at which each chunk starts alongside the result of decoding it.
+    ///
+    /// A chunk which fails to decode does not prevent the following chunks
+    /// from being decoded. If the length of a chunk cannot be determined then
+    /// the error is reported at the offset of that chunk and parsing stops.
+    pub fn parse_chunks(bytes: &'a [u8]) -> Vec<(usize, Result<ChangeRef<'a>, AutomergeError>)> {
+        Self::split_chunks(bytes, true)
+    }
+
+    /// Like `parse_chunks`, but decodes chunks whose checksum does not match
+    /// their hash rather than failing, see `from_bytes_unverified`.
+    pub fn parse_chunks_unverified(
+        bytes: &'a [u8],
+    ) -> Vec<(usize, Result<ChangeRef<'a>, AutomergeError>)> {
+        Self::split_chunks(bytes, false)
+    }
+
+    fn split_chunks(
+        bytes: &'a [u8],
+        verify: bool,
+    ) -> Vec<(usize, Result<ChangeRef<'a>, AutomergeError>)> {
+        let mut chunks = Vec::new();
+        let mut offset = 0;
+        while offset < bytes.len() {
+            let cursor = &bytes[offset..];
+            let chunk_len = if cursor.len() <= HEADER_BYTES {
+                None
+            } else {
+                read_leb128(&mut &cursor[HEADER_BYTES..])
+                    .ok()
+                    .and_then(|(val, len)| (HEADER_BYTES + len).checked_add(val))
+                    .filter(|chunk_len| *chunk_len <= cursor.len())
+            };
+            match chunk_len {
+                Some(chunk_len) => {
+                    let data = &cursor[..chunk_len];
+                    let change =
+                        Self::decode_chunk(data, verify).map_err(|err| err.at_offset(offset));
+                    chunks.push((offset, change));
+                    offset += chunk_len;
+                }
+                None => {
+                    chunks.push((offset, Err(AutomergeError::EncodingError)));
+                    break;
+                }
+            }
+        }
+        chunks
+    }
+
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<ChangeRef<'a>, AutomergeError> {
+        Self::decode_chunk(bytes, true)
+    }
+
+    /// Decode a single change without checking that the checksum in its
+    /// header matches its hash, for inspecting corrupt files. Use
+    /// `checksum_is_valid` to find out whether it does.
+    pub fn from_bytes_unverified(bytes: &'a [u8]) -> Result<ChangeRef<'a>, AutomergeError> {
+        Self::decode_chunk(bytes, false)
+    }
+
+    fn decode_chunk(bytes: &'a [u8], verify: bool) -> Result<ChangeRef<'a>, AutomergeError> {
         if bytes.len() <= HEADER_BYTES {
             return Err(AutomergeError::EncodingError);
         }
@@ -189,7 +493,11 @@ impl Change {
         }
 
         let (val, len) = read_leb128(&mut &bytes[HEADER_BYTES..])?;
-        let body = (HEADER_BYTES + len)..(HEADER_BYTES + len + val);
+        let body_start = HEADER_BYTES + len;
+        let body_end = body_start
+            .checked_add(val)
+            .ok_or(AutomergeError::LengthOutOfRange { offset: 0 })?;
+        let body = body_start..body_end;
         if bytes.len() != body.end {
             return Err(AutomergeError::EncodingError);
         }
@@ -206,141 +514,122 @@ impl Change {
 
         let mut hasher = Sha256::new();
         hasher.input(&bytes[PREAMBLE_BYTES..]);
-        let hash = hasher.result()[..]
+        let hash_result = hasher.result();
+        if verify && bytes[HASH_RANGE] != hash_result[0..4] {
+            return Err(AutomergeError::ChecksumMismatch { offset: 0 });
+        }
+        let hash = hash_result[..]
             .try_into()
             .map_err(InvalidChangeError::from)?;
 
         let mut cursor = body.clone();
         let mut deps = Vec::new();
-        let num_deps = read_slice(&bytes, &mut cursor)?;
+        let num_deps = read_slice(bytes, &mut cursor)?;
         for _ in 0..num_deps {
-            let hash = cursor.start..(cursor.start + HASH_BYTES);
-            cursor = hash.end..cursor.end;
-            //let hash = slice_n_bytes(bytes, HASH_BYTES)?;
+            let hash = take_bytes(&mut cursor, HASH_BYTES)?;
             deps.push(bytes[hash].try_into().map_err(InvalidChangeError::from)?);
         }
-        let actor = amp::ActorID::from(&bytes[slice_bytes(&bytes, &mut cursor)?]);
-        let seq = read_slice(&bytes, &mut cursor)?;
-        let start_op = read_slice(&bytes, &mut cursor)?;
-        let time = read_slice(&bytes, &mut cursor)?;
-        let message = slice_bytes(&bytes, &mut cursor)?;
-        let num_actors = read_slice(&bytes, &mut cursor)?;
+        let actor = amp::ActorID::from(&bytes[slice_bytes(bytes, &mut cursor)?]);
+        let seq = read_slice(bytes, &mut cursor)?;
+        let start_op = read_slice(bytes, &mut cursor)?;
+        let time = read_slice(bytes, &mut cursor)?;
+        let message = slice_bytes(bytes, &mut cursor)?;
+        let num_actors = read_slice(bytes, &mut cursor)?;
         let mut actors = vec![actor];
         for _ in 0..num_actors {
-            actors.push(amp::ActorID::from(
-                &bytes[slice_bytes(&bytes, &mut cursor)?],
-            ));
+            actors.push(amp::ActorID::from(&bytes[slice_bytes(bytes, &mut cursor)?]));
         }
 
-        let num_columns = read_slice(&bytes, &mut cursor)?;
-        let mut columns = Vec::with_capacity(num_columns);
-        let mut last_id = 0;
+        let num_columns = read_slice(bytes, &mut cursor)?;
+        let mut columns = Vec::new();
         for _ in 0..num_columns {
-            let id: u32 = read_slice(&bytes, &mut cursor)?;
-            if id <= last_id {
+            let id: u32 = read_slice(bytes, &mut cursor)?;
+            let length = read_slice(bytes, &mut cursor)?;
+            columns.push((id, length));
+        }
+        let ids: Vec<u32> = columns.iter().map(|(id, _)| *id).collect();
+        if columnar::is_legacy_layout(&ids) {
+            for (id, _) in columns.iter_mut() {
+                *id = columnar::from_legacy_column(*id);
+            }
+        }
+        let mut last_id = 0;
+        for (id, _) in columns.iter() {
+            if id & !columnar::COLUMN_TYPE_DEFLATE <= last_id & !columnar::COLUMN_TYPE_DEFLATE {
                 return Err(AutomergeError::EncodingError);
             }
-            last_id = id;
-            let length = read_slice(&bytes, &mut cursor)?;
-            columns.push((id, length));
+            last_id = *id;
+        }
+        // Without these any columns would be kept as unknown and the change
+        // would decode with no ops, as changes in an unrecognised layout did
+        let ids: Vec<u32> = columns
+            .iter()
+            .map(|(id, _)| id & !columnar::COLUMN_TYPE_DEFLATE)
+            .collect();
+        if !columnar::has_required_columns(&ids) {
+            return Err(AutomergeError::MissingColumns { offset: 0 });
         }
 
         let mut ops = HashMap::new();
+        let mut inflated = HashMap::new();
+        let mut unknown = Vec::new();
         for (id, length) in columns.iter() {
-            let start = cursor.start;
-            let end = start + length;
-            cursor = end..cursor.end;
-            ops.insert(*id, start..end);
+            let range = take_bytes(&mut cursor, *length)?;
+            let col = id & !columnar::COLUMN_TYPE_DEFLATE;
+            if !columnar::is_known_column(*id) {
+                unknown.push(*id);
+            } else if id & columnar::COLUMN_TYPE_DEFLATE != 0 {
+                inflated.insert(col, columnar::inflate(&bytes[range.clone()])?);
+            }
+            ops.insert(col, range);
         }
 
-        Ok(Change {
+        let mut layout = ChangeLayout {
+            body,
+            op_count: 0,
+            actors,
+            message,
+            ops,
+            inflated,
+            unknown,
+            extra_bytes: cursor,
+        };
+        layout.op_count = layout.count_ops(bytes);
+
+        Ok(ChangeRef {
             bytes,
             hash,
-            body,
             seq,
             start_op,
             time,
-            actors,
-            message,
             deps,
-            ops,
-            extra_bytes: cursor,
+            layout,
         })
     }
 
-    pub fn max_op(&self) -> u64 {
-        // TODO - this could be a lot more efficient
-        let len = self.iter_ops().count();
-        self.start_op + (len as u64) - 1
-    }
-
-    fn message(&self) -> Option<String> {
-        let m = &self.bytes[self.message.clone()];
-        if m.is_empty() {
-            None
-        } else {
-            str::from_utf8(&m).map(|s| s.to_string()).ok()
-        }
-    }
-
-    pub fn decode(&self) -> amp::UncompressedChange {
-        amp::UncompressedChange {
-            start_op: self.start_op,
-            seq: self.seq,
-            time: self.time,
-            message: self.message(),
-            actor_id: self.actors[0].clone(),
-            deps: self.deps.clone(),
-            operations: self.iter_ops().collect(),
-            extra_bytes: self.extra_bytes().into(),
-        }
-    }
-
-    fn col_iter<'a, T>(&'a self, col_id: u32) -> T
-    where
-        T: From<&'a [u8]>,
-    {
-        self.ops
-            .get(&col_id)
-            .map(|r| T::from(&self.bytes[r.clone()]))
-            .unwrap_or_else(|| T::from(&[] as &[u8]))
+    /// Copy the bytes of this change so that it no longer borrows the buffer
+    /// it was parsed from
+    pub fn to_change(&self) -> Change {
+        self.clone().into()
     }
+}
 
-    pub fn iter_ops(&self) -> OperationIterator {
-        OperationIterator {
-            objs: ObjIterator {
-                actors: &self.actors,
-                actor: self.col_iter(columnar::COL_OBJ_ACTOR),
-                ctr: self.col_iter(columnar::COL_OBJ_CTR),
-            },
-            keys: KeyIterator {
-                actors: &self.actors,
-                actor: self.col_iter(columnar::COL_KEY_ACTOR),
-                ctr: self.col_iter(columnar::COL_KEY_CTR),
-                str: self.col_iter(columnar::COL_KEY_STR),
-            },
-            value: ValueIterator {
-                val_len: self.col_iter(columnar::COL_VAL_LEN),
-                val_raw: self.col_iter(columnar::COL_VAL_RAW),
-            },
-            pred: PredIterator {
-                actors: &self.actors,
-                pred_num: self.col_iter(columnar::COL_PRED_NUM),
-                pred_actor: self.col_iter(columnar::COL_PRED_ACTOR),
-                pred_ctr: self.col_iter(columnar::COL_PRED_CTR),
-            },
-            insert: self.col_iter(columnar::COL_INSERT),
-            action: self.col_iter(columnar::COL_ACTION),
+impl<'a> From<ChangeRef<'a>> for Change {
+    fn from(change: ChangeRef<'a>) -> Change {
+        Change {
+            bytes: change.bytes.to_vec(),
+            hash: change.hash,
+            seq: change.seq,
+            start_op: change.start_op,
+            time: change.time,
+            deps: change.deps,
+            layout: change.layout,
         }
     }
-
-    pub fn extra_bytes(&self) -> &[u8] {
-        &self.bytes[self.extra_bytes.clone()]
-    }
 }
 
-impl From<&Change> for amp::UncompressedChange {
-    fn from(change: &Change) -> amp::UncompressedChange {
+impl<B: AsRef<[u8]>> From<&EncodedChange<B>> for amp::UncompressedChange {
+    fn from(change: &EncodedChange<B>) -> amp::UncompressedChange {
         change.decode()
     }
 }
@@ -348,7 +637,33 @@ impl From<&Change> for amp::UncompressedChange {
 impl TryFrom<&[u8]> for Change {
     type Error = AutomergeError;
     fn try_from(bytes: &[u8]) -> Result<Self, AutomergeError> {
-        Change::from_bytes(bytes.to_vec())
+        ChangeRef::from_bytes(bytes).map(Change::from)
+    }
+}
+
+/// The total length of the chunk at the start of `bytes`, or `None` if `bytes`
+/// does not yet contain enough of the chunk header to tell. The returned
+/// length may be longer than `bytes`.
+pub(crate) fn chunk_len(bytes: &[u8]) -> Result<Option<usize>, AutomergeError> {
+    let magic_len = bytes.len().min(MAGIC_BYTES.len());
+    if bytes[..magic_len] != MAGIC_BYTES[..magic_len] {
+        return Err(AutomergeError::EncodingError);
+    }
+    if bytes.len() <= HEADER_BYTES {
+        return Ok(None);
+    }
+    let mut cursor = &bytes[HEADER_BYTES..];
+    match leb128::read::unsigned(&mut cursor) {
+        Ok(val) => {
+            let header_len = bytes.len() - cursor.len();
+            (val as usize)
+                .checked_add(header_len)
+                .map(Some)
+                .ok_or(AutomergeError::EncodingError)
+        }
+        // The length is cut off part way through
+        Err(leb128::read::Error::IoError(_)) => Ok(None),
+        Err(leb128::read::Error::Overflow) => Err(AutomergeError::EncodingError),
     }
 }
 
@@ -363,7 +678,9 @@ fn read_slice<T: Decodable + Debug>(
     bytes: &[u8],
     cursor: &mut Range<usize>,
 ) -> Result<T, AutomergeError> {
-    let view = &bytes[cursor.clone()];
+    let view = bytes
+        .get(cursor.clone())
+        .ok_or(AutomergeError::LengthOutOfRange { offset: 0 })?;
     let mut reader = &view[..];
     let val = T::decode::<&[u8]>(&mut reader).ok_or(AutomergeError::EncodingError);
     let len = view.len() - reader.len();
@@ -372,11 +689,26 @@ fn read_slice<T: Decodable + Debug>(
 }
 
 fn slice_bytes(bytes: &[u8], cursor: &mut Range<usize>) -> Result<Range<usize>, AutomergeError> {
-    let (val, len) = read_leb128(&mut &bytes[cursor.clone()])?;
-    let start = cursor.start + len;
-    let end = start + val;
+    let view = bytes
+        .get(cursor.clone())
+        .ok_or(AutomergeError::LengthOutOfRange { offset: 0 })?;
+    let (val, len) = read_leb128(&mut &view[..])?;
+    cursor.start += len;
+    take_bytes(cursor, val)
+}
+
+/// The range of the `len` bytes at the start of `cursor`, which is moved past
+/// them. A length which runs past the end of `cursor` is an error rather than
+/// a panic, as it comes from the data being decoded.
+fn take_bytes(cursor: &mut Range<usize>, len: usize) -> Result<Range<usize>, AutomergeError> {
+    let end = cursor
+        .start
+        .checked_add(len)
+        .filter(|end| *end <= cursor.end)
+        .ok_or(AutomergeError::LengthOutOfRange { offset: 0 })?;
+    let range = cursor.start..end;
     *cursor = end..cursor.end;
-    Ok(start..end)
+    Ok(range)
 }
 
 fn increment_range(range: &mut Range<usize>, len: usize) {
@@ -399,6 +731,20 @@ mod tests {
     use super::*;
     use std::str::FromStr;
 
+    fn test_change(seq: u64) -> Change {
+        Change::from(amp::UncompressedChange {
+            start_op: seq,
+            seq,
+            time: 0,
+            message: Some(format!("change {}", seq)),
+            actor_id: amp::ActorID::from_str("deadbeefdeadbeef").unwrap(),
+            deps: vec![],
+            operations: vec![],
+            extra_bytes: vec![],
+            unknown_columns: Vec::new(),
+        })
+    }
+
     #[test]
     fn test_empty_change() {
         let change1 = amp::UncompressedChange {
@@ -410,6 +756,7 @@ mod tests {
             deps: vec![],
             operations: vec![],
             extra_bytes: vec![1, 1, 1],
+            unknown_columns: Vec::new(),
         };
         let bin1: Change = change1.clone().try_into().unwrap();
         let change2 = bin1.decode();
@@ -518,6 +865,7 @@ mod tests {
                 },
             ],
             extra_bytes: vec![1, 2, 3],
+            unknown_columns: Vec::new(),
         };
         let bin1 = Change::try_from(change1.clone()).unwrap();
         let change2 = bin1.decode();
@@ -525,4 +873,270 @@ mod tests {
         assert_eq!(bin1, bin2);
         assert_eq!(change1, change2);
     }
+
+    #[test]
+    fn test_parse_chunks_reports_offsets() {
+        let change1 = test_change(1);
+        let change2 = test_change(2);
+        let mut bytes = change1.bytes.clone();
+        bytes[0] = 0; // corrupt the magic bytes of the first chunk
+        bytes.extend(&change2.bytes);
+        bytes.extend(&change1.bytes[0..change1.bytes.len() - 1]);
+
+        let chunks = Change::parse_chunks(&bytes);
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[0].0, 0);
+        assert_eq!(chunks[0].1, Err(AutomergeError::EncodingError));
+        assert_eq!(chunks[1], (change1.bytes.len(), Ok(change2.clone())));
+        assert_eq!(chunks[2].0, change1.bytes.len() + change2.bytes.len());
+        assert_eq!(chunks[2].1, Err(AutomergeError::EncodingError));
+        assert!(Change::parse(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_out_of_range_lengths_are_errors() {
+        let change = test_change(1);
+        let offset = change.bytes.len();
+
+        // A chunk length which overflows when the header is added to it
+        let mut oversized = change.bytes[..HEADER_BYTES].to_vec();
+        oversized.extend(&[0xff; 9]);
+        oversized.push(0x01);
+        assert_eq!(
+            ChangeRef::from_bytes(&oversized),
+            Err(AutomergeError::LengthOutOfRange { offset: 0 })
+        );
+        let mut data = change.bytes.clone();
+        data.extend(&oversized);
+        let chunks = ChangeRef::parse_chunks_unverified(&data);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[1], (offset, Err(AutomergeError::EncodingError)));
+
+        // More deps than there are bytes left in the chunk
+        let mut truncated_deps = change.bytes.clone();
+        truncated_deps[change.layout.body.start] = 5;
+        assert_eq!(
+            ChangeRef::from_bytes_unverified(&truncated_deps),
+            Err(AutomergeError::LengthOutOfRange { offset: 0 })
+        );
+        let mut data = change.bytes.clone();
+        data.extend(&truncated_deps);
+        assert_eq!(
+            ChangeRef::parse_chunks_unverified(&data)[1].1,
+            Err(AutomergeError::LengthOutOfRange { offset })
+        );
+    }
+
+    #[test]
+    fn test_parse_owned_matches_parse() {
+        let changes = vec![test_change(1), test_change(2), test_change(3)];
+        let bytes: Vec<u8> = changes.iter().flat_map(|c| c.bytes.clone()).collect();
+        assert_eq!(Change::parse_owned(bytes.clone()), Ok(changes.clone()));
+        assert_eq!(Change::parse_owned(Vec::new()), Ok(Vec::new()));
+
+        // A single chunk keeps the buffer it was given
+        let single = changes[0].bytes.clone();
+        let ptr = single.as_ptr();
+        let parsed = Change::parse_owned(single).unwrap();
+        assert_eq!(parsed, vec![changes[0].clone()]);
+        assert_eq!(parsed[0].bytes.as_ptr(), ptr);
+
+        // Errors are the same as `parse` reports, the first one in the data
+        let mut corrupt = bytes.clone();
+        let second_last = changes[0].bytes.len() + changes[1].bytes.len() - 1;
+        corrupt[second_last] ^= 1;
+        corrupt.truncate(corrupt.len() - 1);
+        assert_eq!(
+            Change::parse_owned(corrupt.clone()),
+            Change::parse(&corrupt)
+        );
+        assert_eq!(
+            Change::parse_owned(corrupt),
+            Err(AutomergeError::ChecksumMismatch {
+                offset: changes[0].bytes.len()
+            })
+        );
+        let truncated = bytes[..bytes.len() - 1].to_vec();
+        assert_eq!(
+            Change::parse_owned(truncated.clone()),
+            Change::parse(&truncated)
+        );
+    }
+
+    #[test]
+    fn test_checksum_mismatch_reports_chunk_offset() {
+        let change = test_change(1);
+        let mut corrupt = change.bytes.clone();
+        let last = corrupt.len() - 1;
+        corrupt[last] ^= 1;
+        assert_eq!(
+            Change::from_bytes(corrupt.clone()),
+            Err(AutomergeError::ChecksumMismatch { offset: 0 })
+        );
+
+        let mut bytes = change.bytes.clone();
+        bytes.extend(&corrupt);
+        assert_eq!(
+            Change::parse(&bytes),
+            Err(AutomergeError::ChecksumMismatch {
+                offset: change.bytes.len()
+            })
+        );
+    }
+
+    #[test]
+    fn test_change_ref_borrows_its_bytes() {
+        let change = |seq, deps| {
+            Change::from(amp::UncompressedChange {
+                deps,
+                operations: vec![amp::Op {
+                    action: amp::OpType::Set(amp::ScalarValue::Uint(seq)),
+                    obj: amp::ObjectID::Root,
+                    key: "bird".into(),
+                    insert: false,
+                    pred: Vec::new(),
+                }],
+                ..test_change(seq).decode()
+            })
+        };
+        let change1 = change(1, vec![]);
+        let change2 = change(2, vec![change1.hash]);
+        let bytes = [change1.bytes.clone(), change2.bytes.clone()].concat();
+
+        let refs = ChangeRef::parse(&bytes).unwrap();
+        assert_eq!(refs.len(), 2);
+        assert!(std::ptr::eq(refs[0].bytes, &bytes[..change1.bytes.len()]));
+        assert!(std::ptr::eq(refs[1].bytes, &bytes[change1.bytes.len()..]));
+        for (change_ref, change) in refs.iter().zip(&[change1, change2]) {
+            assert_eq!(change_ref.hash, change.hash);
+            assert_eq!(change_ref.deps, change.deps);
+            assert_eq!(change_ref.max_op(), change.max_op());
+            assert_eq!(change_ref.decode(), change.decode());
+            assert_eq!(
+                change_ref.iter_ops().collect::<Vec<_>>(),
+                change.iter_ops().collect::<Vec<_>>()
+            );
+            assert_eq!(&change_ref.to_change(), change);
+        }
+    }
+
+    #[test]
+    fn test_op_count_matches_decoded_ops() {
+        let operations: Vec<amp::Op> = (0..5)
+            .map(|i| amp::Op {
+                action: if i == 3 {
+                    amp::OpType::Del
+                } else {
+                    amp::OpType::Set(amp::ScalarValue::Int(i))
+                },
+                obj: amp::ObjectID::Root,
+                key: format!("key{}", i).as_str().into(),
+                insert: false,
+                pred: Vec::new(),
+            })
+            .collect();
+        let change = Change::from(amp::UncompressedChange {
+            start_op: 7,
+            operations,
+            ..test_change(1).decode()
+        });
+        assert_eq!(change.op_count(), 5);
+        assert_eq!(change.max_op(), 11);
+
+        let parsed = Change::from_bytes(change.bytes.clone()).unwrap();
+        assert_eq!(parsed.op_count(), change.iter_ops().count());
+        assert_eq!(parsed.max_op(), 11);
+        assert_eq!(parsed, change);
+    }
+
+    #[test]
+    fn test_unknown_columns_and_actions_round_trip() {
+        let unknown_op = |action, value_type, value: Vec<u8>| amp::Op {
+            action: amp::OpType::Unknown {
+                action,
+                value_type,
+                value,
+            },
+            obj: amp::ObjectID::Root,
+            key: "key".into(),
+            insert: false,
+            pred: Vec::new(),
+        };
+        let change = amp::UncompressedChange {
+            start_op: 1,
+            seq: 1,
+            time: 0,
+            message: None,
+            actor_id: amp::ActorID::from_str("deadbeefdeadbeef").unwrap(),
+            deps: vec![],
+            operations: vec![
+                // An unknown action with a value of a known type
+                unknown_op(20, 3, vec![5]),
+                // A set of a value of an unknown type
+                unknown_op(1, 12, vec![1, 2, 3]),
+                amp::Op {
+                    action: amp::OpType::Set(amp::ScalarValue::Uint(5)),
+                    obj: amp::ObjectID::Root,
+                    key: "key".into(),
+                    insert: false,
+                    pred: Vec::new(),
+                },
+            ],
+            extra_bytes: vec![],
+            unknown_columns: vec![
+                amp::UnknownColumn {
+                    id: 9 << 4 | columnar::COLUMN_TYPE_INT_RLE,
+                    data: vec![3, 1],
+                },
+                // Not valid DEFLATE data, so this fails if we try to inflate it
+                amp::UnknownColumn {
+                    id: 10 << 4 | columnar::COLUMN_TYPE_INT_RLE | columnar::COLUMN_TYPE_DEFLATE,
+                    data: vec![0xff, 0xff],
+                },
+            ],
+        };
+        let bin1 = Change::from(change.clone());
+        let parsed = Change::from_bytes(bin1.bytes.clone()).unwrap();
+        assert_eq!(parsed.op_count(), 3);
+        assert_eq!(parsed.decode(), change);
+        assert!(parsed
+            .column_ranges()
+            .iter()
+            .any(|(id, _)| *id == 10 << 4 | columnar::COLUMN_TYPE_INT_RLE));
+
+        let bin2 = Change::from(parsed.decode());
+        assert_eq!(bin2.bytes, bin1.bytes);
+        assert_eq!(bin2.hash, bin1.hash);
+    }
+
+    #[test]
+    fn test_unknown_columns_without_an_action_column_are_an_error() {
+        // The key and action columns as they were numbered in the legacy
+        // layout, but without the rest of it
+        let change = amp::UncompressedChange {
+            start_op: 1,
+            seq: 1,
+            time: 0,
+            message: None,
+            actor_id: amp::ActorID::from_str("deadbeefdeadbeef").unwrap(),
+            deps: vec![],
+            operations: vec![],
+            extra_bytes: vec![],
+            unknown_columns: vec![
+                amp::UnknownColumn {
+                    id: 1 << 3 | columnar::COLUMN_TYPE_STRING_RLE,
+                    data: vec![0x7f, 3, b'k', b'e', b'y'],
+                },
+                amp::UnknownColumn {
+                    id: 3 << 3 | columnar::COLUMN_TYPE_BOOLEAN,
+                    data: vec![1],
+                },
+            ],
+        };
+        let bytes = Change::from(change).bytes;
+        assert_eq!(
+            Change::from_bytes(bytes),
+            Err(AutomergeError::MissingColumns { offset: 0 })
+        );
+    }
 }
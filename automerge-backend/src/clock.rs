@@ -0,0 +1,33 @@
+use crate::internal::ActorID;
+use std::cmp::max;
+
+/// A vector clock: the number of changes from each actor which are included,
+/// indexed by the actor's index in the `ActorMap`. The clock of a change
+/// includes the change itself and everything it depends on, directly or
+/// indirectly.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub(crate) struct Clock(Vec<u64>);
+
+impl Clock {
+    pub fn get(&self, actor: ActorID) -> u64 {
+        self.0.get(actor.0).cloned().unwrap_or(0)
+    }
+
+    /// Include the changes from `actor` up to and including `seq`
+    pub fn include(&mut self, actor: ActorID, seq: u64) {
+        if self.0.len() <= actor.0 {
+            self.0.resize(actor.0 + 1, 0);
+        }
+        self.0[actor.0] = max(self.0[actor.0], seq);
+    }
+
+    /// Include everything `other` includes
+    pub fn merge(&mut self, other: &Clock) {
+        if self.0.len() < other.0.len() {
+            self.0.resize(other.0.len(), 0);
+        }
+        for (seq, other_seq) in self.0.iter_mut().zip(other.0.iter()) {
+            *seq = max(*seq, *other_seq);
+        }
+    }
+}
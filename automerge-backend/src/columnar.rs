@@ -1,16 +1,21 @@
 use crate::encoding::{BooleanDecoder, Decodable, Decoder, DeltaDecoder, RLEDecoder};
 use crate::encoding::{BooleanEncoder, ColData, DeltaEncoder, Encodable, RLEEncoder};
+use crate::error::AutomergeError;
 use automerge_protocol as amp;
 use core::fmt::Debug;
+use flate2::bufread::{DeflateDecoder, DeflateEncoder};
+use flate2::Compression;
 use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::io;
 use std::io::{Read, Write};
+use std::mem;
 use std::ops::Range;
 use std::str;
 
 impl Encodable for Action {
     fn encode<R: Write>(&self, buf: &mut R) -> io::Result<usize> {
-        (*self as u32).encode(buf)
+        self.code().encode(buf)
     }
 }
 
@@ -108,87 +113,67 @@ impl<'a> Iterator for PredIterator<'a> {
     }
 }
 
-impl<'a> Iterator for ValueIterator<'a> {
-    type Item = amp::ScalarValue;
-    fn next(&mut self) -> Option<amp::ScalarValue> {
-        let val_type = self.val_len.next()??;
-        match val_type {
-            VALUE_TYPE_NULL => Some(amp::ScalarValue::Null),
-            VALUE_TYPE_FALSE => Some(amp::ScalarValue::Boolean(false)),
-            VALUE_TYPE_TRUE => Some(amp::ScalarValue::Boolean(true)),
-            v if v % 16 == VALUE_TYPE_COUNTER => {
-                let len = v >> 4;
-                let val = self.val_raw.read().ok()?;
-                if len != self.val_raw.last_read {
-                    return None;
-                }
-                Some(amp::ScalarValue::Counter(val))
-            }
-            v if v % 16 == VALUE_TYPE_TIMESTAMP => {
-                let len = v >> 4;
-                let val = self.val_raw.read().ok()?;
-                if len != self.val_raw.last_read {
-                    return None;
-                }
-                Some(amp::ScalarValue::Timestamp(val))
-            }
-            v if v % 16 == VALUE_TYPE_LEB128_UINT => {
-                let len = v >> 4;
-                let val = self.val_raw.read().ok()?;
-                if len != self.val_raw.last_read {
-                    return None;
-                }
-                Some(amp::ScalarValue::Uint(val))
-            }
-            v if v % 16 == VALUE_TYPE_LEB128_INT => {
-                let len = v >> 4;
-                let val = self.val_raw.read().ok()?;
-                if len != self.val_raw.last_read {
-                    return None;
-                }
-                Some(amp::ScalarValue::Int(val))
-            }
-            v if v % 16 == VALUE_TYPE_UTF8 => {
-                let len = v >> 4;
-                let data = self.val_raw.read_bytes(len).ok()?;
-                let s = str::from_utf8(&data).ok()?;
+/// A value as it is stored in the value columns: a type code and the bytes
+/// of the value
+pub struct RawValue<'a> {
+    pub(crate) type_code: usize,
+    pub(crate) bytes: &'a [u8],
+}
+
+impl<'a> RawValue<'a> {
+    /// Whether the type code is one we know how to decode
+    fn is_known(&self) -> bool {
+        !(self.type_code == VALUE_TYPE_BYTES
+            || (VALUE_TYPE_MIN_UNKNOWN..=VALUE_TYPE_MAX_UNKNOWN).contains(&self.type_code))
+    }
+
+    /// Decode a value of a known type, `None` if the value is malformed
+    fn decode(&self) -> Option<amp::ScalarValue> {
+        match self.type_code {
+            VALUE_TYPE_NULL if self.bytes.is_empty() => Some(amp::ScalarValue::Null),
+            VALUE_TYPE_FALSE if self.bytes.is_empty() => Some(amp::ScalarValue::Boolean(false)),
+            VALUE_TYPE_TRUE if self.bytes.is_empty() => Some(amp::ScalarValue::Boolean(true)),
+            VALUE_TYPE_COUNTER => Some(amp::ScalarValue::Counter(decode_all(self.bytes)?)),
+            VALUE_TYPE_TIMESTAMP => Some(amp::ScalarValue::Timestamp(decode_all(self.bytes)?)),
+            VALUE_TYPE_LEB128_UINT => Some(amp::ScalarValue::Uint(decode_all(self.bytes)?)),
+            VALUE_TYPE_LEB128_INT => Some(amp::ScalarValue::Int(decode_all(self.bytes)?)),
+            VALUE_TYPE_UTF8 => {
+                let s = str::from_utf8(self.bytes).ok()?;
                 Some(amp::ScalarValue::Str(s.to_string()))
             }
-            v if v % 16 == VALUE_TYPE_BYTES => {
-                let len = v >> 4;
-                let _data = self.val_raw.read_bytes(len).ok()?;
-                unimplemented!()
-                //Some((amp::Value::Bytes(data))
-            }
-            v if v % 16 >= VALUE_TYPE_MIN_UNKNOWN && v % 16 <= VALUE_TYPE_MAX_UNKNOWN => {
-                let len = v >> 4;
-                let _data = self.val_raw.read_bytes(len).ok()?;
-                unimplemented!()
-                //Some((amp::Value::Bytes(data))
-            }
-            v if v % 16 == VALUE_TYPE_IEEE754 => {
-                let len = v >> 4;
-                if len == 4 {
-                    // confirm only 4 bytes read
-                    let num: f32 = self.val_raw.read().ok()?;
-                    Some(amp::ScalarValue::F32(num))
-                } else if len == 8 {
-                    // confirm only 8 bytes read
-                    let num = self.val_raw.read().ok()?;
-                    Some(amp::ScalarValue::F64(num))
-                } else {
-                    // bad size of float
-                    None
-                }
-            }
-            _ => {
-                // unknown command
-                None
-            }
+            VALUE_TYPE_IEEE754 => match self.bytes.len() {
+                4 => Some(amp::ScalarValue::F32(decode_all(self.bytes)?)),
+                8 => Some(amp::ScalarValue::F64(decode_all(self.bytes)?)),
+                // bad size of float
+                _ => None,
+            },
+            _ => None,
         }
     }
 }
 
+/// Decode a `T` which takes up the whole of `bytes`
+fn decode_all<T: Decodable>(mut bytes: &[u8]) -> Option<T> {
+    let val = T::decode(&mut bytes)?;
+    if bytes.is_empty() {
+        Some(val)
+    } else {
+        None
+    }
+}
+
+impl<'a> Iterator for ValueIterator<'a> {
+    type Item = RawValue<'a>;
+    fn next(&mut self) -> Option<RawValue<'a>> {
+        let val_type = self.val_len.next()??;
+        let bytes = self.val_raw.read_bytes(val_type >> 4).ok()?;
+        Some(RawValue {
+            type_code: val_type % 16,
+            bytes,
+        })
+    }
+}
+
 impl<'a> Iterator for KeyIterator<'a> {
     type Item = amp::Key;
     fn next(&mut self) -> Option<amp::Key> {
@@ -225,14 +210,25 @@ impl<'a> Iterator for OperationIterator<'a> {
         let key = self.keys.next()?;
         let pred = self.pred.next()?;
         let value = self.value.next()?;
-        let action = match action {
-            Action::Set => amp::OpType::Set(value),
-            Action::MakeList => amp::OpType::Make(amp::ObjType::list()),
-            Action::MakeText => amp::OpType::Make(amp::ObjType::text()),
-            Action::MakeMap => amp::OpType::Make(amp::ObjType::map()),
-            Action::MakeTable => amp::OpType::Make(amp::ObjType::table()),
-            Action::Del => amp::OpType::Del,
-            Action::Inc => amp::OpType::Inc(value.to_i64()?),
+        let scalar = if value.is_known() {
+            Some(value.decode()?)
+        } else {
+            None
+        };
+        let action = match (action, scalar) {
+            (Action::Set, Some(scalar)) => amp::OpType::Set(scalar),
+            (Action::Inc, Some(scalar)) => amp::OpType::Inc(scalar.to_i64()?),
+            (Action::MakeList, _) => amp::OpType::Make(amp::ObjType::list()),
+            (Action::MakeText, _) => amp::OpType::Make(amp::ObjType::text()),
+            (Action::MakeMap, _) => amp::OpType::Make(amp::ObjType::map()),
+            (Action::MakeTable, _) => amp::OpType::Make(amp::ObjType::table()),
+            (Action::Del, _) => amp::OpType::Del,
+            // Either the action or the type of its value is unknown
+            (action, _) => amp::OpType::Unknown {
+                action: action.code(),
+                value_type: value.type_code as u8,
+                value: value.bytes.to_vec(),
+            },
         };
         Some(amp::Op {
             action,
@@ -298,16 +294,16 @@ impl ValEncoder {
             amp::ScalarValue::F64(n) => {
                 let len = (*n).encode(&mut self.raw).unwrap();
                 self.len.append_value(len << 4 | VALUE_TYPE_IEEE754)
-            } /*
-              amp::Value::Unknown(num,bytes) => {
-                  let len = bytes.len();
-                  self.raw.extend(bytes);
-                  self.len.append_value(len << 4 | num)
-              },
-              */
+            }
         }
     }
 
+    fn append_raw(&mut self, type_code: u8, bytes: &[u8]) {
+        self.raw.extend(bytes);
+        self.len
+            .append_value(bytes.len() << 4 | (type_code as usize % 16))
+    }
+
     fn append_null(&mut self) {
         self.len.append_value(VALUE_TYPE_NULL)
     }
@@ -459,6 +455,19 @@ impl ChildEncoder {
     }
 }
 
+/// The encoded op columns of a change
+pub(crate) struct EncodedOps {
+    pub bytes: Vec<u8>,
+    /// The range of `bytes` each column occupies, by column ID without the
+    /// deflate flag
+    pub ranges: HashMap<u32, Range<usize>>,
+    /// The uncompressed contents of the columns which were compressed
+    pub inflated: HashMap<u32, Vec<u8>>,
+    /// The IDs, with the deflate flag, of the unknown columns which were
+    /// written
+    pub unknown: Vec<u32>,
+}
+
 pub(crate) struct ColumnEncoder {
     obj: ObjEncoder,
     key: KeyEncoder,
@@ -470,16 +479,19 @@ pub(crate) struct ColumnEncoder {
 }
 
 impl ColumnEncoder {
+    /// Encode `ops` along with any columns from `unknown` whose IDs are not
+    /// ones we write ourselves
     pub fn encode_ops<'a, 'b, I>(
         ops: I,
+        unknown: &[amp::UnknownColumn],
         actors: &'a mut Vec<amp::ActorID>,
-    ) -> (Vec<u8>, HashMap<u32, Range<usize>>)
+    ) -> EncodedOps
     where
         I: IntoIterator<Item = &'b amp::Op>,
     {
         let mut e = Self::new();
         e.encode(ops, actors);
-        e.finish()
+        e.finish(unknown)
     }
 
     fn new() -> ColumnEncoder {
@@ -524,6 +536,15 @@ impl ColumnEncoder {
                 self.chld.append_null();
                 Action::Del
             }
+            amp::OpType::Unknown {
+                action,
+                value_type,
+                value,
+            } => {
+                self.val.append_raw(*value_type, value);
+                self.chld.append_null();
+                Action::from_code(*action)
+            }
             amp::OpType::Make(kind) => {
                 self.val.append_null();
                 self.chld.append_null();
@@ -538,7 +559,7 @@ impl ColumnEncoder {
         self.action.append_value(action);
     }
 
-    fn finish(self) -> (Vec<u8>, HashMap<u32, Range<usize>>) {
+    fn finish(self, unknown: &[amp::UnknownColumn]) -> EncodedOps {
         let mut coldata = Vec::new();
         coldata.push(self.insert.finish(COL_INSERT));
         coldata.push(self.action.finish(COL_ACTION));
@@ -549,6 +570,27 @@ impl ColumnEncoder {
         coldata.extend(self.pred.finish());
         coldata.sort_by(|a, b| a.col.cmp(&b.col));
 
+        let mut inflated = HashMap::new();
+        for d in coldata.iter_mut() {
+            if d.data.len() >= DEFLATE_MIN_SIZE {
+                let compressed = deflate(&d.data);
+                inflated.insert(d.col, mem::replace(&mut d.data, compressed));
+                d.col |= COLUMN_TYPE_DEFLATE;
+            }
+        }
+
+        // Unknown columns are written out as they were found, compressed or not
+        coldata.extend(
+            unknown
+                .iter()
+                .filter(|c| !is_known_column(c.id))
+                .map(|c| ColData {
+                    col: c.id,
+                    data: c.data.clone(),
+                }),
+        );
+        coldata.sort_by_key(|d| d.col & !COLUMN_TYPE_DEFLATE);
+
         let mut result = Vec::new();
         let mut rangemap = HashMap::new();
         coldata
@@ -564,10 +606,20 @@ impl ColumnEncoder {
             let begin = result.len();
             result.write_all(d.data.as_slice()).ok();
             if !d.data.is_empty() {
-                rangemap.insert(d.col, begin..result.len());
+                rangemap.insert(d.col & !COLUMN_TYPE_DEFLATE, begin..result.len());
             }
         }
-        (result, rangemap)
+        let unknown = coldata
+            .iter()
+            .filter(|d| !d.data.is_empty() && !is_known_column(d.col))
+            .map(|d| d.col)
+            .collect();
+        EncodedOps {
+            bytes: result,
+            ranges: rangemap,
+            inflated,
+            unknown,
+        }
     }
 }
 
@@ -592,9 +644,30 @@ pub(crate) const COLUMN_TYPE_BOOLEAN: u32 = 4;
 pub(crate) const COLUMN_TYPE_STRING_RLE: u32 = 5;
 pub(crate) const COLUMN_TYPE_VALUE_LEN: u32 = 6;
 pub(crate) const COLUMN_TYPE_VALUE_RAW: u32 = 7;
+/// Set in the ID of a column whose contents are compressed with DEFLATE
+pub(crate) const COLUMN_TYPE_DEFLATE: u32 = 8;
+
+/// Columns at least this long are compressed, the same threshold as the
+/// JavaScript implementation uses
+const DEFLATE_MIN_SIZE: usize = 256;
+
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut compressed = Vec::new();
+    DeflateEncoder::new(data, Compression::default())
+        .read_to_end(&mut compressed)
+        .unwrap();
+    compressed
+}
+
+pub(crate) fn inflate(data: &[u8]) -> Result<Vec<u8>, AutomergeError> {
+    let mut inflated = Vec::new();
+    DeflateDecoder::new(data)
+        .read_to_end(&mut inflated)
+        .map_err(|err| AutomergeError::ChangeDecompressError(err.to_string()))?;
+    Ok(inflated)
+}
 
 #[derive(PartialEq, Debug, Clone, Copy)]
-#[repr(u32)]
 pub(crate) enum Action {
     MakeMap,
     Set,
@@ -603,6 +676,8 @@ pub(crate) enum Action {
     MakeText,
     Inc,
     MakeTable,
+    /// An action code this implementation does not know
+    Unknown(u64),
 }
 const ACTIONS: [Action; 7] = [
     Action::MakeMap,
@@ -614,32 +689,109 @@ const ACTIONS: [Action; 7] = [
     Action::MakeTable,
 ];
 
+impl Action {
+    fn from_code(code: u64) -> Action {
+        usize::try_from(code)
+            .ok()
+            .and_then(|index| ACTIONS.get(index))
+            .cloned()
+            .unwrap_or(Action::Unknown(code))
+    }
+
+    fn code(self) -> u64 {
+        match self {
+            Action::Unknown(code) => code,
+            action => ACTIONS.iter().position(|a| *a == action).unwrap() as u64,
+        }
+    }
+}
+
 impl Decodable for Action {
     fn decode<R>(bytes: &mut R) -> Option<Self>
     where
         R: Read,
     {
-        let num = usize::decode::<R>(bytes)?;
-        ACTIONS.get(num).cloned()
+        let code = u64::decode::<R>(bytes)?;
+        Some(Action::from_code(code))
     }
 }
 
 pub(crate) const COL_OBJ_ACTOR: u32 = COLUMN_TYPE_ACTOR_ID;
 pub(crate) const COL_OBJ_CTR: u32 = COLUMN_TYPE_INT_RLE;
-pub(crate) const COL_KEY_ACTOR: u32 = 1 << 3 | COLUMN_TYPE_ACTOR_ID;
-pub(crate) const COL_KEY_CTR: u32 = 1 << 3 | COLUMN_TYPE_INT_DELTA;
-pub(crate) const COL_KEY_STR: u32 = 1 << 3 | COLUMN_TYPE_STRING_RLE;
-//pub(crate) const COL_ID_ACTOR : u32 = 2 << 3 | COLUMN_TYPE_ACTOR_ID;
-//pub(crate) const COL_ID_CTR : u32 = 2 << 3 | COLUMN_TYPE_INT_DELTA;
-pub(crate) const COL_INSERT: u32 = 3 << 3 | COLUMN_TYPE_BOOLEAN;
-pub(crate) const COL_ACTION: u32 = 4 << 3 | COLUMN_TYPE_INT_RLE;
-pub(crate) const COL_VAL_LEN: u32 = 5 << 3 | COLUMN_TYPE_VALUE_LEN;
-pub(crate) const COL_VAL_RAW: u32 = 5 << 3 | COLUMN_TYPE_VALUE_RAW;
-pub(crate) const COL_CHILD_ACTOR: u32 = 6 << 3 | COLUMN_TYPE_ACTOR_ID;
-pub(crate) const COL_CHILD_CTR: u32 = 6 << 3 | COLUMN_TYPE_INT_DELTA;
-pub(crate) const COL_PRED_NUM: u32 = 7 << 3 | COLUMN_TYPE_GROUP_CARD;
-pub(crate) const COL_PRED_ACTOR: u32 = 7 << 3 | COLUMN_TYPE_ACTOR_ID;
-pub(crate) const COL_PRED_CTR: u32 = 7 << 3 | COLUMN_TYPE_INT_DELTA;
-//pub(crate) const COL_SUCC_NUM : u32 = 8 << 3 | COLUMN_TYPE_GROUP_CARD;
-//pub(crate) const COL_SUCC_ACTOR : u32 = 8 << 3 | COLUMN_TYPE_ACTOR_ID;
-//pub(crate) const COL_SUCC_CTR : u32 = 8 << 3 | COLUMN_TYPE_INT_DELTA;
+pub(crate) const COL_KEY_ACTOR: u32 = 1 << 4 | COLUMN_TYPE_ACTOR_ID;
+pub(crate) const COL_KEY_CTR: u32 = 1 << 4 | COLUMN_TYPE_INT_DELTA;
+pub(crate) const COL_KEY_STR: u32 = 1 << 4 | COLUMN_TYPE_STRING_RLE;
+//pub(crate) const COL_ID_ACTOR : u32 = 2 << 4 | COLUMN_TYPE_ACTOR_ID;
+//pub(crate) const COL_ID_CTR : u32 = 2 << 4 | COLUMN_TYPE_INT_DELTA;
+pub(crate) const COL_INSERT: u32 = 3 << 4 | COLUMN_TYPE_BOOLEAN;
+pub(crate) const COL_ACTION: u32 = 4 << 4 | COLUMN_TYPE_INT_RLE;
+pub(crate) const COL_VAL_LEN: u32 = 5 << 4 | COLUMN_TYPE_VALUE_LEN;
+pub(crate) const COL_VAL_RAW: u32 = 5 << 4 | COLUMN_TYPE_VALUE_RAW;
+pub(crate) const COL_CHILD_ACTOR: u32 = 6 << 4 | COLUMN_TYPE_ACTOR_ID;
+pub(crate) const COL_CHILD_CTR: u32 = 6 << 4 | COLUMN_TYPE_INT_DELTA;
+pub(crate) const COL_PRED_NUM: u32 = 7 << 4 | COLUMN_TYPE_GROUP_CARD;
+pub(crate) const COL_PRED_ACTOR: u32 = 7 << 4 | COLUMN_TYPE_ACTOR_ID;
+pub(crate) const COL_PRED_CTR: u32 = 7 << 4 | COLUMN_TYPE_INT_DELTA;
+//pub(crate) const COL_SUCC_NUM : u32 = 8 << 4 | COLUMN_TYPE_GROUP_CARD;
+//pub(crate) const COL_SUCC_ACTOR : u32 = 8 << 4 | COLUMN_TYPE_ACTOR_ID;
+//pub(crate) const COL_SUCC_CTR : u32 = 8 << 4 | COLUMN_TYPE_INT_DELTA;
+
+/// The columns we read or write ourselves, any other column is kept verbatim
+const KNOWN_COLUMNS: [u32; 14] = [
+    COL_OBJ_ACTOR,
+    COL_OBJ_CTR,
+    COL_KEY_ACTOR,
+    COL_KEY_CTR,
+    COL_KEY_STR,
+    COL_INSERT,
+    COL_ACTION,
+    COL_VAL_LEN,
+    COL_VAL_RAW,
+    COL_CHILD_ACTOR,
+    COL_CHILD_CTR,
+    COL_PRED_NUM,
+    COL_PRED_ACTOR,
+    COL_PRED_CTR,
+];
+
+/// Whether the column with ID `id`, with or without the deflate flag, is one
+/// we know how to interpret
+pub(crate) fn is_known_column(id: u32) -> bool {
+    KNOWN_COLUMNS.contains(&(id & !COLUMN_TYPE_DEFLATE))
+}
+
+/// Whether a change with the columns `ids`, without the deflate flag, has
+/// the columns which every op needs: an action, a key, and both or neither of
+/// the obj columns, which are left out when every op is on the root object.
+/// A change without ops has no columns at all.
+pub(crate) fn has_required_columns(ids: &[u32]) -> bool {
+    let has = |id| ids.contains(&id);
+    ids.is_empty()
+        || (has(COL_ACTION)
+            && (has(COL_KEY_STR) || has(COL_KEY_CTR))
+            && (has(COL_KEY_CTR) || !has(COL_KEY_ACTOR))
+            && has(COL_OBJ_ACTOR) == has(COL_OBJ_CTR))
+}
+
+/// The ID which the column with ID `id` had before the deflate flag was
+/// introduced, when the type of a column took up three bits of its ID rather
+/// than four
+const fn legacy_column(id: u32) -> u32 {
+    (id >> 4) << 3 | (id & 7)
+}
+
+/// The ID of the column which had ID `id` in the legacy layout, see
+/// `is_legacy_layout`
+pub(crate) fn from_legacy_column(id: u32) -> u32 {
+    (id >> 3) << 4 | (id & 7)
+}
+
+/// Whether the column IDs `ids` of a change are in the layout used before
+/// compressed columns were supported. Those changes have an action column
+/// and only columns we know how to interpret, all with their legacy IDs.
+pub(crate) fn is_legacy_layout(ids: &[u32]) -> bool {
+    ids.contains(&legacy_column(COL_ACTION))
+        && ids
+            .iter()
+            .all(|id| KNOWN_COLUMNS.iter().any(|col| legacy_column(*col) == *id))
+}
@@ -63,6 +63,33 @@ pub enum AutomergeError {
     },
     #[error("Encoding error")]
     EncodingError,
+    #[error("Truncated change: {len} trailing bytes at offset {offset}")]
+    TruncatedChange { offset: usize, len: usize },
+    #[error("Checksum mismatch in chunk at offset {offset}")]
+    ChecksumMismatch { offset: usize },
+    #[error("Missing op columns in chunk at offset {offset}")]
+    MissingColumns { offset: usize },
+    #[error("Length out of range in chunk at offset {offset}")]
+    LengthOutOfRange { offset: usize },
+}
+
+impl AutomergeError {
+    /// Shift the offset reported by an error from decoding a single chunk so
+    /// that it is relative to the start of the data the chunk was taken from
+    pub(crate) fn at_offset(self, offset: usize) -> Self {
+        match self {
+            AutomergeError::ChecksumMismatch { offset: o } => {
+                AutomergeError::ChecksumMismatch { offset: o + offset }
+            }
+            AutomergeError::MissingColumns { offset: o } => {
+                AutomergeError::MissingColumns { offset: o + offset }
+            }
+            AutomergeError::LengthOutOfRange { offset: o } => {
+                AutomergeError::LengthOutOfRange { offset: o + offset }
+            }
+            err => err,
+        }
+    }
 }
 
 #[derive(Error, Debug)]
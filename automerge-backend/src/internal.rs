@@ -52,6 +52,8 @@ pub(crate) enum InternalOpType {
     Del,
     Inc(i64),
     Set(amp::ScalarValue),
+    /// An op we don't know how to interpret, which has no effect on state
+    Unknown,
 }
 
 impl Key {
@@ -2,7 +2,6 @@ extern crate fxhash;
 extern crate hex;
 //extern crate im_rc;
 extern crate maplit;
-extern crate rand;
 extern crate uuid;
 extern crate web_sys;
 
@@ -16,11 +15,13 @@ macro_rules! log {
 mod actor_map;
 mod backend;
 mod change;
+mod clock;
 mod columnar;
 mod concurrent_operations;
 mod encoding;
 mod error;
 mod internal;
+mod loader;
 mod object_store;
 mod op_handle;
 mod op_set;
@@ -28,6 +29,9 @@ mod ordered_set;
 mod pending_diff;
 mod time;
 
-pub use backend::Backend;
-pub use change::Change;
+pub use backend::{Backend, Blame};
+pub use change::{Change, ChangeRef, EncodedChange};
 pub use error::AutomergeError;
+pub use loader::{CorruptChunk, LoadProgress, Loader};
+#[cfg(feature = "bench")]
+pub use ordered_set::{CountedBTree, OrderedSet};
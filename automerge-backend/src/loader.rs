@@ -0,0 +1,166 @@
+use crate::change::chunk_len;
+use crate::error::AutomergeError;
+use crate::{Backend, Change};
+use std::io::{ErrorKind, Read};
+
+const READ_BUFFER_BYTES: usize = 64 * 1024;
+
+/// How much of a document a `Loader` has processed so far
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LoadProgress {
+    /// The number of changes which have been parsed and applied
+    pub changes: usize,
+    /// The number of bytes consumed by those changes, this does not include
+    /// bytes which are buffered waiting for the rest of a change
+    pub bytes: usize,
+}
+
+/// A chunk of a saved document which `Backend::load_salvage` skipped because
+/// it could not be decoded
+#[derive(Debug, PartialEq)]
+pub struct CorruptChunk {
+    /// The offset of the chunk in the document
+    pub offset: usize,
+    /// The number of bytes skipped, up to the start of the next chunk
+    pub len: usize,
+    pub error: AutomergeError,
+}
+
+/// Decode every intact change in `data`. When a chunk fails to decode the
+/// bytes up to the next chunk header are skipped, as the length of the bad
+/// chunk may itself be corrupt.
+pub(crate) fn salvage_changes(data: &[u8]) -> (Vec<Change>, Vec<CorruptChunk>) {
+    let mut changes = Vec::new();
+    let mut corrupt = Vec::new();
+    let mut offset = 0;
+    while offset < data.len() {
+        let rest = &data[offset..];
+        let parsed = match chunk_len(rest) {
+            Ok(Some(len)) if len <= rest.len() => {
+                Change::from_bytes(rest[..len].to_vec()).map(|change| (change, len))
+            }
+            Ok(_) => Err(AutomergeError::TruncatedChange {
+                offset,
+                len: rest.len(),
+            }),
+            Err(err) => Err(err),
+        };
+        match parsed {
+            Ok((change, len)) => {
+                changes.push(change);
+                offset += len;
+            }
+            Err(error) => {
+                let len = (1..rest.len())
+                    .find(|i| chunk_len(&rest[*i..]).is_ok())
+                    .unwrap_or(rest.len());
+                corrupt.push(CorruptChunk {
+                    offset,
+                    len,
+                    error: error.at_offset(offset),
+                });
+                offset += len;
+            }
+        }
+    }
+    (changes, corrupt)
+}
+
+/// Loads a saved document incrementally, applying each change as soon as all
+/// of its bytes have arrived rather than waiting for the whole document.
+#[derive(Debug)]
+pub struct Loader {
+    backend: Backend,
+    buffer: Vec<u8>,
+    progress: LoadProgress,
+}
+
+impl Default for Loader {
+    fn default() -> Self {
+        Loader::new()
+    }
+}
+
+impl Loader {
+    pub fn new() -> Loader {
+        Loader {
+            backend: Backend::init(),
+            buffer: Vec::new(),
+            progress: LoadProgress::default(),
+        }
+    }
+
+    pub fn progress(&self) -> LoadProgress {
+        self.progress
+    }
+
+    /// Append `data` to the document, applying every change which is now
+    /// complete. Bytes belonging to an incomplete change are buffered until
+    /// the next call.
+    pub fn feed(&mut self, data: &[u8]) -> Result<LoadProgress, AutomergeError> {
+        self.buffer.extend_from_slice(data);
+        let mut changes = Vec::new();
+        let mut consumed = 0;
+        let parsed = loop {
+            let rest = &self.buffer[consumed..];
+            let len = match chunk_len(rest) {
+                Ok(Some(len)) if len <= rest.len() => len,
+                Ok(_) => break Ok(()),
+                Err(err) => break Err(err),
+            };
+            match Change::from_bytes(rest[..len].to_vec()) {
+                Ok(change) => changes.push(change),
+                Err(err) => break Err(err.at_offset(self.progress.bytes + consumed)),
+            }
+            consumed += len;
+        };
+        self.buffer.drain(..consumed);
+        self.progress.changes += changes.len();
+        self.progress.bytes += consumed;
+        // Apply the changes preceding a bad one so that the progress reported
+        // matches the state of the backend
+        self.backend.load_changes(changes)?;
+        parsed?;
+        Ok(self.progress)
+    }
+
+    /// Feed everything `reader` produces into the loader, calling
+    /// `on_progress` after each read
+    pub fn read_from<R, F>(
+        &mut self,
+        mut reader: R,
+        mut on_progress: F,
+    ) -> Result<LoadProgress, AutomergeError>
+    where
+        R: Read,
+        F: FnMut(LoadProgress),
+    {
+        let mut buf = vec![0; READ_BUFFER_BYTES];
+        loop {
+            let len = match reader.read(&mut buf) {
+                Ok(0) => return Ok(self.progress),
+                Ok(len) => len,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => {
+                    return Err(AutomergeError::GeneralError(format!(
+                        "Error reading document: {}",
+                        err
+                    )))
+                }
+            };
+            on_progress(self.feed(&buf[..len])?);
+        }
+    }
+
+    /// Finish loading, failing if the input ended part way through a change
+    pub fn finish(self) -> Result<Backend, AutomergeError> {
+        if self.buffer.is_empty() {
+            Ok(self.backend)
+        } else {
+            Err(AutomergeError::TruncatedChange {
+                offset: self.progress.bytes,
+                len: self.buffer.len(),
+            })
+        }
+    }
+}
@@ -2,11 +2,109 @@ use crate::actor_map::ActorMap;
 use crate::concurrent_operations::ConcurrentOperations;
 use crate::internal::{ElementID, Key, OpID};
 use crate::op_handle::OpHandle;
-use crate::ordered_set::{OrderedSet, SkipList};
+use crate::ordered_set::{CountedBTree, Delta, OrdDelta, OrderedSet};
 use automerge_protocol as amp;
 use fxhash::FxBuildHasher;
 //use im_rc::{HashMap, HashSet};
+use std::borrow::{Borrow, Cow};
 use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::mem;
+use std::rc::Rc;
+
+/// A map which is shared between clones of an object, along with the entries
+/// changed since it was last committed. Cloning it only copies the changed
+/// entries, so an object can be edited for a preview without copying the
+/// entries the preview did not touch. Entries are never removed.
+#[derive(Debug, Clone)]
+pub(crate) struct SharedMap<K, V>
+where
+    K: Eq + Hash,
+{
+    base: Rc<HashMap<K, V, FxBuildHasher>>,
+    edits: HashMap<K, V, FxBuildHasher>,
+}
+
+impl<K, V> SharedMap<K, V>
+where
+    K: Clone + Eq + Hash,
+    V: Clone,
+{
+    pub fn new() -> SharedMap<K, V> {
+        SharedMap {
+            base: Rc::new(HashMap::default()),
+            edits: HashMap::default(),
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.edits.get(key).or_else(|| self.base.get(key))
+    }
+
+    /// The value at `key` for editing, copied out of the shared entries or
+    /// the default if there is none
+    pub fn get_mut_or_default(&mut self, key: K) -> &mut V
+    where
+        V: Default,
+    {
+        let base = &self.base;
+        self.edits
+            .entry(key)
+            .or_insert_with_key(|key| base.get(key).cloned().unwrap_or_default())
+    }
+
+    pub fn insert(&mut self, key: K, value: V) {
+        self.edits.insert(key, value);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        let edits = &self.edits;
+        self.base
+            .iter()
+            .filter(move |(key, _)| !edits.contains_key(key))
+            .chain(edits.iter())
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, value)| value)
+    }
+
+    pub fn len(&self) -> usize {
+        let base = &self.base;
+        base.len()
+            + self
+                .edits
+                .keys()
+                .filter(|key| !base.contains_key(key))
+                .count()
+    }
+
+    /// Move the changed entries into the shared ones, copying those first if
+    /// another clone still refers to them
+    pub fn commit(&mut self) {
+        if !self.edits.is_empty() {
+            let edits = mem::take(&mut self.edits);
+            Rc::make_mut(&mut self.base).extend(edits);
+        }
+    }
+}
+
+impl<K, V> PartialEq for SharedMap<K, V>
+where
+    K: Clone + Eq + Hash,
+    V: Clone + PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(key, value)| other.get(key) == Some(value))
+    }
+}
 
 /// ObjectHistory is what the OpSet uses to store operations for a particular
 /// key, they represent the two possible container types in automerge, a map or
@@ -16,25 +114,31 @@ use std::collections::{HashMap, HashSet};
 /// Stores operations on map objects
 #[derive(Debug, Clone, PartialEq)]
 pub(crate) struct ObjState {
-    pub props: HashMap<Key, ConcurrentOperations>,
+    pub props: SharedMap<Key, ConcurrentOperations>,
     pub obj_type: amp::ObjType,
     pub inbound: HashSet<OpHandle, FxBuildHasher>,
-    pub following: HashMap<ElementID, Vec<ElementID>, FxBuildHasher>,
-    pub insertions: HashMap<ElementID, OpHandle, FxBuildHasher>,
-    pub seq: SkipList<OpID>,
+    pub following: SharedMap<ElementID, Vec<ElementID>>,
+    pub insertions: SharedMap<ElementID, OpHandle>,
+    /// The visible elements of a sequence in order. This is shared between
+    /// clones of the object and only copied when edits are committed to it.
+    pub seq: Rc<CountedBTree<OpID>>,
+    /// Edits to `seq` which have not been committed yet
+    pub seq_delta: Delta<OpID>,
 }
 
 impl ObjState {
     pub fn new(obj_type: amp::ObjType) -> ObjState {
-        let mut following = HashMap::default();
+        let mut following = SharedMap::new();
         following.insert(ElementID::Head, Vec::new());
+        following.commit();
         ObjState {
-            props: HashMap::default(),
+            props: SharedMap::new(),
             following,
-            insertions: HashMap::default(),
+            insertions: SharedMap::new(),
             obj_type,
             inbound: HashSet::default(),
-            seq: SkipList::new(),
+            seq: Rc::new(CountedBTree::new()),
+            seq_delta: Delta::new(),
         }
     }
 
@@ -50,8 +154,44 @@ impl ObjState {
         self.following.get(parent).cloned().unwrap_or_default()
     }
 
-    // this is the efficient way to do it for a SkipList
+    /// The visible elements of a sequence, including uncommitted edits
+    pub fn seq(&self) -> OrdDelta<'_, OpID, CountedBTree<OpID>> {
+        OrdDelta::with_delta(&self.seq, Cow::Borrowed(&self.seq_delta))
+    }
+
+    /// Insert `id` into the visible elements after its nearest visible
+    /// predecessor, returning the index it was inserted at
+    pub fn seq_insert(&mut self, id: OpID) -> usize {
+        let index = self.index_of(id).unwrap_or(0);
+        let delta = mem::take(&mut self.seq_delta);
+        let mut seq = OrdDelta::with_delta(self.seq.as_ref(), Cow::Owned(delta));
+        seq.insert_index(index, id);
+        self.seq_delta = seq.into_delta();
+        index
+    }
+
+    /// Remove `id` from the visible elements, returning the index it was at
+    pub fn seq_remove(&mut self, id: &OpID) -> Option<usize> {
+        let delta = mem::take(&mut self.seq_delta);
+        let mut seq = OrdDelta::with_delta(self.seq.as_ref(), Cow::Owned(delta));
+        let index = seq.remove_key(id);
+        self.seq_delta = seq.into_delta();
+        index
+    }
+
+    /// Apply any uncommitted edits to `seq` and the shared maps
+    pub fn commit(&mut self) {
+        if !self.seq_delta.is_empty() {
+            let delta = mem::take(&mut self.seq_delta);
+            OrdDelta::commit(Rc::make_mut(&mut self.seq), delta);
+        }
+        self.props.commit();
+        self.following.commit();
+        self.insertions.commit();
+    }
+
     pub fn index_of(&self, id: OpID) -> Option<usize> {
+        let seq = self.seq();
         let mut prev_id = id.into();
         let mut index = None;
         // reverse walk through the following/insertions and looking for something that not deleted
@@ -63,7 +203,7 @@ impl ObjState {
             match prev_id {
                 ElementID::ID(id) => {
                     // FIXME maybe I can speed this up with self.props.get before looking for
-                    index = self.seq.index_of(&id)
+                    index = seq.index_of(&id)
                 }
                 ElementID::Head => return None,
             }
@@ -97,8 +237,37 @@ impl ObjState {
     pub fn insert_after(&mut self, elem: ElementID, op: OpHandle, actors: &ActorMap) {
         let eid = op.id.into();
         self.insertions.insert(eid, op);
-        let following = self.following.entry(elem).or_default();
+        let following = self.following.get_mut_or_default(elem);
         following.push(eid);
         following.sort_unstable_by(|a, b| actors.cmp(b, a));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shared_map_clones_share_untouched_entries() {
+        let mut map = SharedMap::new();
+        for key in 0..100 {
+            map.insert(key, key.to_string());
+        }
+        map.commit();
+
+        let mut edited = map.clone();
+        edited.get_mut_or_default(3).push('!');
+        edited.insert(100, "new".to_string());
+        assert!(Rc::ptr_eq(&map.base, &edited.base));
+        assert_eq!(edited.edits.len(), 2);
+        assert_eq!(edited.get(&3).map(String::as_str), Some("3!"));
+        assert_eq!(map.get(&3).map(String::as_str), Some("3"));
+        assert_eq!(edited.len(), 101);
+
+        // Committing copies the shared entries since `map` still refers to them
+        edited.commit();
+        assert!(!Rc::ptr_eq(&map.base, &edited.base));
+        assert_eq!(edited.values().filter(|v| v.as_str() == "3!").count(), 1);
+        assert_ne!(map, edited);
+    }
+}
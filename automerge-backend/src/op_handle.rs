@@ -17,18 +17,14 @@ pub(crate) struct OpHandle {
 
 impl OpHandle {
     pub fn extract(change: Rc<Change>, actors: &mut ActorMap) -> Vec<OpHandle> {
-        change
-            .iter_ops()
-            .enumerate()
-            .map(|(index, op)| {
-                let id = OpID(
-                    change.start_op + (index as u64),
-                    actors.import_actor(change.actor_id()),
-                );
-                let op = actors.import_op(op);
-                OpHandle { id, op, delta: 0 }
-            })
-            .collect()
+        let actor = actors.import_actor(change.actor_id());
+        let mut handles = Vec::with_capacity(change.op_count());
+        for (index, op) in change.iter_ops().enumerate() {
+            let id = OpID(change.start_op + (index as u64), actor);
+            let op = actors.import_op(op);
+            handles.push(OpHandle { id, op, delta: 0 });
+        }
+        handles
     }
 
     pub fn adjusted_value(&self) -> amp::ScalarValue {
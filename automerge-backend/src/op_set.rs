@@ -7,8 +7,9 @@
 //! and then recursively walks through the tree of histories constructing the
 //! state. Obviously this is not very efficient.
 use crate::actor_map::ActorMap;
+use crate::concurrent_operations::ConcurrentOperations;
 use crate::error::AutomergeError;
-use crate::internal::{InternalOpType, ObjectID};
+use crate::internal::{ElementID, InternalOpType, Key, ObjectID, OpID};
 use crate::object_store::ObjState;
 use crate::op_handle::OpHandle;
 use crate::ordered_set::OrderedSet;
@@ -42,6 +43,15 @@ pub(crate) struct OpSet {
     pub objs: HashMap<ObjectID, Rc<ObjState>, FxBuildHasher>,
     pub deps: HashSet<amp::ChangeHash>,
     pub max_op: u64,
+    /// When set, edits are left uncommitted in each object's `seq_delta` and
+    /// shared maps so that the patch for some changes can be generated
+    /// without copying the sequence indexes or props of the objects they
+    /// touch
+    pub preview: bool,
+    /// The ops we don't know how to interpret, along with every op whose
+    /// object or element was created by one of them. These are left out of
+    /// the object store so they have no effect on the state.
+    pub ignored: HashSet<OpID, FxBuildHasher>,
 }
 
 impl OpSet {
@@ -53,21 +63,26 @@ impl OpSet {
             objs,
             max_op: 0,
             deps: HashSet::default(),
+            preview: false,
+            ignored: HashSet::default(),
         }
     }
 
+    /// Apply `ops`, recording the changes they make in `diffs`. When there
+    /// are no `diffs` to record into, as when loading a document, no
+    /// `PendingDiff`s are created at all.
     pub(crate) fn apply_ops(
         &mut self,
         mut ops: Vec<OpHandle>,
-        diffs: &mut HashMap<ObjectID, Vec<PendingDiff>>,
+        mut diffs: Option<&mut HashMap<ObjectID, Vec<PendingDiff>>>,
         actors: &ActorMap,
     ) -> Result<(), AutomergeError> {
         for op in ops.drain(..) {
             let obj_id = op.obj;
 
-            let pending_diff = self.apply_op(op, actors)?;
+            let pending_diff = self.apply_op(op, actors, diffs.is_some())?;
 
-            if let Some(d) = pending_diff {
+            if let (Some(d), Some(diffs)) = (pending_diff, diffs.as_deref_mut()) {
                 diffs.entry(obj_id).or_default().push(d);
             }
         }
@@ -84,13 +99,20 @@ impl OpSet {
         &mut self,
         op: OpHandle,
         actors: &ActorMap,
+        track_diffs: bool,
     ) -> Result<Option<PendingDiff>, AutomergeError> {
+        if self.should_ignore(&op) {
+            self.ignored.insert(op.id);
+            return Ok(None);
+        }
+
         if let (Some(child), Some(obj_type)) = (op.child(), op.obj_type()) {
             //let child = actors.import_obj(child);
             self.objs.insert(child, Rc::new(ObjState::new(obj_type)));
         }
 
         let object_id = &op.obj;
+        let preview = self.preview;
         let object = self.get_obj_mut(&object_id)?;
 
         if object.is_seq() {
@@ -102,44 +124,50 @@ impl OpSet {
                 );
             }
 
-            let ops = object.props.entry(op.operation_key()).or_default();
+            let ops = object.props.get_mut_or_default(op.operation_key());
             let before = !ops.is_empty();
             let overwritten_ops = ops.incorporate_new_op(&op)?;
             let after = !ops.is_empty();
 
             let diff = match (before, after) {
-                (true, true) => Some(PendingDiff::Set(op.clone())),
+                (true, true) => track_diffs.then(|| PendingDiff::Set(op.clone())),
                 (true, false) => {
                     let opid = op
                         .operation_key()
                         .to_opid()
                         .ok_or(AutomergeError::HeadToOpID)?;
-                    let index = object.seq.remove_key(&opid).unwrap();
-                    Some(PendingDiff::SeqRemove(op.clone(), index))
+                    let index = object.seq_remove(&opid).unwrap();
+                    track_diffs.then(|| PendingDiff::SeqRemove(op.clone(), index))
                 }
                 (false, true) => {
                     let id = op
                         .operation_key()
                         .to_opid()
                         .ok_or(AutomergeError::HeadToOpID)?;
-                    let index = object.index_of(id).unwrap_or(0);
-                    object.seq.insert_index(index, id);
-                    Some(PendingDiff::SeqInsert(op.clone(), index, op.id))
+                    let index = object.seq_insert(id);
+                    track_diffs.then(|| PendingDiff::SeqInsert(op.clone(), index, op.id))
                 }
                 (false, false) => None,
             };
 
+            if !preview {
+                object.commit();
+            }
+
             self.unlink(&op, &overwritten_ops)?;
 
             Ok(diff)
         } else {
-            let ops = object.props.entry(op.key.clone()).or_default();
+            let ops = object.props.get_mut_or_default(op.key.clone());
             let before = !ops.is_empty();
             let overwritten_ops = ops.incorporate_new_op(&op)?;
             let after = !ops.is_empty();
+            if !preview {
+                object.commit();
+            }
             self.unlink(&op, &overwritten_ops)?;
 
-            if before || after {
+            if track_diffs && (before || after) {
                 Ok(Some(PendingDiff::Set(op)))
             } else {
                 Ok(None)
@@ -147,6 +175,21 @@ impl OpSet {
         }
     }
 
+    fn should_ignore(&self, op: &OpHandle) -> bool {
+        if let InternalOpType::Unknown = op.action {
+            return true;
+        }
+        if self.ignored.is_empty() {
+            return false;
+        }
+        let obj_ignored = match op.obj {
+            ObjectID::ID(id) => self.ignored.contains(&id),
+            ObjectID::Root => false,
+        };
+        let elem_ignored = matches!(op.key.to_opid(), Some(id) if self.ignored.contains(&id));
+        obj_ignored || elem_ignored
+    }
+
     fn unlink(&mut self, op: &OpHandle, overwritten: &[OpHandle]) -> Result<(), AutomergeError> {
         if let Some(child) = op.child() {
             self.get_obj_mut(&child)?.inbound.insert(op.clone());
@@ -262,6 +305,152 @@ impl OpSet {
         }
     }
 
+    /// The value of `prop` in `object_id`. Where there are conflicting values
+    /// this is the one with the highest op ID, as a frontend would show.
+    pub fn get_value(
+        &self,
+        object_id: &ObjectID,
+        prop: &amp::PathElement,
+        actors: &ActorMap,
+    ) -> Result<Option<amp::Value>, AutomergeError> {
+        let object = self.get_obj(object_id)?;
+        match self.winning_op(object, prop, actors) {
+            Some(op) => Ok(Some(self.read_value(op, actors)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Every value of `prop` in `object_id`, keyed by the op which set it
+    pub fn get_conflicts(
+        &self,
+        object_id: &ObjectID,
+        prop: &amp::PathElement,
+        actors: &ActorMap,
+    ) -> Result<HashMap<amp::OpID, amp::Value>, AutomergeError> {
+        let object = self.get_obj(object_id)?;
+        let mut conflicts = HashMap::new();
+        if let Some(ops) = Self::resolve_prop(object, prop).and_then(|key| object.props.get(&key)) {
+            for op in ops.iter() {
+                conflicts.insert(actors.export_opid(&op.id), self.read_value(op, actors)?);
+            }
+        }
+        Ok(conflicts)
+    }
+
+    /// The number of elements in a list or text object, or of keys in a map
+    pub fn length(&self, object_id: &ObjectID) -> Result<usize, AutomergeError> {
+        let object = self.get_obj(object_id)?;
+        if object.is_seq() {
+            Ok(object.seq.len())
+        } else {
+            Ok(object.props.values().filter(|ops| !ops.is_empty()).count())
+        }
+    }
+
+    /// The keys of a map in sorted order, or the indexes of a list, each
+    /// alongside its value
+    pub fn entries(
+        &self,
+        object_id: &ObjectID,
+        actors: &ActorMap,
+    ) -> Result<Vec<(amp::PathElement, amp::Value)>, AutomergeError> {
+        let object = self.get_obj(object_id)?;
+        let mut entries = Vec::new();
+        if object.is_seq() {
+            for (index, opid) in object.seq.into_iter().enumerate() {
+                let op = object
+                    .props
+                    .get(&(*opid).into())
+                    .and_then(|ops| Self::winner(ops, actors))
+                    .ok_or(AutomergeError::MissingIndex(actors.export_opid(opid)))?;
+                let index = amp::PathElement::Index(index as u32);
+                entries.push((index, self.read_value(op, actors)?));
+            }
+        } else {
+            let mut keys: Vec<_> = object
+                .props
+                .iter()
+                .filter_map(|(key, ops)| match key {
+                    Key::Map(key) => Some((key, Self::winner(ops, actors)?)),
+                    Key::Seq(_) => None,
+                })
+                .collect();
+            keys.sort_by_key(|(key, _)| *key);
+            for (key, op) in keys {
+                let key = amp::PathElement::Key(key.clone());
+                entries.push((key, self.read_value(op, actors)?));
+            }
+        }
+        Ok(entries)
+    }
+
+    /// The path from the root object to `object_id`, found by following the
+    /// op which links each object into its parent. This is `None` when the
+    /// object, or one of the objects above it, has been deleted.
+    pub fn path_of(
+        &self,
+        object_id: &ObjectID,
+    ) -> Result<Option<Vec<amp::PathElement>>, AutomergeError> {
+        let mut path = Vec::new();
+        let mut object_id = *object_id;
+        while object_id != ObjectID::Root {
+            let link = match self.get_obj(&object_id)?.inbound.iter().next() {
+                Some(op) => op,
+                None => return Ok(None),
+            };
+            let parent = self.get_obj(&link.obj)?;
+            let prop = match link.operation_key() {
+                Key::Map(key) => amp::PathElement::Key(key),
+                Key::Seq(ElementID::ID(id)) => match parent.seq.index_of(&id) {
+                    Some(index) => amp::PathElement::Index(index as u32),
+                    None => return Ok(None),
+                },
+                Key::Seq(ElementID::Head) => return Err(AutomergeError::HeadToOpID),
+            };
+            path.push(prop);
+            object_id = link.obj;
+        }
+        path.reverse();
+        Ok(Some(path))
+    }
+
+    fn winning_op<'a>(
+        &self,
+        object: &'a ObjState,
+        prop: &amp::PathElement,
+        actors: &ActorMap,
+    ) -> Option<&'a OpHandle> {
+        let ops = object.props.get(&Self::resolve_prop(object, prop)?)?;
+        Self::winner(ops, actors)
+    }
+
+    fn winner<'a>(ops: &'a ConcurrentOperations, actors: &ActorMap) -> Option<&'a OpHandle> {
+        ops.iter()
+            .max_by(|a, b| actors.cmp(&a.id.into(), &b.id.into()))
+    }
+
+    /// The key which `prop` refers to in `object`, map keys only refer to
+    /// anything in maps and indexes only in sequences
+    fn resolve_prop(object: &ObjState, prop: &amp::PathElement) -> Option<Key> {
+        match prop {
+            amp::PathElement::Key(key) if !object.is_seq() => Some(Key::Map(key.clone())),
+            amp::PathElement::Index(index) if object.is_seq() => {
+                object.seq.key_of(*index as usize).map(|id| (*id).into())
+            }
+            _ => None,
+        }
+    }
+
+    fn read_value(&self, op: &OpHandle, actors: &ActorMap) -> Result<amp::Value, AutomergeError> {
+        match op.child() {
+            Some(child) => Ok(amp::Value::Object {
+                object_id: actors.export_obj(&child),
+                obj_type: self.get_obj(&child)?.obj_type,
+            }),
+            None => Ok(amp::Value::Primitive(op.adjusted_value())),
+        }
+    }
+
     // this recursivly walks through all the objects touched by the changes
     // to generate a diff in a single pass
     pub fn finalize_diffs(
@@ -319,7 +508,7 @@ impl OpSet {
                 opid_to_value.insert(actors.export_opid(&op.id), link);
             }
             if let Some(index) = obj
-                .seq
+                .seq()
                 .index_of(&key.to_opid().ok_or(AutomergeError::HeadToOpID)?)
             {
                 props.insert(index, opid_to_value);
@@ -371,8 +560,6 @@ impl OpSet {
     }
 
     pub fn update_deps(&mut self, change: &Change) {
-        //self.max_op = max(self.max_op, change.max_op());
-
         for d in change.deps.iter() {
             self.deps.remove(d);
         }
@@ -1,144 +1,12 @@
-#![allow(dead_code)]
-
 use fxhash::FxBuildHasher;
 //use im_rc::HashMap;
-use rand::rngs::ThreadRng;
-use rand::Rng;
-use std::cmp::{max, min};
+use std::borrow::Cow;
+use std::cmp::max;
 use std::collections::HashMap;
 use std::fmt::Debug;
 use std::hash::Hash;
 use std::iter::Iterator;
 use std::mem;
-use std::ops::AddAssign;
-
-#[derive(Debug, Copy, Clone, PartialEq)]
-struct Link<K>
-where
-    K: Clone + Copy + Debug + PartialEq,
-{
-    key: Option<K>,
-    count: usize,
-}
-
-#[derive(Debug, Clone, PartialEq)]
-struct LinkLevel<K>
-where
-    K: Copy + Clone + Debug + PartialEq,
-{
-    next: Link<K>,
-    prev: Link<K>,
-}
-
-#[derive(Debug, Clone, PartialEq)]
-struct Node<K>
-where
-    K: Copy + Clone + Debug + PartialEq,
-{
-    level: usize,
-    links: Vec<LinkLevel<K>>,
-    // IDEA: can I make this an unsized array??
-    // IDEA - Node could be Node(Vec<K>)
-}
-
-impl<K> AddAssign for Link<K>
-where
-    K: Copy + Clone + Debug + PartialEq,
-{
-    fn add_assign(&mut self, other: Self) {
-        self.key = other.key;
-        self.count += other.count;
-    }
-}
-
-impl<K> Node<K>
-where
-    K: Debug + Copy + Clone + PartialEq,
-{
-    fn successor(&self) -> Option<&K> {
-        if self.links.is_empty() {
-            None
-        } else {
-            self.links[0].next.key.as_ref()
-        }
-    }
-
-    fn remove_node_after(&mut self, from_level: usize, removed_level: usize, links: &[Link<K>]) {
-        for (level, link) in links.iter().enumerate().take(self.level).skip(from_level) {
-            if level < removed_level {
-                self.links[level].next = *link;
-            } else {
-                self.links[level].next.count -= 1;
-            }
-        }
-    }
-
-    fn remove_node_before(&mut self, from_level: usize, removed_level: usize, links: &[Link<K>]) {
-        for (level, link) in links.iter().enumerate().take(self.level).skip(from_level) {
-            if level < removed_level {
-                self.links[level].prev = *link;
-            } else {
-                self.links[level].prev.count -= 1;
-            }
-        }
-    }
-
-    fn insert_node_after(
-        &mut self,
-        new_key: &K,
-        new_level: usize,
-        from_level: usize,
-        distance: usize,
-        is_head: bool,
-    ) {
-        if new_level > self.level && !is_head {
-            panic!("Cannot increase the level of a non-head node")
-        }
-        self.level = max(self.level, new_level);
-
-        for level in from_level..self.level {
-            if level < new_level {
-                let next = Link {
-                    key: Some(*new_key),
-                    count: distance,
-                };
-                let prev = Link {
-                    key: None,
-                    count: 0,
-                };
-                if self.links.len() == level {
-                    self.links.push(LinkLevel { next, prev });
-                } else {
-                    self.links[level].next = next;
-                }
-            } else {
-                self.links[level].next.count += 1;
-            }
-        }
-    }
-
-    fn insert_node_before(
-        &mut self,
-        new_key: &K,
-        new_level: usize,
-        from_level: usize,
-        distance: usize,
-    ) {
-        if new_level > self.level {
-            panic!("Cannot increase the level on insert_node_before")
-        }
-        for level in from_level..self.level {
-            if level < new_level {
-                self.links[level].prev = Link {
-                    key: Some(*new_key),
-                    count: distance,
-                };
-            } else {
-                self.links[level].prev.count += 1;
-            }
-        }
-    }
-}
 
 #[derive(Debug, Clone, PartialEq)]
 pub(crate) struct VecOrderedSet<K>
@@ -168,78 +36,6 @@ where
     fn key_of(&self, index: usize) -> Option<&K>;
 }
 
-impl<K> OrderedSet<K> for SkipList<K>
-where
-    K: Copy + Clone + Debug + Hash + PartialEq + Eq,
-{
-    fn remove_index(&mut self, index: usize) -> Option<K> {
-        let key = self.key_of(index).cloned();
-        if let Some(ref k) = &key {
-            self.remove(k);
-        }
-        key
-    }
-
-    fn remove_key(&mut self, key: &K) -> Option<usize> {
-        let index = self.index_of(key);
-        if index.is_some() {
-            self.remove(key);
-        }
-        index
-    }
-
-    fn key_of(&self, index: usize) -> Option<&K> {
-        if index >= self.len {
-            return None;
-        }
-        let target = index + 1;
-        let mut node = &self.head;
-        let mut level = node.level - 1;
-        let mut count = 0;
-        loop {
-            while count + node.links[level].next.count > target {
-                level -= 1
-            }
-            count += node.links[level].next.count;
-            let k = node.links[level].next.key.as_ref();
-            if count == target {
-                return k;
-            }
-            node = self.get_node(k)
-        }
-    }
-
-    fn index_of(&self, key: &K) -> Option<usize> {
-        let mut count = 0;
-        let mut key = key;
-        loop {
-            if let Some(node) = self.nodes.get(key) {
-                let link = &node.links[node.level - 1].prev;
-                count += link.count;
-                if let Some(ref k) = &link.key {
-                    key = k;
-                } else {
-                    break;
-                }
-            } else {
-                return None;
-            }
-        }
-        Some(count - 1)
-    }
-
-    fn insert_index(&mut self, index: usize, key: K) -> bool {
-        if index == 0 {
-            self.insert_head(key)
-        } else {
-            self.key_of(index - 1)
-                .cloned()
-                .map(|suc| self.insert_after(&suc, key))
-                .unwrap_or(false)
-        }
-    }
-}
-
 impl<K> OrderedSet<K> for VecOrderedSet<K>
 where
     K: Clone + Debug + Hash + PartialEq + Eq,
@@ -276,15 +72,6 @@ where
     }
 }
 
-impl<K> Default for SkipList<K>
-where
-    K: Copy + Clone + Debug + Hash + PartialEq + Eq,
-{
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
 impl<K> Default for VecOrderedSet<K>
 where
     K: Clone + Debug + Hash + PartialEq + Eq,
@@ -306,400 +93,633 @@ where
     }
 }
 
-impl<'a, K> IntoIterator for &'a SkipList<K>
-where
-    K: Copy + Clone + Debug + Hash + PartialEq + Eq,
-{
-    type Item = &'a K;
-    type IntoIter = SkipIterator<'a, K>;
+/// The most items (keys in a leaf, children in an interior node) a node in a
+/// `CountedBTree` holds before it is split
+const MAX_ITEMS: usize = 64;
+/// Nodes other than the root with fewer items than this are merged with or
+/// topped up from a sibling
+const MIN_ITEMS: usize = MAX_ITEMS / 2;
 
-    fn into_iter(self) -> Self::IntoIter {
-        SkipIterator {
-            id: self.head.successor(),
-            nodes: &self.nodes,
+#[derive(Debug, Clone)]
+enum Items<K> {
+    Leaf(Vec<K>),
+    Interior(Vec<usize>),
+}
+
+impl<K> Items<K> {
+    fn len(&self) -> usize {
+        match self {
+            Items::Leaf(keys) => keys.len(),
+            Items::Interior(children) => children.len(),
         }
     }
 }
 
 #[derive(Debug, Clone)]
-pub(crate) struct SkipList<K>
-where
-    K: Copy + Clone + Debug + Hash + PartialEq + Eq,
-{
-    nodes: HashMap<K, Node<K>, FxBuildHasher>,
-    head: Node<K>,
-    rng: ThreadRng,
-    pub len: usize,
+struct BTreeNode<K> {
+    parent: Option<usize>,
+    /// The number of keys in this subtree
+    len: usize,
+    items: Items<K>,
 }
 
-impl<K> PartialEq for SkipList<K>
+/// An ordered set stored in a B-tree where every node records the number of
+/// keys beneath it, so finding the key at an index is a walk down from the
+/// root and finding the index of a key is a walk up from the leaf holding it.
+/// Both, along with inserts and removals, are O(log n).
+///
+/// Nodes live in an arena and refer to each other by their position in it,
+/// and `leaf_of` maps every key to the leaf it is stored in. The shape of the
+/// tree depends only on the edits made to it.
+#[derive(Debug, Clone)]
+pub struct CountedBTree<K>
 where
-    K: Copy + Clone + Debug + Hash + PartialEq + Eq,
+    K: Clone + Debug + Hash + PartialEq + Eq,
 {
-    fn eq(&self, other: &Self) -> bool {
-        self.nodes.eq(&other.nodes)
-    }
+    nodes: Vec<BTreeNode<K>>,
+    free: Vec<usize>,
+    root: usize,
+    leaf_of: HashMap<K, usize, FxBuildHasher>,
 }
 
-impl<K> SkipList<K>
+impl<K> CountedBTree<K>
 where
-    K: Copy + Clone + Debug + Hash + PartialEq + Eq,
+    K: Clone + Debug + Hash + PartialEq + Eq,
 {
-    pub fn new() -> SkipList<K> {
-        let nodes = HashMap::default();
-        let head = Node {
-            links: Vec::new(),
-            level: 1,
-            //is_head: true,
-        };
-        let len = 0;
-        let rng = rand::thread_rng();
-        SkipList {
-            nodes,
-            head,
-            len,
-            rng,
+    pub fn new() -> CountedBTree<K> {
+        CountedBTree {
+            nodes: vec![BTreeNode {
+                parent: None,
+                len: 0,
+                items: Items::Leaf(Vec::new()),
+            }],
+            free: Vec::new(),
+            root: 0,
+            leaf_of: HashMap::default(),
         }
     }
 
-    fn remove(&mut self, key: &K) {
-        let removed = self
-            .nodes
-            .remove(key)
-            .unwrap_or_else(|| panic!("The given key cannot be removed because it does not exist"));
-
-        let max_level = self.head.level;
-        let mut pre = self.predecessors(removed.links[0].prev.key.as_ref(), max_level);
-        let mut suc = self.successors(removed.links[0].next.key.as_ref(), max_level);
-
-        for i in 0..max_level {
-            let distance = pre[i].count + suc[i].count - 1;
-            pre[i].count = distance;
-            suc[i].count = distance;
-        }
-
-        self.len -= 1;
-        let mut pre_level = 0;
-        let mut suc_level = 0;
-
-        for level in 1..(max_level + 1) {
-            let update_level = min(level, removed.level);
-            if level == max_level
-                || pre.get(level).map(|l| &l.key) != pre.get(pre_level).map(|l| &l.key)
-            {
-                self.get_node_mut(pre[pre_level].key.as_ref())
-                    .remove_node_after(pre_level, update_level, &suc);
-                pre_level = level;
-            }
-            if suc[suc_level].key.is_some()
-                && (level == max_level
-                    || suc.get(level).map(|l| &l.key) != suc.get(suc_level).map(|l| &l.key))
-            {
-                self.get_node_mut(suc[suc_level].key.as_ref())
-                    .remove_node_before(suc_level, update_level, &pre);
-                suc_level = level;
-            }
-        }
+    pub fn len(&self) -> usize {
+        self.nodes[self.root].len
     }
 
-    fn get_node(&self, key: Option<&K>) -> &Node<K> {
-        if let Some(ref k) = key {
-            self.nodes
-                .get(k)
-                .unwrap_or_else(|| panic!(format!("get_node - missing key {:?}", key)))
-        } else {
-            &self.head
-        }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
     }
 
-    fn get_node_mut(&mut self, key: Option<&K>) -> &mut Node<K> {
-        if let Some(ref k) = key {
-            self.nodes
-                .get_mut(k)
-                .unwrap_or_else(|| panic!(format!("get_node - missing key {:?}", key)))
+    fn alloc(&mut self, node: BTreeNode<K>) -> usize {
+        if let Some(id) = self.free.pop() {
+            self.nodes[id] = node;
+            id
         } else {
-            &mut self.head
+            self.nodes.push(node);
+            self.nodes.len() - 1
         }
     }
 
-    // IDEA: Can i merge the successors and predecessors into a singlue unified function
-    // so we dont need to zip the results?
-    fn predecessors(&self, predecessor: Option<&K>, max_level: usize) -> Vec<Link<K>> {
-        let mut pre = Vec::with_capacity(max_level);
-        pre.push(Link {
-            key: predecessor.cloned(),
-            count: 1,
-        });
+    fn release(&mut self, id: usize) {
+        self.nodes[id].items = Items::Leaf(Vec::new());
+        self.free.push(id);
+    }
 
-        for level in 1..max_level {
-            let mut link = pre[level - 1];
-            while link.key.is_some() {
-                let node = self.get_node(link.key.as_ref());
-                if node.level > level {
-                    break;
+    /// The child of interior node `id` which holds `index`, and the index
+    /// within that child. With `inclusive` an index equal to the length of a
+    /// child is placed in that child rather than the next, which is where an
+    /// insert at that index goes.
+    fn child_at(&self, id: usize, mut index: usize, inclusive: bool) -> (usize, usize) {
+        let children = match &self.nodes[id].items {
+            Items::Interior(children) => children,
+            Items::Leaf(_) => panic!("child_at called on a leaf"),
+        };
+        let (last, rest) = children.split_last().unwrap();
+        for child in rest {
+            let len = self.nodes[*child].len;
+            if index < len || (inclusive && index == len) {
+                return (*child, index);
+            }
+            index -= len;
+        }
+        (*last, index)
+    }
+
+    /// Point every item of `id` back at it and recompute its length
+    fn adopt(&mut self, id: usize) {
+        let items = mem::replace(&mut self.nodes[id].items, Items::Leaf(Vec::new()));
+        let len = match &items {
+            Items::Leaf(keys) => {
+                for key in keys {
+                    self.leaf_of.insert(key.clone(), id);
                 }
-                if node.level < level {
-                    panic!("Level lower than expected");
+                keys.len()
+            }
+            Items::Interior(children) => {
+                let mut len = 0;
+                for child in children {
+                    self.nodes[*child].parent = Some(id);
+                    len += self.nodes[*child].len;
                 }
-                link += node.links[level - 1].prev;
+                len
             }
-            pre.push(link);
-        }
-        pre
+        };
+        let node = &mut self.nodes[id];
+        node.items = items;
+        node.len = len;
     }
 
-    fn successors(&self, successor: Option<&K>, max_level: usize) -> Vec<Link<K>> {
-        let mut suc = Vec::with_capacity(max_level);
-        suc.push(Link {
-            key: successor.cloned(),
-            count: 1,
+    /// Move the second half of an overfull node into a new sibling
+    fn split(&mut self, id: usize) {
+        let right_items = match &mut self.nodes[id].items {
+            Items::Leaf(keys) => Items::Leaf(keys.split_off(keys.len() / 2)),
+            Items::Interior(children) => Items::Interior(children.split_off(children.len() / 2)),
+        };
+        let parent = self.nodes[id].parent;
+        let right = self.alloc(BTreeNode {
+            parent,
+            len: 0,
+            items: right_items,
         });
+        self.adopt(right);
+        self.nodes[id].len -= self.nodes[right].len;
+        match parent {
+            Some(parent) => {
+                if let Items::Interior(children) = &mut self.nodes[parent].items {
+                    let position = children.iter().position(|c| *c == id).unwrap();
+                    children.insert(position + 1, right);
+                    if children.len() > MAX_ITEMS {
+                        self.split(parent);
+                    }
+                }
+            }
+            None => {
+                let root = self.alloc(BTreeNode {
+                    parent: None,
+                    len: 0,
+                    items: Items::Interior(vec![id, right]),
+                });
+                self.adopt(root);
+                self.root = root;
+            }
+        }
+    }
 
-        for level in 1..max_level {
-            let mut link = suc[level - 1];
-            while link.key.is_some() {
-                let node = self.get_node(link.key.as_ref());
-                if node.level > level {
-                    break;
+    /// Restore the minimum occupancy of `id` after something was removed
+    /// from it, by merging it with a sibling if they fit in one node or by
+    /// sharing the sibling's items between them if not
+    fn rebalance(&mut self, id: usize) {
+        let parent = match self.nodes[id].parent {
+            Some(parent) => parent,
+            None => {
+                // Collapse a root with a single child
+                if let Items::Interior(children) = &self.nodes[id].items {
+                    if children.len() == 1 {
+                        let child = children[0];
+                        self.nodes[child].parent = None;
+                        self.root = child;
+                        self.release(id);
+                    }
                 }
-                if node.level < level {
-                    panic!("Level lower than expected");
+                return;
+            }
+        };
+        if self.nodes[id].items.len() >= MIN_ITEMS {
+            return;
+        }
+        let (left, right) = match &self.nodes[parent].items {
+            Items::Interior(children) if children.len() > 1 => {
+                let position = children.iter().position(|c| *c == id).unwrap();
+                if position == 0 {
+                    (id, children[1])
+                } else {
+                    (children[position - 1], id)
                 }
-                link += node.links[level - 1].next;
             }
-            suc.push(link);
+            _ => {
+                self.rebalance(parent);
+                return;
+            }
+        };
+        let left_items = mem::replace(&mut self.nodes[left].items, Items::Leaf(Vec::new()));
+        let right_items = mem::replace(&mut self.nodes[right].items, Items::Leaf(Vec::new()));
+        if left_items.len() + right_items.len() <= MAX_ITEMS {
+            self.nodes[left].items = match (left_items, right_items) {
+                (Items::Leaf(mut l), Items::Leaf(r)) => {
+                    l.extend(r);
+                    Items::Leaf(l)
+                }
+                (Items::Interior(mut l), Items::Interior(r)) => {
+                    l.extend(r);
+                    Items::Interior(l)
+                }
+                _ => panic!("siblings at different depths"),
+            };
+            self.adopt(left);
+            self.release(right);
+            if let Items::Interior(children) = &mut self.nodes[parent].items {
+                children.retain(|c| *c != right);
+            }
+            self.rebalance(parent);
+        } else {
+            let (left_items, right_items) = match (left_items, right_items) {
+                (Items::Leaf(l), Items::Leaf(r)) => {
+                    let (l, r) = share(l, r);
+                    (Items::Leaf(l), Items::Leaf(r))
+                }
+                (Items::Interior(l), Items::Interior(r)) => {
+                    let (l, r) = share(l, r);
+                    (Items::Interior(l), Items::Interior(r))
+                }
+                _ => panic!("siblings at different depths"),
+            };
+            self.nodes[left].items = left_items;
+            self.nodes[right].items = right_items;
+            self.adopt(left);
+            self.adopt(right);
         }
-        suc
     }
+}
+
+/// Split the items of two siblings evenly between them
+fn share<T>(mut left: Vec<T>, right: Vec<T>) -> (Vec<T>, Vec<T>) {
+    let half = (left.len() + right.len()) / 2;
+    left.extend(right);
+    let right = left.split_off(half);
+    (left, right)
+}
 
-    pub fn insert_head(&mut self, key: K) -> bool {
-        self.insert(None, key)
+impl<K> OrderedSet<K> for CountedBTree<K>
+where
+    K: Clone + Debug + Hash + PartialEq + Eq,
+{
+    fn remove_index(&mut self, index: usize) -> Option<K> {
+        if index >= self.len() {
+            return None;
+        }
+        let mut id = self.root;
+        let mut index = index;
+        loop {
+            self.nodes[id].len -= 1;
+            if let Items::Leaf(keys) = &mut self.nodes[id].items {
+                let key = keys.remove(index);
+                self.leaf_of.remove(&key);
+                self.rebalance(id);
+                return Some(key);
+            }
+            let (child, child_index) = self.child_at(id, index, false);
+            id = child;
+            index = child_index;
+        }
     }
 
-    pub fn insert_after(&mut self, predecessor: &K, key: K) -> bool {
-        self.insert(Some(predecessor), key)
+    fn remove_key(&mut self, key: &K) -> Option<usize> {
+        let index = self.index_of(key)?;
+        self.remove_index(index);
+        Some(index)
     }
 
-    fn insert(&mut self, predecessor: Option<&K>, key: K) -> bool {
-        if self.nodes.contains_key(&key) {
-            return false;
+    fn key_of(&self, index: usize) -> Option<&K> {
+        if index >= self.len() {
+            return None;
+        }
+        let mut id = self.root;
+        let mut index = index;
+        loop {
+            if let Items::Leaf(keys) = &self.nodes[id].items {
+                return keys.get(index);
+            }
+            let (child, child_index) = self.child_at(id, index, false);
+            id = child;
+            index = child_index;
         }
+    }
 
-        let new_level = self.random_level();
-        let max_level = max(new_level, self.head.level);
-        let successor = self.get_node(predecessor).successor();
-        let mut pre = self.predecessors(predecessor, max_level);
-        let mut suc = self.successors(successor, max_level);
-
-        self.len += 1;
-
-        let mut pre_level = 0;
-        let mut suc_level = 0;
-        for level in 1..(max_level + 1) {
-            let update_level = min(level, new_level);
-            if level == max_level
-                || pre.get(level).map(|l| &l.key) != pre.get(pre_level).map(|l| &l.key)
-            {
-                self.get_node_mut(pre[pre_level].key.as_ref())
-                    .insert_node_after(
-                        &key,
-                        update_level,
-                        pre_level,
-                        pre[pre_level].count,
-                        pre[pre_level].key.is_none(),
-                    );
-                pre_level = level;
+    fn index_of(&self, key: &K) -> Option<usize> {
+        let mut id = *self.leaf_of.get(key)?;
+        let mut index = match &self.nodes[id].items {
+            Items::Leaf(keys) => keys.iter().position(|k| k == key)?,
+            Items::Interior(_) => return None,
+        };
+        while let Some(parent) = self.nodes[id].parent {
+            if let Items::Interior(children) = &self.nodes[parent].items {
+                for child in children.iter().take_while(|c| **c != id) {
+                    index += self.nodes[*child].len;
+                }
             }
-            if suc[suc_level].key.is_some()
-                && (level == max_level
-                    || suc.get(level).map(|l| &l.key) != suc.get(suc_level).map(|l| &l.key))
-            {
-                self.get_node_mut(suc[suc_level].key.as_ref())
-                    .insert_node_before(&key, update_level, suc_level, suc[suc_level].count);
-                suc_level = level;
+            id = parent;
+        }
+        Some(index)
+    }
+
+    fn insert_index(&mut self, index: usize, key: K) -> bool {
+        if index > self.len() || self.leaf_of.contains_key(&key) {
+            return false;
+        }
+        let mut id = self.root;
+        let mut index = index;
+        loop {
+            self.nodes[id].len += 1;
+            if let Items::Leaf(keys) = &mut self.nodes[id].items {
+                keys.insert(index, key.clone());
+                let full = keys.len() > MAX_ITEMS;
+                self.leaf_of.insert(key, id);
+                if full {
+                    self.split(id);
+                }
+                return true;
             }
+            let (child, child_index) = self.child_at(id, index, true);
+            id = child;
+            index = child_index;
         }
+    }
+}
 
-        pre.truncate(new_level);
-        suc.truncate(new_level);
-        let links = pre
-            .into_iter()
-            .zip(suc.into_iter())
-            .map(|(prev, next)| LinkLevel { prev, next })
-            .collect();
-        self.nodes.insert(
-            key,
-            Node {
-                level: new_level,
-                links,
-            },
-        );
-        true
+impl<K> Default for CountedBTree<K>
+where
+    K: Clone + Debug + Hash + PartialEq + Eq,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K> PartialEq for CountedBTree<K>
+where
+    K: Clone + Debug + Hash + PartialEq + Eq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.into_iter().eq(other)
     }
+}
 
-    // Returns a random number from the geometric distribution with p = 0.75.
-    // That is, returns k with probability p * (1 - p)^(k - 1).
-    // For example, returns 1 with probability 3/4, returns 2 with probability 3/16,
-    // returns 3 with probability 3/64, and so on.
+impl<'a, K> IntoIterator for &'a CountedBTree<K>
+where
+    K: Clone + Debug + Hash + PartialEq + Eq,
+{
+    type Item = &'a K;
+    type IntoIter = CountedBTreeIterator<'a, K>;
 
-    fn random_level(&mut self) -> usize {
-        // Create random number between 0 and 2^32 - 1
-        // Count leading zeros in that 32-bit number
-        let rand: u32 = self.rng.gen();
-        let mut level = 1;
-        while rand < 1 << (32 - 2 * level) && level < 16 {
-            level += 1
+    fn into_iter(self) -> Self::IntoIter {
+        CountedBTreeIterator {
+            tree: self,
+            stack: vec![(self.root, 0)],
+            leaf: [].iter(),
         }
-        level
     }
 }
 
-pub(crate) struct SkipIterator<'a, K>
+pub struct CountedBTreeIterator<'a, K>
 where
-    K: Debug + Copy + Clone + PartialEq,
+    K: Clone + Debug + Hash + PartialEq + Eq,
 {
-    id: Option<&'a K>,
-    nodes: &'a HashMap<K, Node<K>, FxBuildHasher>,
+    tree: &'a CountedBTree<K>,
+    /// The interior nodes above the current leaf and the next child to visit
+    /// in each of them
+    stack: Vec<(usize, usize)>,
+    leaf: std::slice::Iter<'a, K>,
 }
 
-impl<'a, K> Iterator for SkipIterator<'a, K>
+impl<'a, K> Iterator for CountedBTreeIterator<'a, K>
 where
-    K: Debug + Copy + Clone + Hash + PartialEq + Eq,
+    K: Clone + Debug + Hash + PartialEq + Eq,
 {
     type Item = &'a K;
 
     fn next(&mut self) -> Option<&'a K> {
-        let mut successor = match self.id {
-            None => None,
-            Some(ref key) => self.nodes.get(key).and_then(|n| n.successor()),
-        };
-        mem::swap(&mut successor, &mut self.id);
-        successor
+        loop {
+            if let Some(key) = self.leaf.next() {
+                return Some(key);
+            }
+            let (id, position) = self.stack.pop()?;
+            match &self.tree.nodes[id].items {
+                Items::Leaf(keys) => self.leaf = keys.iter(),
+                Items::Interior(children) => {
+                    if let Some(child) = children.get(position) {
+                        self.stack.push((id, position + 1));
+                        self.stack.push((*child, 0));
+                    }
+                }
+            }
+        }
     }
 }
 
+/// The uncommitted edits in an `OrdDelta`. Keys inserted by the edits are
+/// kept in order in `inserted`, each with its anchor: the number of keys of
+/// the base set (removed or not) which come before it. Keys removed from the
+/// base set are kept as their index in it, in ascending order in `removed`.
+/// Both are counted trees, so positions are found with binary searches over
+/// them rather than by replaying the edits.
 #[derive(Debug, Clone, PartialEq)]
-struct Delta<K>
+pub(crate) struct Delta<K>
 where
     K: Clone + Debug + Hash + PartialEq + Eq,
 {
-    index: isize,
-    key: Option<K>,
+    inserted: CountedBTree<K>,
+    anchors: HashMap<K, usize, FxBuildHasher>,
+    removed: CountedBTree<usize>,
 }
 
-// this is an experiment to if I can change request processing
-// index lookups by not mutating the skip list
-// throuput was quite signifigant actually - about 1.5x over in the
-// mass edit perf test
-// ideally we can speed up the skip list enough to not need this
-// also this could perform worse if the ops per change were huge
-// eg.. 10,000 changes with 10 ops each vs 10 changes with 10,000 ops each
+impl<K> Delta<K>
+where
+    K: Clone + Debug + Hash + PartialEq + Eq,
+{
+    pub fn new() -> Delta<K> {
+        Delta {
+            inserted: CountedBTree::new(),
+            anchors: HashMap::default(),
+            removed: CountedBTree::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inserted.is_empty() && self.removed.is_empty()
+    }
+
+    /// The number of keys removed from the base set before base index `index`
+    fn removed_before(&self, index: usize) -> usize {
+        partition_point(&self.removed, |_, removed| *removed < index)
+    }
 
-/*
+    /// The base index of the `n`th key of the base set which is not removed
+    fn nth_kept(&self, n: usize) -> usize {
+        n + partition_point(&self.removed, |i, removed| *removed - i <= n)
+    }
+
+    /// The index in the edited set of the inserted key at `index` in
+    /// `inserted`
+    fn inserted_index(&self, index: usize, key: &K) -> usize {
+        let anchor = self.anchors[key];
+        anchor - self.removed_before(anchor) + index
+    }
+
+    /// The number of inserted keys which come before `index` in the edited
+    /// set, or at it with `inclusive`
+    fn inserted_before(&self, index: usize, inclusive: bool) -> usize {
+        partition_point(&self.inserted, |i, key| {
+            let i = self.inserted_index(i, key);
+            i < index || (inclusive && i == index)
+        })
+    }
+}
+
+impl<K> Default for Delta<K>
+where
+    K: Clone + Debug + Hash + PartialEq + Eq,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The number of keys at the start of `set` for which `pred` holds, `pred`
+/// must hold for every key before any for which it does not
+fn partition_point<K, F>(set: &CountedBTree<K>, pred: F) -> usize
+where
+    K: Clone + Debug + Hash + PartialEq + Eq,
+    F: Fn(usize, &K) -> bool,
+{
+    let (mut low, mut high) = (0, set.len());
+    while low < high {
+        let mid = (low + high) / 2;
+        if matches!(set.key_of(mid), Some(key) if pred(mid, key)) {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+    low
+}
+
+/// An ordered set made up of a base set and some edits on top of it. This
+/// lets us work out the indices a set of changes would produce without
+/// mutating (or cloning) the base set, the edits can then be committed to the
+/// base with `commit` or thrown away.
+///
+/// Lookups are O(log n) in the base plus O(log² k) for k edits.
 #[derive(Debug, Clone, PartialEq)]
-pub(crate) struct OrdDelta<'a, K>
+pub(crate) struct OrdDelta<'a, K, B>
 where
     K: Clone + Debug + Hash + PartialEq + Eq,
+    B: OrderedSet<K>,
 {
-    list: Option<&'a SkipList<K>>,
-    delta: Vec<Delta<K>>,
+    base: &'a B,
+    delta: Cow<'a, Delta<K>>,
 }
 
-impl<'a, K> OrdDelta<'a, K>
+impl<'a, K, B> OrdDelta<'a, K, B>
 where
     K: Clone + Debug + Hash + PartialEq + Eq,
+    B: OrderedSet<K>,
 {
-    pub fn new(list: Option<&'a SkipList<K>>) -> OrdDelta<'a, K> {
+    #[cfg(test)]
+    pub fn new(base: &'a B) -> OrdDelta<'a, K, B> {
         OrdDelta {
-            list,
-            delta: Vec::new(),
+            base,
+            delta: Cow::Owned(Delta::new()),
+        }
+    }
+
+    pub fn with_delta(base: &'a B, delta: Cow<'a, Delta<K>>) -> OrdDelta<'a, K, B> {
+        OrdDelta { base, delta }
+    }
+
+    pub fn into_delta(self) -> Delta<K> {
+        self.delta.into_owned()
+    }
+
+    /// Apply `delta` to `base`, removals first from the back so that the
+    /// base indices stay valid, then insertions from the front at the index
+    /// they have once every edit is made
+    pub fn commit(base: &mut B, delta: Delta<K>) {
+        let inserted: Vec<_> = delta
+            .inserted
+            .into_iter()
+            .enumerate()
+            .map(|(i, key)| (delta.inserted_index(i, key), key.clone()))
+            .collect();
+        let removed: Vec<_> = delta.removed.into_iter().cloned().collect();
+        for index in removed.into_iter().rev() {
+            base.remove_index(index);
+        }
+        for (index, key) in inserted {
+            base.insert_index(index, key);
+        }
+    }
+
+    fn remove(&mut self, key: &K) {
+        let delta = self.delta.to_mut();
+        if delta.anchors.remove(key).is_some() {
+            delta.inserted.remove_key(key);
+        } else if let Some(index) = self.base.index_of(key) {
+            let position = delta.removed_before(index);
+            delta.removed.insert_index(position, index);
         }
     }
 }
 
-impl<'a, K> OrderedSet<K> for OrdDelta<'a, K>
+impl<'a, K, B> OrderedSet<K> for OrdDelta<'a, K, B>
 where
     K: Clone + Debug + Hash + PartialEq + Eq,
+    B: OrderedSet<K>,
 {
     fn insert_index(&mut self, index: usize, key: K) -> bool {
-        let index = index as isize;
-        let delta = Delta {
-            index,
-            key: Some(key),
+        if self.index_of(&key).is_some() || (index > 0 && self.key_of(index - 1).is_none()) {
+            return false;
+        }
+        // Anchor the key after the last base key before it, and after any
+        // inserted key before it which is anchored later than that
+        let position = self.delta.inserted_before(index, false);
+        let kept = index - position;
+        let mut anchor = match kept {
+            0 => 0,
+            kept => self.delta.nth_kept(kept - 1) + 1,
         };
-        for i in 0..self.delta.len() {
-            if self.delta[i].index >= index {
-                self.delta.iter_mut().skip(i).for_each(|d| d.index += 1);
-                self.delta.insert(i, delta);
-                return true;
-            }
+        if let Some(previous) = position.checked_sub(1) {
+            let previous = self.delta.inserted.key_of(previous).unwrap();
+            anchor = max(anchor, self.delta.anchors[previous]);
         }
-        self.delta.push(delta);
+        let delta = self.delta.to_mut();
+        delta.inserted.insert_index(position, key.clone());
+        delta.anchors.insert(key, anchor);
         true
     }
 
     fn key_of(&self, index: usize) -> Option<&K> {
-        let index = index as isize;
-        let mut acc: isize = 0;
-        for i in 0..self.delta.len() {
-            match &self.delta[i] {
-                Delta {
-                    index: j,
-                    key: Some(key),
-                } => {
-                    if j == &index {
-                        return Some(&key);
-                    }
-                    if j > &index {
-                        break;
-                    }
-                    acc += 1;
-                }
-                Delta {
-                    index: j,
-                    key: None,
-                } => {
-                    if j > &index {
-                        break;
-                    }
-                    acc -= 1;
-                }
+        let position = self.delta.inserted_before(index, true);
+        if let Some(last) = position.checked_sub(1) {
+            let key = self.delta.inserted.key_of(last).unwrap();
+            if self.delta.inserted_index(last, key) == index {
+                return Some(key);
             }
         }
-        self.list
-            .and_then(|l| l.key_of((index as isize - acc) as usize))
+        self.base.key_of(self.delta.nth_kept(index - position))
     }
 
     fn remove_index(&mut self, index: usize) -> Option<K> {
-        let index = index as isize;
-        let delta = Delta { index, key: None };
-        for i in 0..self.delta.len() {
-            if self.delta[i].index == index && self.delta[i].key.is_some() {
-                let old_insert = self.delta.remove(i);
-                self.delta.iter_mut().skip(i).for_each(|d| d.index -= 1);
-                return old_insert.key;
-            }
-            if self.delta[i].index > index {
-                let key = self.key_of(index as usize).cloned();
-                self.delta.iter_mut().skip(i).for_each(|d| d.index -= 1);
-                self.delta.insert(i, delta);
-                return key;
-            }
-        }
-        let key = self.key_of(index as usize).cloned();
-        self.delta.push(delta);
-        key
+        let key = self.key_of(index).cloned()?;
+        self.remove(&key);
+        Some(key)
     }
 
-    fn index_of(&self, _key: &K) -> Option<usize> {
-        panic!("not implemented");
+    fn index_of(&self, key: &K) -> Option<usize> {
+        if self.delta.anchors.contains_key(key) {
+            let position = self.delta.inserted.index_of(key)?;
+            return Some(self.delta.inserted_index(position, key));
+        }
+        let index = self.base.index_of(key)?;
+        if self.delta.removed.index_of(&index).is_some() {
+            return None;
+        }
+        let inserted = partition_point(&self.delta.inserted, |_, k| self.delta.anchors[k] <= index);
+        Some(index - self.delta.removed_before(index) + inserted)
     }
 
-    fn remove_key(&mut self, _key: &K) -> Option<usize> {
-        panic!("not implemented");
+    fn remove_key(&mut self, key: &K) -> Option<usize> {
+        let index = self.index_of(key)?;
+        self.remove(key);
+        Some(index)
     }
 }
-*/
 
 // get(n)
 // insert(n)
@@ -711,26 +731,27 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
     //use std::str::FromStr;
 
     #[test]
     fn test_index_of() {
-        let mut s = SkipList::<&str>::new();
+        let mut s = CountedBTree::<&str>::new();
 
         // should return None on an empty list
         assert_eq!(s.index_of(&"foo"), None);
 
         // should return None for a nonexistent key
-        s.insert_head("foo");
+        s.insert_index(0, "foo");
         assert_eq!(s.index_of(&"baz"), None);
 
         // should return 0 for the first list element
         assert_eq!(s.index_of(&"foo"), Some(0));
 
         // should return length-1 for the last list element
-        s.insert_after(&"foo", "bar");
-        s.insert_after(&"bar", "baz");
-        assert_eq!(s.index_of(&"baz"), Some(s.len - 1));
+        s.insert_index(1, "bar");
+        s.insert_index(2, "baz");
+        assert_eq!(s.index_of(&"baz"), Some(s.len() - 1));
 
         // should adjust based on removed elements
         s.remove_key(&"foo");
@@ -742,33 +763,33 @@ mod tests {
 
     #[test]
     fn test_len() {
-        let mut s = SkipList::<&str>::new();
+        let mut s = CountedBTree::<&str>::new();
 
         //should be 0 for an empty list
-        assert_eq!(s.len, 0);
+        assert_eq!(s.len(), 0);
 
         // should increase by 1 for every insertion
-        s.insert_head("a3");
-        s.insert_head("a2");
-        s.insert_head("a1");
-        assert_eq!(s.len, 3);
+        s.insert_index(0, "a3");
+        s.insert_index(0, "a2");
+        s.insert_index(0, "a1");
+        assert_eq!(s.len(), 3);
 
         //should decrease by 1 for every removal
         s.remove_key(&"a2");
-        assert_eq!(s.len, 2);
+        assert_eq!(s.len(), 2);
     }
 
     #[test]
     fn test_key_of() {
-        let mut s = SkipList::<&str>::new();
+        let mut s = CountedBTree::<&str>::new();
 
         // should return None on an empty list
         assert_eq!(s.key_of(0), None);
 
         // should return None for an index past the end of the list
-        s.insert_head("a3");
-        s.insert_head("a2");
-        s.insert_head("a1");
+        s.insert_index(0, "a3");
+        s.insert_index(0, "a2");
+        s.insert_index(0, "a1");
         assert_eq!(s.key_of(10), None);
 
         // should return the first key for index 0
@@ -778,7 +799,7 @@ mod tests {
         // assert_eq!(s.key_of(-1), Some("a3"));
 
         // should return the last key for index length-1
-        assert_eq!(s.key_of(s.len - 1), Some(&"a3"));
+        assert_eq!(s.key_of(s.len() - 1), Some(&"a3"));
 
         // should not count removed elements
         s.remove_key(&"a1");
@@ -788,11 +809,11 @@ mod tests {
 
     #[test]
     fn test_insert_index() {
-        let mut s = SkipList::<&str>::new();
+        let mut s = CountedBTree::<&str>::new();
 
         // should insert the new key-value pair at the given index
-        s.insert_head("aaa");
-        s.insert_after(&"aaa", "ccc");
+        s.insert_index(0, "aaa");
+        s.insert_index(1, "ccc");
         s.insert_index(1, "bbb");
         assert_eq!(s.index_of(&"aaa"), Some(0));
         assert_eq!(s.index_of(&"bbb"), Some(1));
@@ -805,12 +826,12 @@ mod tests {
 
     #[test]
     fn test_remove_index() {
-        let mut s = SkipList::<&str>::new();
+        let mut s = CountedBTree::<&str>::new();
 
         // should remove the value at the given index
-        s.insert_head("ccc");
-        s.insert_head("bbb");
-        s.insert_head("aaa");
+        s.insert_index(0, "ccc");
+        s.insert_index(0, "bbb");
+        s.insert_index(0, "aaa");
         s.remove_index(1);
         assert_eq!(s.index_of(&"aaa"), Some(0));
         assert_eq!(s.index_of(&"bbb"), None);
@@ -830,9 +851,9 @@ mod tests {
             })
             .collect();
 
-        let mut s = SkipList::<&str>::new();
+        let mut s = CountedBTree::<&str>::new();
         for elem in elems.iter() {
-            s.insert_head(elem);
+            s.insert_index(0, elem);
         }
 
         assert_eq!(s.index_of(&"a20"), Some(20));
@@ -852,28 +873,28 @@ mod tests {
 
     #[test]
     fn test_remove_key() {
-        let mut s = SkipList::<&str>::new();
-        s.insert_head("a20");
-        s.insert_head("a19");
-        s.insert_head("a18");
-        s.insert_head("a17");
-        s.insert_head("a16");
-        s.insert_head("a15");
-        s.insert_head("a14");
-        s.insert_head("a13");
-        s.insert_head("a12");
-        s.insert_head("a11");
-        s.insert_head("a10");
-        s.insert_head("a9");
-        s.insert_head("a8");
-        s.insert_head("a7");
-        s.insert_head("a6");
-        s.insert_head("a5");
-        s.insert_head("a4");
-        s.insert_head("a3");
-        s.insert_head("a2");
-        s.insert_head("a1");
-        s.insert_head("a0");
+        let mut s = CountedBTree::<&str>::new();
+        s.insert_index(0, "a20");
+        s.insert_index(0, "a19");
+        s.insert_index(0, "a18");
+        s.insert_index(0, "a17");
+        s.insert_index(0, "a16");
+        s.insert_index(0, "a15");
+        s.insert_index(0, "a14");
+        s.insert_index(0, "a13");
+        s.insert_index(0, "a12");
+        s.insert_index(0, "a11");
+        s.insert_index(0, "a10");
+        s.insert_index(0, "a9");
+        s.insert_index(0, "a8");
+        s.insert_index(0, "a7");
+        s.insert_index(0, "a6");
+        s.insert_index(0, "a5");
+        s.insert_index(0, "a4");
+        s.insert_index(0, "a3");
+        s.insert_index(0, "a2");
+        s.insert_index(0, "a1");
+        s.insert_index(0, "a0");
 
         assert_eq!(s.index_of(&"a20"), Some(20));
 
@@ -891,4 +912,140 @@ mod tests {
         assert_eq!(s.index_of(&"a20"), Some(10));
         assert_eq!(s.index_of(&"a10"), Some(5));
     }
+
+    #[derive(Debug, Clone)]
+    enum Edit {
+        Insert(usize),
+        RemoveIndex(usize),
+        RemoveKey(u32),
+    }
+
+    fn arb_edit() -> impl Strategy<Value = Edit> {
+        prop_oneof![
+            any::<usize>().prop_map(Edit::Insert),
+            any::<usize>().prop_map(Edit::RemoveIndex),
+            (0..1000u32).prop_map(Edit::RemoveKey),
+        ]
+    }
+
+    /// Apply the same edits to `set` and `expected`, with insert and remove
+    /// indices wrapped to the length of `expected` so that they are valid
+    fn apply_edits<S: OrderedSet<u32>>(
+        set: &mut S,
+        expected: &mut VecOrderedSet<u32>,
+        edits: &[Edit],
+        next_key: &mut u32,
+    ) {
+        for edit in edits {
+            let len = expected.keys.len();
+            match edit {
+                Edit::Insert(index) => {
+                    let index = index % (len + 1);
+                    assert!(set.insert_index(index, *next_key));
+                    expected.insert_index(index, *next_key);
+                    *next_key += 1;
+                }
+                Edit::RemoveIndex(index) if len > 0 => {
+                    let index = index % len;
+                    assert_eq!(set.remove_index(index), expected.remove_index(index));
+                }
+                Edit::RemoveIndex(index) => assert_eq!(set.remove_index(*index), None),
+                Edit::RemoveKey(key) => {
+                    assert_eq!(set.remove_key(key), expected.remove_key(key));
+                }
+            }
+        }
+    }
+
+    fn assert_same<S: OrderedSet<u32>>(set: &S, expected: &VecOrderedSet<u32>, max_key: u32) {
+        for index in 0..=expected.keys.len() {
+            assert_eq!(set.key_of(index), expected.key_of(index));
+        }
+        for key in 0..max_key {
+            assert_eq!(set.index_of(&key), expected.index_of(&key));
+        }
+    }
+
+    #[test]
+    fn test_counted_btree_grows_and_shrinks() {
+        // Enough keys for a tree three levels deep, inserted in the middle and
+        // removed from the front so that every node is split and merged
+        let mut tree = CountedBTree::new();
+        let mut expected = VecOrderedSet::new();
+        for key in 0..20_000 {
+            let index = tree.len() / 2;
+            assert!(tree.insert_index(index, key));
+            expected.insert_index(index, key);
+        }
+        assert!(!tree.insert_index(0, 0));
+        assert_eq!(
+            tree.into_iter().collect::<Vec<_>>(),
+            expected.into_iter().collect::<Vec<_>>()
+        );
+        for key in (0..20_000).step_by(997) {
+            assert_eq!(tree.index_of(&key), expected.index_of(&key));
+        }
+        while !tree.is_empty() {
+            assert_eq!(tree.remove_index(0), expected.remove_index(0));
+            let index = tree.len() / 3;
+            assert_eq!(tree.key_of(index), expected.key_of(index));
+        }
+        assert_eq!(tree, CountedBTree::new());
+        assert_eq!(tree.nodes.len() - tree.free.len(), 1);
+    }
+
+    proptest! {
+        #[test]
+        fn test_ord_delta_matches_vec_ordered_set(
+            base_edits in proptest::collection::vec(arb_edit(), 0..40),
+            delta_edits in proptest::collection::vec(arb_edit(), 0..200),
+        ) {
+            let mut next_key = 0;
+            let mut base = CountedBTree::new();
+            let mut expected = VecOrderedSet::new();
+            apply_edits(&mut base, &mut expected, &base_edits, &mut next_key);
+            let before = expected.clone();
+
+            let mut delta = OrdDelta::new(&base);
+            apply_edits(&mut delta, &mut expected, &delta_edits, &mut next_key);
+            assert_same(&delta, &expected, next_key);
+            let delta = delta.into_delta();
+
+            // The base is untouched until the delta is committed
+            assert_same(&base, &before, next_key);
+            OrdDelta::commit(&mut base, delta);
+            assert_same(&base, &expected, next_key);
+            prop_assert_eq!(base.into_iter().cloned().collect::<Vec<_>>(), expected.keys);
+        }
+
+        #[test]
+        fn test_counted_btree_matches_vec_ordered_set(
+            edits in proptest::collection::vec(
+                prop_oneof![
+                    3 => any::<usize>().prop_map(Edit::Insert),
+                    1 => any::<usize>().prop_map(Edit::RemoveIndex),
+                ],
+                0..1000,
+            ),
+            removals in proptest::collection::vec(arb_edit(), 0..1000),
+        ) {
+            // Grow the tree enough to split nodes, then shrink it to merge them
+            let mut next_key = 0;
+            let mut tree = CountedBTree::new();
+            let mut expected = VecOrderedSet::new();
+            apply_edits(&mut tree, &mut expected, &edits, &mut next_key);
+            assert_same(&tree, &expected, next_key);
+            let removals: Vec<_> = removals
+                .into_iter()
+                .map(|edit| match edit {
+                    Edit::Insert(index) => Edit::RemoveIndex(index),
+                    edit => edit,
+                })
+                .collect();
+            apply_edits(&mut tree, &mut expected, &removals, &mut next_key);
+            assert_same(&tree, &expected, next_key);
+            prop_assert_eq!(tree.len(), expected.keys.len());
+            prop_assert_eq!(tree.into_iter().cloned().collect::<Vec<_>>(), expected.keys);
+        }
+    }
 }
@@ -27,6 +27,7 @@ fn test_incremental_diffs_in_a_map() {
             pred: Vec::new(),
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     }
     .try_into()
     .unwrap();
@@ -69,6 +70,7 @@ fn test_increment_key_in_map() {
             pred: Vec::new(),
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     }
     .try_into()
     .unwrap();
@@ -88,6 +90,7 @@ fn test_increment_key_in_map() {
             pred: vec![actor.op_id_at(1)],
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     }
     .try_into()
     .unwrap();
@@ -134,6 +137,7 @@ fn test_conflict_on_assignment_to_same_map_key() {
             pred: Vec::new(),
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     }
     .try_into()
     .unwrap();
@@ -154,6 +158,7 @@ fn test_conflict_on_assignment_to_same_map_key() {
             insert: false,
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     }
     .try_into()
     .unwrap();
@@ -204,6 +209,7 @@ fn delete_key_from_map() {
             insert: false,
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     }
     .try_into()
     .unwrap();
@@ -223,6 +229,7 @@ fn delete_key_from_map() {
             insert: false,
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     }
     .try_into()
     .unwrap();
@@ -275,6 +282,7 @@ fn create_nested_maps() {
             },
         ],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     }
     .try_into()
     .unwrap();
@@ -336,6 +344,7 @@ fn test_assign_to_nested_keys_in_map() {
             },
         ],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     }
     .try_into()
     .unwrap();
@@ -355,6 +364,7 @@ fn test_assign_to_nested_keys_in_map() {
             insert: false,
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     }
     .try_into()
     .unwrap();
@@ -419,6 +429,7 @@ fn test_create_lists() {
             },
         ],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     }
     .try_into()
     .unwrap();
@@ -483,6 +494,7 @@ fn test_apply_updates_inside_lists() {
             },
         ],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     }
     .try_into()
     .unwrap();
@@ -502,6 +514,7 @@ fn test_apply_updates_inside_lists() {
             insert: false,
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     }
     .try_into()
     .unwrap();
@@ -567,6 +580,7 @@ fn test_delete_list_elements() {
             },
         ],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     }
     .try_into()
     .unwrap();
@@ -586,6 +600,7 @@ fn test_delete_list_elements() {
             insert: false,
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     }
     .try_into()
     .unwrap();
@@ -638,6 +653,7 @@ fn test_handle_list_element_insertion_and_deletion_in_same_change() {
             insert: false,
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     }
     .try_into()
     .unwrap();
@@ -666,6 +682,7 @@ fn test_handle_list_element_insertion_and_deletion_in_same_change() {
             },
         ],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     }
     .try_into()
     .unwrap();
@@ -677,7 +694,12 @@ fn test_handle_list_element_insertion_and_deletion_in_same_change() {
         seq: None,
         actor: None,
         max_op: 3,
-        deps: vec![change2.hash, change1.hash],
+        // heads are reported in hash order
+        deps: {
+            let mut deps = vec![change2.hash, change1.hash];
+            deps.sort();
+            deps
+        },
         diffs: Some(Diff::Map(MapDiff {
             object_id: ObjectID::Root,
             obj_type: MapType::Map,
@@ -722,6 +744,7 @@ fn test_handle_changes_within_conflicted_objects() {
             insert: false,
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     }
     .try_into()
     .unwrap();
@@ -741,6 +764,7 @@ fn test_handle_changes_within_conflicted_objects() {
             insert: false,
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     }
     .try_into()
     .unwrap();
@@ -760,6 +784,7 @@ fn test_handle_changes_within_conflicted_objects() {
             insert: false,
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     }
     .try_into()
     .unwrap();
@@ -772,7 +797,12 @@ fn test_handle_changes_within_conflicted_objects() {
             actor2.clone() => 2,
         },
         max_op: 2,
-        deps: vec![change3.hash, change1.hash],
+        // heads are reported in hash order
+        deps: {
+            let mut deps = vec![change3.hash, change1.hash];
+            deps.sort();
+            deps
+        },
         diffs: Some(Diff::Map(MapDiff {
             object_id: ObjectID::Root,
             obj_type: MapType::Map,
@@ -821,6 +851,7 @@ fn test_support_date_objects_at_root() {
             insert: false,
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     }
     .try_into()
     .unwrap();
@@ -876,6 +907,7 @@ fn test_support_date_objects_in_a_list() {
             },
         ],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     }
     .try_into()
     .unwrap();
@@ -912,3 +944,175 @@ fn test_support_date_objects_in_a_list() {
     let patch = backend.apply_changes(vec![change]).unwrap();
     assert_eq!(patch, expected_patch)
 }
+
+#[test]
+fn test_preview_changes_does_not_modify_the_backend() {
+    use automerge_frontend::{
+        Frontend, InvalidChangeRequest, LocalChange, MutableDocument, Path, Value,
+    };
+
+    let text = || Path::root().key("text");
+    let mut frontend = Frontend::new();
+    let mut author = Backend::init();
+    let mut changes = Vec::new();
+    let mut edit = |f: &dyn Fn(&mut dyn MutableDocument) -> Result<(), InvalidChangeRequest>| {
+        let request = frontend.change(None, |doc| f(doc)).unwrap().unwrap();
+        let (patch, change) = author.apply_local_change(request).unwrap();
+        frontend.apply_patch(patch).unwrap();
+        changes.push((*change).clone());
+    };
+    edit(&|doc| {
+        doc.add_change(LocalChange::set(
+            text(),
+            Value::Text("hello".chars().collect()),
+        ))
+    });
+    edit(&|doc| {
+        doc.add_change(LocalChange::insert(
+            text().index(5),
+            Value::Primitive(ScalarValue::Str("!".into())),
+        ))?;
+        doc.add_change(LocalChange::delete(text().index(0)))
+    });
+    edit(&|doc| {
+        doc.add_change(LocalChange::delete(text().index(1)))?;
+        doc.add_change(LocalChange::insert(
+            text().index(0),
+            Value::Primitive(ScalarValue::Str("j".into())),
+        ))
+    });
+
+    let mut backend = Backend::init();
+    backend.apply_changes(changes[..1].to_vec()).unwrap();
+    let before = backend.get_patch().unwrap();
+
+    let preview = backend.preview_changes(changes[1..].to_vec()).unwrap();
+    assert_eq!(backend.get_patch().unwrap(), before);
+    assert_eq!(backend.get_heads(), vec![changes[0].hash]);
+
+    let patch = backend.apply_changes(changes[1..].to_vec()).unwrap();
+    assert_eq!(preview, patch);
+    assert_eq!(backend.get_patch().unwrap(), author.get_patch().unwrap());
+}
+
+#[test]
+fn test_ops_we_cannot_interpret_are_ignored() {
+    let actor: ActorID = "2d6c2b1bd8a44d1e8a3d8d4bd16b1f0e".try_into().unwrap();
+    let unknown = |action, value_type, key: &str| Op {
+        action: amp::OpType::Unknown {
+            action,
+            value_type,
+            value: vec![1, 2, 3],
+        },
+        obj: ObjectID::Root,
+        key: key.into(),
+        insert: false,
+        pred: Vec::new(),
+    };
+    let change1: Change = UncompressedChange {
+        actor_id: actor.clone(),
+        seq: 1,
+        start_op: 1,
+        time: 0,
+        deps: Vec::new(),
+        message: None,
+        operations: vec![
+            // An action we don't know, which might create an object
+            unknown(20, 6, "future"),
+            // An op inside the object the unknown op may have created
+            Op {
+                action: amp::OpType::Set("inside".into()),
+                obj: actor.op_id_at(1).into(),
+                key: "key".into(),
+                insert: false,
+                pred: Vec::new(),
+            },
+            // A set of a value of an unknown type
+            unknown(1, 12, "data"),
+            Op {
+                action: amp::OpType::Set("magpie".into()),
+                obj: ObjectID::Root,
+                key: "bird".into(),
+                insert: false,
+                pred: Vec::new(),
+            },
+        ],
+        extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
+    }
+    .try_into()
+    .unwrap();
+
+    let change2: Change = UncompressedChange {
+        actor_id: actor.clone(),
+        seq: 2,
+        start_op: 5,
+        time: 0,
+        deps: vec![change1.hash],
+        message: None,
+        operations: vec![Op {
+            action: amp::OpType::Set("present".into()),
+            obj: ObjectID::Root,
+            key: "future".into(),
+            insert: false,
+            pred: vec![actor.op_id_at(1)],
+        }],
+        extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
+    }
+    .try_into()
+    .unwrap();
+
+    let mut backend = Backend::init();
+    let patch1 = backend.apply_changes(vec![change1.clone()]).unwrap();
+    let expected_patch1 = Patch {
+        clock: hashmap! {actor.clone() => 1},
+        max_op: 4,
+        seq: None,
+        actor: None,
+        deps: vec![change1.hash],
+        diffs: Some(Diff::Map(MapDiff {
+            object_id: ObjectID::Root,
+            obj_type: MapType::Map,
+            props: hashmap! {
+                "bird".into() => hashmap!{
+                    actor.op_id_at(4) => "magpie".into()
+                }
+            },
+        })),
+    };
+    assert_eq!(patch1, expected_patch1);
+
+    let patch2 = backend.apply_changes(vec![change2.clone()]).unwrap();
+    let expected_patch2 = Patch {
+        clock: hashmap! {actor.clone() => 2},
+        max_op: 5,
+        seq: None,
+        actor: None,
+        deps: vec![change2.hash],
+        diffs: Some(Diff::Map(MapDiff {
+            object_id: ObjectID::Root,
+            obj_type: MapType::Map,
+            props: hashmap! {
+                "future".into() => hashmap!{
+                    actor.op_id_at(5) => "present".into()
+                }
+            },
+        })),
+    };
+    assert_eq!(patch2, expected_patch2);
+
+    // The unknown ops are kept in the change, so it is stored and forwarded
+    // with the hash it was created with
+    let saved = backend.save().unwrap();
+    let loaded = Backend::load(saved).unwrap();
+    assert_eq!(loaded.get_patch().unwrap(), backend.get_patch().unwrap());
+    assert_eq!(
+        loaded
+            .get_changes(&[])
+            .into_iter()
+            .cloned()
+            .collect::<Vec<_>>(),
+        vec![change1, change2]
+    );
+}
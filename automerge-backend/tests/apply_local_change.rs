@@ -28,6 +28,7 @@ fn test_apply_local_change() {
             pred: Vec::new(),
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     };
 
     let mut backend = Backend::init();
@@ -49,6 +50,7 @@ fn test_apply_local_change() {
             insert: false,
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     }
     .try_into()
     .unwrap();
@@ -93,6 +95,7 @@ fn test_error_on_duplicate_requests() {
             pred: Vec::new(),
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     };
 
     let change_request2 = UncompressedChange {
@@ -110,6 +113,7 @@ fn test_error_on_duplicate_requests() {
             pred: Vec::new(),
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     };
     let mut backend = Backend::init();
     backend.apply_local_change(change_request1.clone()).unwrap();
@@ -136,6 +140,7 @@ fn test_handle_concurrent_frontend_and_backend_changes() {
             pred: Vec::new(),
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     };
 
     let local2 = UncompressedChange {
@@ -153,6 +158,7 @@ fn test_handle_concurrent_frontend_and_backend_changes() {
             pred: vec![actor.op_id_at(1)],
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     };
     let remote_actor: ActorID = "6d48a01318644eed90455d2cb68ac657".try_into().unwrap();
     let remote1 = UncompressedChange {
@@ -170,6 +176,7 @@ fn test_handle_concurrent_frontend_and_backend_changes() {
             insert: false,
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     }
     .try_into()
     .unwrap();
@@ -189,6 +196,7 @@ fn test_handle_concurrent_frontend_and_backend_changes() {
             insert: false,
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     };
 
     let mut expected_change2 = UncompressedChange {
@@ -206,6 +214,7 @@ fn test_handle_concurrent_frontend_and_backend_changes() {
             insert: false,
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     };
 
     let mut expected_change3 = UncompressedChange {
@@ -223,6 +232,7 @@ fn test_handle_concurrent_frontend_and_backend_changes() {
             insert: false,
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     };
     let mut backend = Backend::init();
     backend.apply_local_change(local1).unwrap();
@@ -269,6 +279,7 @@ fn test_transform_list_indexes_into_element_ids() {
             insert: false,
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     }
     .try_into()
     .unwrap();
@@ -288,6 +299,7 @@ fn test_transform_list_indexes_into_element_ids() {
             pred: Vec::new(),
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     }
     .try_into()
     .unwrap();
@@ -307,6 +319,7 @@ fn test_transform_list_indexes_into_element_ids() {
             pred: Vec::new(),
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     };
     let local2 = UncompressedChange {
         actor_id: actor.clone(),
@@ -323,6 +336,7 @@ fn test_transform_list_indexes_into_element_ids() {
             pred: Vec::new(),
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     };
 
     let local3 = UncompressedChange {
@@ -349,6 +363,7 @@ fn test_transform_list_indexes_into_element_ids() {
             },
         ],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     };
 
     let mut expected_change1 = UncompressedChange {
@@ -366,6 +381,7 @@ fn test_transform_list_indexes_into_element_ids() {
             pred: Vec::new(),
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     };
     let mut expected_change2 = UncompressedChange {
         actor_id: actor.clone(),
@@ -382,6 +398,7 @@ fn test_transform_list_indexes_into_element_ids() {
             pred: Vec::new(),
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     };
     let mut expected_change3 = UncompressedChange {
         actor_id: actor.clone(),
@@ -407,6 +424,7 @@ fn test_transform_list_indexes_into_element_ids() {
             },
         ],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     };
 
     let mut backend = Backend::init();
@@ -456,6 +474,7 @@ fn test_handle_list_insertion_and_deletion_in_same_change() {
             pred: Vec::new(),
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     };
 
     let local2 = UncompressedChange {
@@ -482,6 +501,7 @@ fn test_handle_list_insertion_and_deletion_in_same_change() {
             },
         ],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     };
 
     let mut expected_patch = Patch {
@@ -537,6 +557,7 @@ fn test_handle_list_insertion_and_deletion_in_same_change() {
             pred: Vec::new(),
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     }
     .try_into()
     .unwrap();
@@ -565,6 +586,7 @@ fn test_handle_list_insertion_and_deletion_in_same_change() {
             },
         ],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     }
     .try_into()
     .unwrap();
@@ -573,6 +595,95 @@ fn test_handle_list_insertion_and_deletion_in_same_change() {
     assert_eq!(change2, expected_change2);
 }
 
+#[test]
+fn test_last_local_change_and_ack() {
+    use automerge_frontend::{Frontend, InvalidChangeRequest, LocalChange, Path};
+
+    let mut frontend = Frontend::new();
+    let mut backend = Backend::init();
+    assert_eq!(backend.get_last_local_change(), None);
+
+    let mut requests = Vec::new();
+    for bird in &["magpie", "wren", "crow"] {
+        let request = frontend
+            .change::<_, InvalidChangeRequest>(None, |doc| {
+                doc.add_change(LocalChange::set(
+                    Path::root().key("bird"),
+                    protocol::ScalarValue::Str(bird.to_string()).into(),
+                ))
+            })
+            .unwrap()
+            .unwrap();
+        requests.push(request);
+    }
+    assert_eq!(frontend.acked_seq(), 0);
+
+    let mut patches = Vec::new();
+    for request in requests {
+        let (patch, change) = backend.apply_local_change(request).unwrap();
+        assert_eq!(backend.get_last_local_change(), Some(change.as_ref()));
+        patches.push(patch);
+    }
+    let last_local_change = backend.get_last_local_change().unwrap().clone();
+    assert_eq!(last_local_change.seq, 3);
+    assert_eq!(backend.get_unacked_changes().len(), 3);
+
+    // Changes from other actors don't replace the last local change
+    let mut remote = Backend::init();
+    let (_, remote_change) = remote
+        .apply_local_change(UncompressedChange {
+            actor_id: ActorID::random(),
+            seq: 1,
+            start_op: 1,
+            time: 0,
+            message: None,
+            deps: Vec::new(),
+            operations: vec![Op {
+                action: OpType::Set("pigeon".into()),
+                obj: ObjectID::Root,
+                key: "other".into(),
+                insert: false,
+                pred: Vec::new(),
+            }],
+            extra_bytes: Vec::new(),
+            unknown_columns: Vec::new(),
+        })
+        .unwrap();
+    backend
+        .apply_changes(vec![(*remote_change).clone()])
+        .unwrap();
+    assert_eq!(backend.get_last_local_change(), Some(&last_local_change));
+
+    let mut patches = patches.into_iter();
+    frontend.apply_patch(patches.next().unwrap()).unwrap();
+    assert_eq!(frontend.acked_seq(), 1);
+    backend
+        .ack(&frontend.actor_id, frontend.acked_seq())
+        .unwrap();
+    let unacked: Vec<u64> = backend
+        .get_unacked_changes()
+        .iter()
+        .map(|c| c.seq)
+        .collect();
+    assert_eq!(unacked, vec![2, 3]);
+
+    for patch in patches {
+        frontend.apply_patch(patch).unwrap();
+    }
+    assert_eq!(frontend.acked_seq(), 3);
+    backend
+        .ack(&frontend.actor_id, frontend.acked_seq())
+        .unwrap();
+    assert!(backend.get_unacked_changes().is_empty());
+
+    // Acknowledging an earlier seq again doesn't bring changes back
+    backend.ack(&frontend.actor_id, 1).unwrap();
+    assert!(backend.get_unacked_changes().is_empty());
+
+    // Acknowledging a change which was never applied is an error
+    assert!(backend.ack(&frontend.actor_id, 4).is_err());
+}
+
 /// Asserts that the changes are equal without respect to order of the hashes
 /// in the change dependencies
 fn assert_changes_equal(mut change1: UncompressedChange, change2: UncompressedChange) {
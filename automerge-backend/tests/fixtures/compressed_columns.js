@@ -0,0 +1,22 @@
+// Writes compressed_columns.bin: the change built by `expected_change` in
+// tests/interop.rs, as encoded by the automerge npm package. From this
+// directory run
+//
+//     npm install && node compressed_columns.js
+//
+// then update the hash asserted in test_decode_change_with_compressed_columns.
+const fs = require('fs')
+const path = require('path')
+const Automerge = require('automerge')
+
+const actor = '9b1f0cfa3b4d4c5e9a4a8a6e0c3b1f2d'
+const options = {message: 'compressed columns', time: 1600000000}
+const doc = Automerge.change(Automerge.init(actor), options, doc => {
+  for (let i = 0; i < 20; i++) {
+    const value = `the value of key ${i} padded out to sixty characters`
+    doc[`key ${i}`] = value.padEnd(60, '.')
+  }
+})
+
+const [change] = Automerge.getAllChanges(doc)
+fs.writeFileSync(path.join(__dirname, 'compressed_columns.bin'), change)
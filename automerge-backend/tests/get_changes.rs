@@ -0,0 +1,246 @@
+extern crate automerge_backend;
+use automerge_backend::{Backend, Change};
+use automerge_protocol as amp;
+use automerge_protocol::{ActorID, ObjectID, Op, UncompressedChange};
+use std::collections::HashSet;
+use std::convert::TryInto;
+
+fn change(actor: &ActorID, seq: u64, start_op: u64, deps: Vec<&Change>) -> Change {
+    UncompressedChange {
+        actor_id: actor.clone(),
+        seq,
+        start_op,
+        time: 0,
+        message: None,
+        deps: deps.iter().map(|c| c.hash).collect(),
+        operations: vec![Op {
+            action: amp::OpType::Set(amp::ScalarValue::Uint(seq)),
+            obj: ObjectID::Root,
+            key: actor.to_hex_string().as_str().into(),
+            insert: false,
+            pred: Vec::new(),
+        }],
+        extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
+    }
+    .into()
+}
+
+/// Two actors which each make two changes, merge, and then each make
+/// another change concurrently
+fn branching_history() -> Vec<Change> {
+    let actor1: ActorID = "9f35bc58ba6e4e1e8e4f0e2cbd4b2bd1".try_into().unwrap();
+    let actor2: ActorID = "46c8b67c1c9c4e4a85a13b0e8e3a7a42".try_into().unwrap();
+    let a1 = change(&actor1, 1, 1, vec![]);
+    let a2 = change(&actor1, 2, 2, vec![&a1]);
+    let b1 = change(&actor2, 1, 1, vec![]);
+    let b2 = change(&actor2, 2, 2, vec![&b1]);
+    let a3 = change(&actor1, 3, 3, vec![&a2, &b2]);
+    let b3 = change(&actor2, 3, 3, vec![&b2]);
+    vec![a1, b1, a2, b2, a3, b3]
+}
+
+/// `heads` and all their ancestors, found by walking the dependency graph
+fn ancestors(changes: &[Change], heads: &[amp::ChangeHash]) -> HashSet<amp::ChangeHash> {
+    let mut seen = HashSet::new();
+    let mut stack = heads.to_vec();
+    while let Some(hash) = stack.pop() {
+        if seen.insert(hash) {
+            if let Some(change) = changes.iter().find(|c| c.hash == hash) {
+                stack.extend(change.deps.iter().cloned());
+            }
+        }
+    }
+    seen
+}
+
+/// The changes which are not `have` or an ancestor of them
+fn changes_not_covered_by<'a>(changes: &'a [Change], have: &[amp::ChangeHash]) -> Vec<&'a Change> {
+    let seen = ancestors(changes, have);
+    changes.iter().filter(|c| !seen.contains(&c.hash)).collect()
+}
+
+#[test]
+fn test_get_changes_matches_walking_the_graph() {
+    let changes = branching_history();
+    let mut backend = Backend::init();
+    backend.apply_changes(changes.clone()).unwrap();
+
+    // Every pair of changes as the heads we already have
+    for first in changes.iter() {
+        for second in changes.iter() {
+            let have = vec![first.hash, second.hash];
+            assert_eq!(
+                backend.get_changes(&have),
+                changes_not_covered_by(&changes, &have)
+            );
+        }
+    }
+    assert_eq!(backend.get_changes(&[]), changes.iter().collect::<Vec<_>>());
+    assert_eq!(
+        backend.get_changes(&backend.get_heads()),
+        Vec::<&Change>::new()
+    );
+
+    // Hashes we don't know about are ignored
+    let unknown = amp::ChangeHash([7; 32]);
+    assert_eq!(
+        backend.get_changes(&[unknown, changes[0].hash]),
+        changes[1..].iter().collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_get_changes_with_stale_heads() {
+    // One actor makes a long run of changes, every third of which a second
+    // actor follows concurrently, merging in the first actor's every tenth
+    let actor1: ActorID = "9f35bc58ba6e4e1e8e4f0e2cbd4b2bd1".try_into().unwrap();
+    let actor2: ActorID = "46c8b67c1c9c4e4a85a13b0e8e3a7a42".try_into().unwrap();
+    let mut changes: Vec<Change> = Vec::new();
+    let (mut last1, mut last2): (Option<usize>, Option<usize>) = (None, None);
+    for i in 0..300 {
+        let start_op = changes.len() as u64 + 1;
+        let next = if i % 3 == 2 {
+            let mut deps: Vec<&Change> = last2.iter().map(|j| &changes[*j]).collect();
+            if i % 10 == 8 {
+                deps.extend(last1.iter().map(|j| &changes[*j]));
+            }
+            let seq = last2.map_or(1, |j| changes[j].seq + 1);
+            last2 = Some(changes.len());
+            change(&actor2, seq, start_op, deps)
+        } else {
+            let deps = last1.iter().map(|j| &changes[*j]).collect();
+            let seq = last1.map_or(1, |j| changes[j].seq + 1);
+            last1 = Some(changes.len());
+            change(&actor1, seq, start_op, deps)
+        };
+        changes.push(next);
+    }
+    let mut backend = Backend::init();
+    backend.apply_changes(changes.clone()).unwrap();
+
+    // Heads a peer saw some time ago, every one of which has been superseded
+    for (first, second) in &[(0, 2), (40, 41), (150, 98), (297, 296)] {
+        let have = vec![changes[*first].hash, changes[*second].hash];
+        assert!(have.iter().all(|h| !backend.get_heads().contains(h)));
+        assert_eq!(
+            backend.get_changes(&have),
+            changes_not_covered_by(&changes, &have)
+        );
+    }
+}
+
+#[test]
+fn test_get_changes_added() {
+    let changes = branching_history();
+    let mut old = Backend::init();
+    old.apply_changes(changes[..4].to_vec()).unwrap();
+    let mut new = old.clone();
+    new.apply_changes(changes[4..].to_vec()).unwrap();
+
+    assert_eq!(
+        new.get_changes_added(&old),
+        changes[4..].iter().collect::<Vec<_>>()
+    );
+    assert_eq!(old.get_changes_added(&new), Vec::<&Change>::new());
+    assert_eq!(
+        new.get_changes_added(&Backend::init()),
+        changes.iter().collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_get_change_by_hash() {
+    let changes = branching_history();
+    let mut backend = Backend::init();
+    backend.apply_changes(changes.clone()).unwrap();
+    for change in changes.iter() {
+        assert_eq!(backend.get_change_by_hash(&change.hash), Some(change));
+    }
+    assert_eq!(backend.get_change_by_hash(&amp::ChangeHash([7; 32])), None);
+}
+
+#[test]
+fn test_is_ancestor_matches_walking_the_graph() {
+    let changes = branching_history();
+    let mut backend = Backend::init();
+    backend.apply_changes(changes.clone()).unwrap();
+    for head in changes.iter() {
+        let expected = ancestors(&changes, &[head.hash]);
+        for change in changes.iter() {
+            assert_eq!(
+                backend.is_ancestor(&change.hash, &[head.hash]),
+                expected.contains(&change.hash)
+            );
+        }
+    }
+    for change in changes.iter() {
+        assert!(backend.is_ancestor(&change.hash, &backend.get_heads()));
+    }
+    assert!(!backend.is_ancestor(&amp::ChangeHash([7; 32]), &backend.get_heads()));
+}
+
+#[test]
+fn test_common_ancestors_match_walking_the_graph() {
+    let changes = branching_history();
+    let mut backend = Backend::init();
+    backend.apply_changes(changes.clone()).unwrap();
+    for change1 in changes.iter() {
+        for change2 in changes.iter() {
+            let common: HashSet<_> = ancestors(&changes, &[change1.hash])
+                .intersection(&ancestors(&changes, &[change2.hash]))
+                .cloned()
+                .collect();
+            // The common changes which no other common change depends on
+            let mut expected: Vec<_> = common
+                .iter()
+                .filter(|hash| {
+                    !changes
+                        .iter()
+                        .any(|c| common.contains(&c.hash) && c.deps.contains(hash))
+                })
+                .cloned()
+                .collect();
+            expected.sort();
+            assert_eq!(
+                backend.get_common_ancestors(&[change1.hash], &[change2.hash]),
+                expected
+            );
+        }
+    }
+}
+
+#[test]
+fn test_get_dependents() {
+    let changes = branching_history();
+    let mut backend = Backend::init();
+    backend.apply_changes(changes.clone()).unwrap();
+    let (b2, a3, b3) = (&changes[3], &changes[4], &changes[5]);
+    assert_eq!(backend.get_dependents(&b2.hash), vec![a3.hash, b3.hash]);
+    assert_eq!(backend.get_dependents(&a3.hash), Vec::new());
+}
+
+#[test]
+fn test_topological_order_does_not_depend_on_application_order() {
+    let changes = branching_history();
+    let mut backend1 = Backend::init();
+    backend1.apply_changes(changes.clone()).unwrap();
+    // The same changes applied in a different causal order
+    let mut backend2 = Backend::init();
+    for index in &[1, 3, 5, 0, 2, 4] {
+        backend2
+            .apply_changes(vec![changes[*index].clone()])
+            .unwrap();
+    }
+
+    let order1: Vec<_> = backend1.iter_topological().map(|c| c.hash).collect();
+    let order2: Vec<_> = backend2.iter_topological().map(|c| c.hash).collect();
+    assert_eq!(order1, order2);
+    assert_eq!(order1.len(), changes.len());
+    for (index, hash) in order1.iter().enumerate() {
+        let change = backend1.get_change_by_hash(hash).unwrap();
+        for dep in change.deps.iter() {
+            assert!(order1[..index].contains(dep));
+        }
+    }
+}
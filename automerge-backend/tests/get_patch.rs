@@ -26,6 +26,7 @@ fn test_include_most_recent_value_for_key() {
             insert: false,
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     }
     .try_into()
     .unwrap();
@@ -45,6 +46,7 @@ fn test_include_most_recent_value_for_key() {
             insert: false,
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     }
     .try_into()
     .unwrap();
@@ -93,6 +95,7 @@ fn test_includes_conflicting_values_for_key() {
             insert: false,
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     }
     .try_into()
     .unwrap();
@@ -112,6 +115,7 @@ fn test_includes_conflicting_values_for_key() {
             insert: false,
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     }
     .try_into()
     .unwrap();
@@ -161,6 +165,7 @@ fn test_handles_counter_increment_at_keys_in_a_map() {
             insert: false,
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     }
     .try_into()
     .unwrap();
@@ -180,6 +185,7 @@ fn test_handles_counter_increment_at_keys_in_a_map() {
             insert: false,
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     }
     .try_into()
     .unwrap();
@@ -236,6 +242,7 @@ fn test_creates_nested_maps() {
             },
         ],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     }
     .try_into()
     .unwrap();
@@ -264,6 +271,7 @@ fn test_creates_nested_maps() {
             },
         ],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     }
     .try_into()
     .unwrap();
@@ -328,6 +336,7 @@ fn test_create_lists() {
             },
         ],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     }
     .try_into()
     .unwrap();
@@ -410,6 +419,7 @@ fn test_includes_latests_state_of_list() {
             },
         ],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     }
     .try_into()
     .unwrap();
@@ -477,6 +487,7 @@ fn test_includes_date_objects_at_root() {
             insert: false,
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     }
     .try_into()
     .unwrap();
@@ -533,6 +544,7 @@ fn test_includes_date_objects_in_a_list() {
             },
         ],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     }
     .try_into()
     .unwrap();
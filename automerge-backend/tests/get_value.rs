@@ -0,0 +1,304 @@
+extern crate automerge_backend;
+use automerge_backend::Backend;
+use automerge_frontend::{Frontend, InvalidChangeRequest, LocalChange, MutableDocument, Path};
+use automerge_protocol as amp;
+use automerge_protocol::{ObjType, ObjectID, PathElement, ScalarValue, SequenceType};
+use maplit::hashmap;
+use std::collections::HashSet;
+
+fn edit<F>(frontend: &mut Frontend, backend: &mut Backend, f: F)
+where
+    F: FnOnce(&mut dyn MutableDocument) -> Result<(), InvalidChangeRequest>,
+{
+    let request = frontend.change(None, f).unwrap().unwrap();
+    let (patch, _) = backend.apply_local_change(request).unwrap();
+    frontend.apply_patch(patch).unwrap();
+}
+
+/// A document with nested maps, a list, text and a counter
+fn document() -> (Frontend, Backend) {
+    let mut frontend = Frontend::new();
+    let mut backend = Backend::init();
+    edit(&mut frontend, &mut backend, |doc| {
+        doc.add_change(LocalChange::set(
+            Path::root().key("birds"),
+            automerge_frontend::Value::Sequence(vec![
+                ScalarValue::Str("wren".into()).into(),
+                ScalarValue::Str("magpie".into()).into(),
+                ScalarValue::Str("crow".into()).into(),
+            ]),
+        ))?;
+        doc.add_change(LocalChange::set(
+            Path::root().key("title"),
+            automerge_frontend::Value::Text("hello".chars().collect()),
+        ))?;
+        doc.add_change(LocalChange::set(
+            Path::root().key("info"),
+            hashmap! {"name" => ScalarValue::Str("garden".into())}.into(),
+        ))?;
+        doc.add_change(LocalChange::set(
+            Path::root().key("count"),
+            ScalarValue::Counter(1).into(),
+        ))
+    });
+    edit(&mut frontend, &mut backend, |doc| {
+        doc.add_change(LocalChange::delete(Path::root().key("birds").index(0)))?;
+        doc.add_change(LocalChange::increment_by(Path::root().key("count"), 2))
+    });
+    (frontend, backend)
+}
+
+/// Read the whole of `value` from the backend into a frontend value
+fn materialize(backend: &Backend, value: amp::Value) -> automerge_frontend::Value {
+    let (object_id, obj_type) = match value {
+        amp::Value::Primitive(value) => return value.into(),
+        amp::Value::Object {
+            object_id,
+            obj_type,
+        } => (object_id, obj_type),
+    };
+    let entries = backend.entries(&object_id).unwrap();
+    assert_eq!(backend.length(&object_id).unwrap(), entries.len());
+    match obj_type {
+        ObjType::Map(map_type) => automerge_frontend::Value::Map(
+            entries
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), materialize(backend, value)))
+                .collect(),
+            map_type,
+        ),
+        ObjType::Sequence(SequenceType::List) => automerge_frontend::Value::Sequence(
+            entries
+                .into_iter()
+                .map(|(_, value)| materialize(backend, value))
+                .collect(),
+        ),
+        ObjType::Sequence(SequenceType::Text) => automerge_frontend::Value::Text(
+            entries
+                .into_iter()
+                .map(|(_, value)| match value {
+                    amp::Value::Primitive(ScalarValue::Str(s)) => s.chars().next().unwrap(),
+                    other => panic!("unexpected value in text: {:?}", other),
+                })
+                .collect(),
+        ),
+    }
+}
+
+#[test]
+fn test_read_document_matches_frontend() {
+    let (frontend, backend) = document();
+    let root = backend.get_path(&[]).unwrap().unwrap();
+    assert_eq!(&materialize(&backend, root), frontend.state());
+}
+
+#[test]
+fn test_get_by_path_and_object_id() {
+    let (frontend, backend) = document();
+
+    let birds = backend.get_path(&["birds".into()]).unwrap().unwrap();
+    let birds_id = frontend.get_object_id(&Path::root().key("birds")).unwrap();
+    assert_eq!(
+        birds,
+        amp::Value::Object {
+            object_id: birds_id.clone(),
+            obj_type: ObjType::list(),
+        }
+    );
+    assert_eq!(backend.length(&birds_id).unwrap(), 2);
+    assert_eq!(
+        backend.get(&birds_id, &PathElement::Index(1)).unwrap(),
+        Some(amp::Value::Primitive(ScalarValue::Str("crow".into())))
+    );
+    assert_eq!(
+        backend.get_path(&["info".into(), "name".into()]).unwrap(),
+        Some(amp::Value::Primitive(ScalarValue::Str("garden".into())))
+    );
+    assert_eq!(
+        backend.get(&ObjectID::Root, &"count".into()).unwrap(),
+        Some(amp::Value::Primitive(ScalarValue::Counter(3)))
+    );
+
+    // Missing keys, indexes past the end, map keys in lists and paths
+    // through primitives find nothing
+    assert_eq!(backend.get(&birds_id, &2.into()).unwrap(), None);
+    assert_eq!(backend.get(&birds_id, &"wren".into()).unwrap(), None);
+    assert_eq!(backend.get(&ObjectID::Root, &0.into()).unwrap(), None);
+    assert_eq!(backend.get_path(&["nothing".into()]).unwrap(), None);
+    assert_eq!(
+        backend.get_path(&["count".into(), "x".into()]).unwrap(),
+        None
+    );
+
+    // Objects which don't exist are an error
+    let missing = amp::ActorID::random().op_id_at(1).into();
+    assert!(backend.get(&missing, &"x".into()).is_err());
+    assert!(backend.length(&missing).is_err());
+}
+
+#[test]
+fn test_get_conflicts() {
+    let (mut frontend1, mut backend1) = document();
+    let mut frontend2 = Frontend::new();
+    let mut backend2 = Backend::init();
+    let set_bird = |name: &'static str| {
+        move |doc: &mut dyn MutableDocument| {
+            doc.add_change(LocalChange::set(
+                Path::root().key("bird"),
+                ScalarValue::Str(name.into()).into(),
+            ))
+        }
+    };
+    edit(&mut frontend1, &mut backend1, set_bird("robin"));
+    edit(&mut frontend2, &mut backend2, set_bird("jay"));
+
+    let changes = backend2.get_changes(&[]).into_iter().cloned().collect();
+    let patch = backend1.apply_changes(changes).unwrap();
+    frontend1.apply_patch(patch).unwrap();
+
+    let conflicts = backend1
+        .get_conflicts(&ObjectID::Root, &"bird".into())
+        .unwrap();
+    let expected: HashSet<_> = frontend1
+        .get_conflicts(&Path::root().key("bird"))
+        .unwrap()
+        .keys()
+        .cloned()
+        .collect();
+    assert_eq!(conflicts.keys().cloned().collect::<HashSet<_>>(), expected);
+    let values: HashSet<_> = conflicts
+        .values()
+        .map(|value| match value {
+            amp::Value::Primitive(ScalarValue::Str(s)) => s.as_str(),
+            other => panic!("unexpected value: {:?}", other),
+        })
+        .collect();
+    assert_eq!(values, ["robin", "jay"].iter().cloned().collect());
+
+    // The winning value is the one the frontend shows
+    let winner = backend1
+        .get(&ObjectID::Root, &"bird".into())
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        materialize(&backend1, winner),
+        frontend1.get_value(&Path::root().key("bird")).unwrap()
+    );
+
+    assert!(backend1
+        .get_conflicts(&ObjectID::Root, &"nothing".into())
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn test_path_of() {
+    let (mut frontend, mut backend) = document();
+    let flock = |name: &str| -> automerge_frontend::Value {
+        hashmap! {"name" => ScalarValue::Str(name.into())}.into()
+    };
+    edit(&mut frontend, &mut backend, |doc| {
+        doc.add_change(LocalChange::set(
+            Path::root().key("info").key("flocks"),
+            automerge_frontend::Value::Sequence(vec![flock("north")]),
+        ))
+    });
+    edit(&mut frontend, &mut backend, |doc| {
+        doc.add_change(LocalChange::insert(
+            Path::root().key("info").key("flocks").index(0),
+            flock("south"),
+        ))
+    });
+    let object_id = |path: Path| frontend.get_object_id(&path).unwrap();
+    let info = object_id(Path::root().key("info"));
+    let north = object_id(Path::root().key("info").key("flocks").index(1));
+    let south = object_id(Path::root().key("info").key("flocks").index(0));
+
+    assert_eq!(backend.path_of(&ObjectID::Root).unwrap(), Some(Vec::new()));
+    assert_eq!(backend.path_of(&info).unwrap(), Some(vec!["info".into()]));
+    assert_eq!(
+        backend.path_of(&north).unwrap(),
+        Some(vec!["info".into(), "flocks".into(), 1.into()])
+    );
+
+    // Deleting an object, or one containing it, makes it unreachable
+    edit(&mut frontend, &mut backend, |doc| {
+        doc.add_change(LocalChange::delete(
+            Path::root().key("info").key("flocks").index(0),
+        ))
+    });
+    assert_eq!(backend.path_of(&south).unwrap(), None);
+    assert_eq!(
+        backend.path_of(&north).unwrap(),
+        Some(vec!["info".into(), "flocks".into(), 0.into()])
+    );
+    edit(&mut frontend, &mut backend, |doc| {
+        doc.add_change(LocalChange::delete(Path::root().key("info")))
+    });
+    assert_eq!(backend.path_of(&info).unwrap(), None);
+    assert_eq!(backend.path_of(&north).unwrap(), None);
+
+    let missing = amp::ActorID::random().op_id_at(1).into();
+    assert!(backend.path_of(&missing).is_err());
+}
+
+#[test]
+fn test_blame() {
+    let (mut frontend1, mut backend1) = document();
+    let mut frontend2 = Frontend::new();
+    let mut backend2 = Backend::init();
+    let request = frontend2
+        .change(Some("jay".into()), |doc| {
+            doc.add_change(LocalChange::set(
+                Path::root().key("bird"),
+                ScalarValue::Str("jay".into()).into(),
+            ))
+        })
+        .unwrap()
+        .unwrap();
+    backend2.apply_local_change(request).unwrap();
+    edit(&mut frontend1, &mut backend1, |doc| {
+        doc.add_change(LocalChange::set(
+            Path::root().key("bird"),
+            ScalarValue::Str("robin".into()).into(),
+        ))
+    });
+    let changes = backend2.get_changes(&[]).into_iter().cloned().collect();
+    let patch = backend1.apply_changes(changes).unwrap();
+    frontend1.apply_patch(patch).unwrap();
+
+    // Each conflicting value is attributed to the change which set it, with
+    // the value the frontend shows first
+    let blame = backend1.blame(&["bird".into()]).unwrap();
+    assert_eq!(blame.len(), 2);
+    let winner = backend1.get_path(&["bird".into()]).unwrap().unwrap();
+    assert_eq!(blame[0].value, winner);
+    for entry in blame.iter() {
+        let change = backend1.get_change_by_hash(&entry.hash).unwrap();
+        assert_eq!(&entry.actor, change.actor_id());
+        assert_eq!(entry.actor, entry.op_id.1);
+        assert_eq!(entry.time, change.time);
+        assert!(change.start_op <= entry.op_id.0 && entry.op_id.0 <= change.max_op());
+        let expected_message = if entry.actor == frontend2.actor_id {
+            Some("jay".to_string())
+        } else {
+            None
+        };
+        assert_eq!(entry.message, expected_message);
+    }
+
+    // Values nested in lists point to the change which inserted them
+    let blame = backend1.blame(&["birds".into(), 1.into()]).unwrap();
+    assert_eq!(blame.len(), 1);
+    assert_eq!(
+        blame[0].value,
+        amp::Value::Primitive(ScalarValue::Str("crow".into()))
+    );
+    assert_eq!(
+        Some(blame[0].hash),
+        backend1.get_changes(&[]).first().map(|c| c.hash)
+    );
+
+    assert!(backend1.blame(&[]).unwrap().is_empty());
+    assert!(backend1.blame(&["nothing".into()]).unwrap().is_empty());
+}
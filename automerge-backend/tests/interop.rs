@@ -0,0 +1,119 @@
+extern crate automerge_backend;
+use automerge_backend::{Backend, Change};
+use automerge_frontend::Frontend;
+use automerge_protocol as amp;
+use automerge_protocol::{ActorID, ObjectID, Op, UncompressedChange};
+use std::convert::TryInto;
+
+/// A change whose valRaw column is compressed. `fixtures/compressed_columns.js`
+/// writes it with the automerge npm package. The bytes checked in are still
+/// the ones this crate wrote when compressed columns were introduced, so they
+/// need regenerating with the script, and the hash below updating to match.
+const COMPRESSED_COLUMNS: &[u8] = include_bytes!("fixtures/compressed_columns.bin");
+
+/// A document saved by automerge-backend before compressed columns were
+/// supported, when column IDs were laid out differently. It was made with
+/// the frontend by setting `{"birds": {"wrens": 3}, "list": ["a", "b", "c"],
+/// "count": 0}` as the initial state, then in a second change setting "text"
+/// to "hello", deleting `list[1]`, inserting "z" at `list[0]` and setting
+/// `birds.wrens` to 4, then in a third change replacing `text[0]` with "j"
+/// and deleting "birds".
+const LEGACY_COLUMNS: &[u8] = include_bytes!("fixtures/legacy_columns.bin");
+
+/// The ID of the valRaw column, the only one in `expected_change` large
+/// enough to be compressed
+const VAL_RAW: u32 = 5 << 4 | 7;
+
+fn expected_change() -> UncompressedChange {
+    let actor: ActorID = "9b1f0cfa3b4d4c5e9a4a8a6e0c3b1f2d".try_into().unwrap();
+    UncompressedChange {
+        actor_id: actor,
+        seq: 1,
+        start_op: 1,
+        time: 1_600_000_000,
+        message: Some("compressed columns".to_string()),
+        deps: Vec::new(),
+        operations: (0..20)
+            .map(|i| {
+                let value = format!("the value of key {} padded out to sixty characters", i);
+                Op {
+                    action: amp::OpType::Set(format!("{:.<60}", value).as_str().into()),
+                    obj: ObjectID::Root,
+                    key: format!("key {}", i).as_str().into(),
+                    insert: false,
+                    pred: Vec::new(),
+                }
+            })
+            .collect(),
+        extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
+    }
+}
+
+#[test]
+fn test_decode_change_with_compressed_columns() {
+    let change = Change::from_bytes(COMPRESSED_COLUMNS.to_vec()).unwrap();
+    assert_eq!(
+        hex::encode(change.hash.0),
+        "3d9536078345dcec6fe88ab949a67788b91edb785efceb3f4474539e54f7d5e3"
+    );
+    assert_eq!(change.op_count(), 20);
+    assert_eq!(change.compressed_columns(), vec![VAL_RAW]);
+    assert_eq!(change.decode(), expected_change());
+
+    let mut backend = Backend::init();
+    backend.apply_changes(vec![change]).unwrap();
+    let saved = backend.save().unwrap();
+    assert_eq!(saved, COMPRESSED_COLUMNS);
+    let loaded = Backend::load(saved).unwrap();
+    assert_eq!(loaded.get_patch().unwrap(), backend.get_patch().unwrap());
+}
+
+#[test]
+fn test_encode_change_with_compressed_columns() {
+    let change = Change::from(expected_change());
+    // The whole change is smaller than the 1200 bytes of values it contains
+    assert!(change.bytes.len() < 1200);
+    let decoded = Change::from_bytes(change.bytes.clone()).unwrap();
+    assert_eq!(decoded.compressed_columns(), vec![VAL_RAW]);
+    assert_eq!(decoded, change);
+    assert_eq!(decoded.decode(), expected_change());
+}
+
+#[test]
+fn test_load_document_with_legacy_column_ids() {
+    let changes = Change::parse(LEGACY_COLUMNS).unwrap();
+    assert_eq!(changes.len(), 3);
+    assert_eq!(
+        changes.iter().map(Change::op_count).collect::<Vec<_>>(),
+        vec![7, 9, 3]
+    );
+    let keys: Vec<_> = changes[2]
+        .decode()
+        .operations
+        .into_iter()
+        .map(|op| op.key)
+        .collect();
+    let first_char: amp::OpID = "9@02d1db56c37c4721b973cb4788647979".try_into().unwrap();
+    assert_eq!(
+        keys,
+        vec![
+            amp::ElementID::ID(first_char).into_key(),
+            amp::Key::head(),
+            "birds".into()
+        ]
+    );
+    assert!(changes
+        .iter()
+        .all(|c| c.decode().unknown_columns.is_empty()));
+
+    let backend = Backend::load(LEGACY_COLUMNS.to_vec()).unwrap();
+    let mut frontend = Frontend::new();
+    frontend.apply_patch(backend.get_patch().unwrap()).unwrap();
+    assert_eq!(
+        frontend.state().to_json(),
+        serde_json::json!({"count": 0.0, "list": ["z", "a", "c"], "text": "jello"})
+    );
+    // The changes are kept as they were written
+    assert_eq!(backend.save().unwrap(), LEGACY_COLUMNS);
+}
@@ -0,0 +1,224 @@
+extern crate automerge_backend;
+use automerge_backend::{AutomergeError, Backend, Change, CorruptChunk, LoadProgress, Loader};
+use automerge_protocol as amp;
+use automerge_protocol::{ActorID, ObjectID, Op, UncompressedChange};
+use std::convert::TryInto;
+
+fn saved_document() -> (Vec<Change>, Vec<u8>) {
+    let actor: ActorID = "ec28cfbcdb9e4f32ad24b3c776e651b0".try_into().unwrap();
+    let mut changes: Vec<Change> = Vec::new();
+    for seq in 1..=3 {
+        let change: Change = UncompressedChange {
+            actor_id: actor.clone(),
+            seq,
+            start_op: seq,
+            time: 0,
+            message: None,
+            deps: changes.last().map(|c| vec![c.hash]).unwrap_or_default(),
+            operations: vec![Op {
+                action: amp::OpType::Set(format!("bird {}", seq).as_str().into()),
+                key: "bird".into(),
+                obj: ObjectID::Root,
+                pred: Vec::new(),
+                insert: false,
+            }],
+            extra_bytes: Vec::new(),
+            unknown_columns: Vec::new(),
+        }
+        .into();
+        changes.push(change);
+    }
+    let mut backend = Backend::init();
+    backend.apply_changes(changes.clone()).unwrap();
+    let saved = backend.save().unwrap();
+    (changes, saved)
+}
+
+#[test]
+fn test_feed_one_byte_at_a_time() {
+    let (changes, saved) = saved_document();
+    let mut loader = Loader::new();
+    let mut applied = Vec::new();
+    for byte in saved.iter() {
+        let progress = loader.feed(&[*byte]).unwrap();
+        if applied.last() != Some(&progress) {
+            applied.push(progress);
+        }
+    }
+    let mut expected_bytes = 0;
+    let expected: Vec<_> = changes
+        .iter()
+        .enumerate()
+        .map(|(i, change)| {
+            expected_bytes += change.bytes.len();
+            LoadProgress {
+                changes: i + 1,
+                bytes: expected_bytes,
+            }
+        })
+        .collect();
+    assert_eq!(applied[1..], expected[..]);
+
+    let backend = loader.finish().unwrap();
+    let loaded = Backend::load(saved).unwrap();
+    assert_eq!(backend.get_patch().unwrap(), loaded.get_patch().unwrap());
+}
+
+#[test]
+fn test_load_from_reader() {
+    let (_, saved) = saved_document();
+    let backend = Backend::load_from_reader(saved.as_slice()).unwrap();
+    let loaded = Backend::load(saved.clone()).unwrap();
+    assert_eq!(backend.get_patch().unwrap(), loaded.get_patch().unwrap());
+
+    let mut loader = Loader::new();
+    let mut reports = Vec::new();
+    let progress = loader
+        .read_from(saved.as_slice(), |p| reports.push(p))
+        .unwrap();
+    assert_eq!(
+        progress,
+        LoadProgress {
+            changes: 3,
+            bytes: saved.len()
+        }
+    );
+    assert_eq!(reports, vec![progress]);
+}
+
+#[test]
+fn test_truncated_trailing_change_is_an_error() {
+    let (changes, saved) = saved_document();
+    let truncated = &saved[..saved.len() - 1];
+
+    let mut loader = Loader::new();
+    let progress = loader.feed(truncated).unwrap();
+    let complete = changes[0].bytes.len() + changes[1].bytes.len();
+    assert_eq!(
+        progress,
+        LoadProgress {
+            changes: 2,
+            bytes: complete
+        }
+    );
+    assert_eq!(
+        loader.finish().unwrap_err(),
+        AutomergeError::TruncatedChange {
+            offset: complete,
+            len: truncated.len() - complete
+        }
+    );
+
+    assert_eq!(
+        Backend::load_from_reader(truncated).unwrap_err(),
+        AutomergeError::TruncatedChange {
+            offset: complete,
+            len: truncated.len() - complete
+        }
+    );
+}
+
+#[test]
+fn test_garbage_is_rejected_without_buffering() {
+    let mut loader = Loader::new();
+    assert_eq!(
+        loader.feed(&[0x85, 0x6f, 0x00]).unwrap_err(),
+        AutomergeError::EncodingError
+    );
+}
+
+#[test]
+fn test_corrupt_change_is_detected() {
+    let (changes, mut saved) = saved_document();
+    let offset = changes[0].bytes.len();
+    let last = offset + changes[1].bytes.len() - 1;
+    saved[last] ^= 0x01;
+
+    let error = AutomergeError::ChecksumMismatch { offset };
+    assert_eq!(Backend::load(saved.clone()).unwrap_err(), error);
+    let mut loader = Loader::new();
+    assert_eq!(loader.feed(&saved).unwrap_err(), error);
+    assert_eq!(loader.progress().changes, 1);
+}
+
+#[test]
+fn test_salvage_skips_corrupt_chunks() {
+    let (changes, saved) = saved_document();
+    let offset = changes[0].bytes.len();
+    let mut expected = Backend::init();
+    expected.load_changes(vec![changes[0].clone()]).unwrap();
+
+    // A bit flip in the body of the second change, and one in the length in
+    // its header which misaligns everything after it
+    let length_byte = offset + 9;
+    for corrupt_byte in &[offset + changes[1].bytes.len() - 1, length_byte] {
+        let mut corrupt = saved.clone();
+        corrupt[*corrupt_byte] ^= 0x01;
+        let (backend, skipped) = Backend::load_salvage(corrupt).unwrap();
+        assert_eq!(skipped.len(), 1);
+        let CorruptChunk { offset: o, len, .. } = skipped[0];
+        assert_eq!((o, len), (offset, changes[1].bytes.len()));
+        assert_eq!(backend.get_patch().unwrap(), expected.get_patch().unwrap());
+        assert_eq!(backend.get_missing_deps(), vec![changes[1].hash]);
+    }
+
+    let (backend, skipped) = Backend::load_salvage(saved.clone()).unwrap();
+    assert!(skipped.is_empty());
+    assert_eq!(
+        backend.get_patch().unwrap(),
+        Backend::load(saved).unwrap().get_patch().unwrap()
+    );
+}
+
+#[test]
+fn test_load_changes_out_of_order() {
+    let (changes, saved) = saved_document();
+    let reversed: Vec<u8> = changes
+        .iter()
+        .rev()
+        .flat_map(|change| change.bytes.clone())
+        .collect();
+    let loaded = Backend::load(reversed).unwrap();
+    let expected = Backend::load(saved.clone()).unwrap();
+    assert_eq!(loaded.get_patch().unwrap(), expected.get_patch().unwrap());
+    assert_eq!(loaded.get_missing_deps(), Vec::new());
+    // Changes are saved in the order they were applied
+    assert_eq!(loaded.save().unwrap(), saved);
+}
+
+#[test]
+fn test_load_holds_back_changes_with_missing_deps() {
+    let (changes, saved) = saved_document();
+    let mut backend = Backend::init();
+    backend.load_changes(changes[1..].to_vec()).unwrap();
+    assert_eq!(backend.get_missing_deps(), vec![changes[0].hash]);
+    assert_eq!(backend.get_heads(), Vec::new());
+
+    backend.load_changes(vec![changes[0].clone()]).unwrap();
+    assert_eq!(backend.get_missing_deps(), Vec::new());
+    let expected = Backend::load(saved).unwrap();
+    assert_eq!(backend.get_patch().unwrap(), expected.get_patch().unwrap());
+}
+
+#[test]
+fn test_load_duplicated_chunks() {
+    let (changes, saved) = saved_document();
+    let expected = Backend::load(saved.clone()).unwrap();
+    // A change repeated before and after one which depends on it, and a
+    // repeat of the last change at the end
+    let chunks = [
+        &changes[0],
+        &changes[1],
+        &changes[0],
+        &changes[2],
+        &changes[2],
+    ];
+    let duplicated: Vec<u8> = chunks
+        .iter()
+        .flat_map(|change| change.bytes.clone())
+        .collect();
+    let loaded = Backend::load(duplicated).unwrap();
+    assert_eq!(loaded.get_patch().unwrap(), expected.get_patch().unwrap());
+    assert_eq!(loaded.get_missing_deps(), Vec::new());
+    assert_eq!(loaded.save().unwrap(), saved);
+}
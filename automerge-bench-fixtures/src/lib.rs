@@ -0,0 +1,184 @@
+//! Workloads shared by the benchmarks. Every change is made through
+//! `Frontend::change` and applied with `Backend::apply_local_change`, the same
+//! way an application would produce them.
+
+use automerge_backend::{Backend, Change};
+use automerge_frontend::{
+    Frontend, InvalidChangeRequest, LocalChange, MutableDocument, Path, Value,
+};
+use automerge_protocol as amp;
+use std::collections::HashMap;
+
+/// A frontend along with its backend
+pub struct Peer {
+    pub frontend: Frontend,
+    pub backend: Backend,
+}
+
+impl Peer {
+    pub fn new() -> Peer {
+        Peer {
+            frontend: Frontend::new(),
+            backend: Backend::init(),
+        }
+    }
+
+    /// Make a local change, apply it to the backend and the resulting patch
+    /// to the frontend, returning the binary change
+    pub fn change<F>(&mut self, f: F) -> Change
+    where
+        F: FnOnce(&mut dyn MutableDocument) -> Result<(), InvalidChangeRequest>,
+    {
+        let request = self.frontend.change(None, f).unwrap().unwrap();
+        let (patch, change) = self.backend.apply_local_change(request).unwrap();
+        self.frontend.apply_patch(patch).unwrap();
+        (*change).clone()
+    }
+
+    /// Apply changes from another peer
+    pub fn merge(&mut self, changes: Vec<Change>) {
+        let patch = self.backend.apply_changes(changes).unwrap();
+        self.frontend.apply_patch(patch).unwrap();
+    }
+}
+
+impl Default for Peer {
+    fn default() -> Self {
+        Peer::new()
+    }
+}
+
+fn text() -> Path {
+    Path::root().key("text")
+}
+
+/// A single actor typing `keystrokes` characters into a text object, one
+/// change per keystroke. Every seventh keystroke is a backspace and the cursor
+/// jumps elsewhere in the text every fiftieth keystroke.
+pub fn typing_trace(keystrokes: usize) -> Vec<Change> {
+    let mut peer = Peer::new();
+    let mut changes =
+        vec![peer.change(|doc| doc.add_change(LocalChange::set(text(), Value::Text(Vec::new()))))];
+    let mut cursor: u32 = 0;
+    let mut len: u32 = 0;
+    for i in 1..keystrokes {
+        if i % 50 == 0 {
+            cursor = (i as u32 * 31) % (len + 1);
+        }
+        let change = if i % 7 == 0 && cursor > 0 {
+            cursor -= 1;
+            len -= 1;
+            let path = text().index(cursor);
+            peer.change(|doc| doc.add_change(LocalChange::delete(path)))
+        } else {
+            let c = (b'a' + (i % 26) as u8) as char;
+            let path = text().index(cursor);
+            cursor += 1;
+            len += 1;
+            peer.change(|doc| {
+                doc.add_change(LocalChange::insert(
+                    path,
+                    Value::Primitive(amp::ScalarValue::Str(c.to_string())),
+                ))
+            })
+        };
+        changes.push(change);
+    }
+    changes
+}
+
+/// A single actor filling a map with `keys` keys, `keys_per_change` at a time
+pub fn large_map(keys: usize, keys_per_change: usize) -> Vec<Change> {
+    let mut peer = Peer::new();
+    let mut changes = vec![peer.change(|doc| {
+        doc.add_change(LocalChange::set(
+            Path::root().key("map"),
+            Value::Map(HashMap::new(), amp::MapType::Map),
+        ))
+    })];
+    for start in (0..keys).step_by(keys_per_change) {
+        let end = (start + keys_per_change).min(keys);
+        changes.push(peer.change(|doc| {
+            for i in start..end {
+                doc.add_change(LocalChange::set(
+                    Path::root().key("map").key(format!("key {}", i)),
+                    Value::Primitive(amp::ScalarValue::Uint(i as u64)),
+                ))?;
+            }
+            Ok(())
+        }));
+    }
+    changes
+}
+
+/// `actors` actors which in each of `rounds` rounds concurrently set a shared
+/// key, increment their own counter and append to a shared list, then merge
+/// each other's changes before the next round
+pub fn concurrent_actors(actors: usize, rounds: usize) -> Vec<Change> {
+    let mut peers: Vec<Peer> = (0..actors).map(|_| Peer::new()).collect();
+    let setup = peers[0].change(|doc| {
+        doc.add_change(LocalChange::set(
+            Path::root().key("list"),
+            Value::Sequence(Vec::new()),
+        ))?;
+        doc.add_change(LocalChange::set(
+            Path::root().key("counters"),
+            Value::Map(HashMap::new(), amp::MapType::Map),
+        ))
+    });
+    for peer in peers[1..].iter_mut() {
+        peer.merge(vec![setup.clone()]);
+    }
+    let mut changes = vec![setup];
+
+    for round in 0..rounds {
+        let round_changes: Vec<Change> = peers
+            .iter_mut()
+            .enumerate()
+            .map(|(i, peer)| {
+                let counter = Path::root().key("counters").key(format!("actor {}", i));
+                peer.change(|doc| {
+                    doc.add_change(LocalChange::set(
+                        Path::root().key("shared"),
+                        Value::Primitive(amp::ScalarValue::Uint(round as u64)),
+                    ))?;
+                    if round == 0 {
+                        doc.add_change(LocalChange::set(
+                            counter,
+                            Value::Primitive(amp::ScalarValue::Counter(0)),
+                        ))?;
+                    } else {
+                        doc.add_change(LocalChange::increment(counter))?;
+                    }
+                    doc.add_change(LocalChange::insert(
+                        Path::root().key("list").index(0),
+                        Value::Primitive(amp::ScalarValue::Uint(i as u64)),
+                    ))
+                })
+            })
+            .collect();
+        for (i, peer) in peers.iter_mut().enumerate() {
+            let others = round_changes
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, change)| change.clone())
+                .collect();
+            peer.merge(others);
+        }
+        changes.extend(round_changes);
+    }
+    changes
+}
+
+/// A backend with `changes` applied
+pub fn backend_with(changes: &[Change]) -> Backend {
+    let mut backend = Backend::init();
+    backend.apply_changes(changes.to_vec()).unwrap();
+    backend
+}
+
+/// The saved form of a document made up of `changes`
+pub fn saved(changes: &[Change]) -> Vec<u8> {
+    backend_with(changes).save().unwrap()
+}
@@ -8,7 +8,6 @@
  * need to test: 
  *
   automerge_get_missing_deps
-  automerge_error
 */
 
 #define BUFSIZE 4096
@@ -26,10 +25,10 @@ int main() {
   Backend * dbA = automerge_init();
   Backend * dbB = automerge_init();
 
-  const char * requestA1 = R"({"actor":"111111","seq":1,"time":0,"version":0,"ops":[{"action":"set","obj":"_root","key":"bird","value":"magpie"}]})";
-  const char * requestA2 = R"({"actor":"111111","seq":2,"time":0,"version":1,"ops":[{"action":"set","obj":"_root","key":"dog","value":"mastiff"}]})";
-  const char * requestB1 = R"({"actor":"222222","seq":1,"time":0,"version":0,"ops":[{"action":"set","obj":"_root","key":"bird","value":"crow"}]})";
-  const char * requestB2 = R"({"actor":"222222","seq":2,"time":0,"version":1,"ops":[{"action":"set","obj":"_root","key":"cat","value":"tabby"}]})";
+  const char * requestA1 = R"({"actor":"111111","seq":1,"time":0,"startOp":1,"deps":[],"ops":[{"action":"set","obj":"_root","key":"bird","value":"magpie","pred":[]}]})";
+  const char * requestA2 = R"({"actor":"111111","seq":2,"time":0,"startOp":2,"deps":[],"ops":[{"action":"set","obj":"_root","key":"dog","value":"mastiff","pred":[]}]})";
+  const char * requestB1 = R"({"actor":"222222","seq":1,"time":0,"startOp":1,"deps":[],"ops":[{"action":"set","obj":"_root","key":"bird","value":"crow","pred":[]}]})";
+  const char * requestB2 = R"({"actor":"222222","seq":2,"time":0,"startOp":2,"deps":[],"ops":[{"action":"set","obj":"_root","key":"cat","value":"tabby","pred":[]}]})";
 
   printf("*** requestA1 ***\n\n%s\n\n",requestA1);
 
@@ -66,6 +65,12 @@ int main() {
   printf("*** get_patch of dbA & dbC -- equal? *** --> %s\n\n",strlen(buff) == strlen(buff2) ? "true" : "false");
   assert(strlen(buff) == strlen(buff2));
 
+  len = automerge_get_patch_binary(dbA);
+  assert(len > 0 && len <= BUFSIZE);
+  automerge_read_binary(dbA, buff2);
+  printf("*** get_patch_binary of dbA - %d bytes vs %d bytes of json ***\n\n",len,(int)strlen(buff));
+  assert(len < strlen(buff));
+
   len = automerge_save(dbA);
   assert(len <= BUFSIZE);
   automerge_read_binary(dbA, buff2);
@@ -146,12 +151,79 @@ int main() {
   automerge_read_json(dbE, buff); // [] - nothing missing
   assert(strlen(buff) == 2);
 
+  printf("*** last local change and ack ***\n\n");
+  assert(automerge_get_last_local_change(dbE) == 0);
+  len = automerge_get_last_local_change(dbB);
+  assert(len > 0 && len <= BUFSIZE);
+  assert(automerge_read_binary(dbB, buff) == 0);
+  automerge_decode_change(dbB, len, buff);
+  automerge_read_json(dbB, buff2);
+  printf("last local change of dbB -- %s\n", buff2);
+  assert(automerge_ack(dbB, "222222", 2) == 0);
+  assert(automerge_ack(dbB, "222222", 3) == -1);
+  assert(automerge_error_code() == AutomergeErrorCode_InvalidSeq);
+
+  printf("*** edit a document by path ***\n\n");
+  Document * docA = automerge_document_init();
+  len = automerge_document_set(docA, R"(["birds"])", R"(["wren"])");
+  assert(len > 0 && len <= BUFSIZE);
+  automerge_document_read_binary(docA, buff);
+
+  printf("*** apply the change to docB ***\n\n");
+  Document * docB = automerge_document_init();
+  assert(automerge_document_apply_change(docB, len, buff) == 0);
+
+  len = automerge_document_insert(docA, R"(["birds", 1])", R"("magpie")");
+  assert(len > 0 && len <= BUFSIZE);
+  automerge_document_read_binary(docA, buff);
+  assert(automerge_document_apply_change(docB, len, buff) == 0);
+
+  len = automerge_document_set(docA, R"(["count"])", "3");
+  assert(len > 0 && len <= BUFSIZE);
+  automerge_document_read_binary(docA, buff);
+  assert(automerge_document_apply_change(docB, len, buff) == 0);
+
+  len = automerge_document_delete(docA, R"(["birds", 0])");
+  assert(len > 0 && len <= BUFSIZE);
+  automerge_document_read_binary(docA, buff);
+  assert(automerge_document_apply_change(docB, len, buff) == 0);
+
+  len = automerge_document_get_json(docB, "[]");
+  assert(len <= BUFSIZE);
+  automerge_document_read_json(docB, buff);
+  printf("*** docB state *** --> %s\n\n", buff);
+
+  len = automerge_document_get_json(docB, R"(["birds", 0])");
+  assert(len <= BUFSIZE);
+  automerge_document_read_json(docB, buff);
+  assert(strcmp(buff, "\"magpie\"") == 0);
+
+  double count;
+  assert(automerge_document_get_double(docB, R"(["count"])", &count) == 0);
+  assert(count == 3.0);
+  assert(automerge_document_get_json(docB, R"(["missing"])") == -1);
+
+  printf("*** errors are reported through automerge_error ***\n\n");
+  assert(automerge_document_set(docB, "not a path", "1") == -1);
+  assert(automerge_error_code() == AutomergeErrorCode_InvalidJson);
+  printf("bad path -- %s\n", automerge_error());
+  assert(automerge_document_delete(docB, R"(["missing", 0])") == -1);
+  assert(automerge_error_code() == AutomergeErrorCode_InvalidChangeRequest);
+  printf("missing path -- %s\n", automerge_error());
+  assert(automerge_load(3, (const uint8_t *) "bad") == NULL);
+  assert(automerge_error_code() != AutomergeErrorCode_Ok);
+  printf("bad load -- %s\n\n", automerge_error());
+  assert(automerge_document_get_double(docB, R"(["count"])", &count) == 0);
+  assert(automerge_error() == NULL);
+
   printf("free resources\n");
   automerge_free(dbA);
   automerge_free(dbB);
   automerge_free(dbC);
   automerge_free(dbD);
   automerge_free(dbE);
+  automerge_document_free(docA);
+  automerge_document_free(docB);
 
   printf("end\n");
 }
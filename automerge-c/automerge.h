@@ -6,14 +6,95 @@
 #include <stdint.h>
 #include <stdbool.h>
 
+/**
+ * The kind of error raised by the last failed call on the current thread.
+ * The numeric values are part of the C API and must not be reordered.
+ */
+typedef enum AutomergeErrorCode {
+  AutomergeErrorCode_Ok = 0,
+  AutomergeErrorCode_MissingObject = 1,
+  AutomergeErrorCode_MissingIndex = 2,
+  AutomergeErrorCode_MissingElement = 3,
+  AutomergeErrorCode_NoPathToObject = 4,
+  AutomergeErrorCode_CantExtractObject = 5,
+  AutomergeErrorCode_SkipList = 6,
+  AutomergeErrorCode_IndexOutOfBounds = 7,
+  AutomergeErrorCode_InvalidOpId = 8,
+  AutomergeErrorCode_InvalidObjectId = 9,
+  AutomergeErrorCode_MissingValue = 10,
+  AutomergeErrorCode_General = 11,
+  AutomergeErrorCode_MissingNumberValue = 12,
+  AutomergeErrorCode_UnknownVersion = 13,
+  AutomergeErrorCode_DuplicateChange = 14,
+  AutomergeErrorCode_DivergedState = 15,
+  AutomergeErrorCode_ChangeDecompress = 16,
+  AutomergeErrorCode_InvalidSeq = 17,
+  AutomergeErrorCode_MapKeyInSeq = 18,
+  AutomergeErrorCode_HeadToOpId = 19,
+  AutomergeErrorCode_DocFormatUnimplemented = 20,
+  AutomergeErrorCode_DivergentChange = 21,
+  AutomergeErrorCode_EncodeFailed = 22,
+  AutomergeErrorCode_DecodeFailed = 23,
+  AutomergeErrorCode_InvalidChange = 24,
+  AutomergeErrorCode_ChangeBadFormat = 25,
+  AutomergeErrorCode_Encoding = 26,
+  AutomergeErrorCode_TruncatedChange = 27,
+  AutomergeErrorCode_ChecksumMismatch = 28,
+  /**
+   * A JSON argument could not be parsed or a result could not be serialized
+   */
+  AutomergeErrorCode_InvalidJson = 100,
+  /**
+   * An argument was malformed, e.g. a bad actor ID, path or hash
+   */
+  AutomergeErrorCode_InvalidArgument = 101,
+  /**
+   * A local edit was rejected by the frontend
+   */
+  AutomergeErrorCode_InvalidChangeRequest = 102,
+  /**
+   * The frontend could not apply a patch from its backend
+   */
+  AutomergeErrorCode_InvalidPatch = 103,
+  /**
+   * A read was attempted with no pending result to read
+   */
+  AutomergeErrorCode_NoResult = 104,
+  /**
+   * The call panicked, which indicates a bug in automerge
+   */
+  AutomergeErrorCode_Panic = 199,
+} AutomergeErrorCode;
+
 typedef struct Backend Backend;
 
+/**
+ * A document which pairs a frontend with its own backend, so that C
+ * consumers can edit values by path and exchange binary changes with peers
+ * without implementing a frontend themselves
+ */
+typedef struct Document Document;
+
+/**
+ * # Safety
+ * This must me called with a valid backend pointer
+ * actor must be a valid pointer pointing to a cstring
+ */
+intptr_t automerge_ack(Backend *backend, const char *actor, uint64_t seq);
+
 /**
  * # Safety
  * This must me called with a valid backend pointer
  */
 intptr_t automerge_apply_changes(Backend *backend);
 
+/**
+ * # Safety
+ * This must me called with a valid backend pointer
+ * The patch is returned in the binary patch encoding and read with `automerge_read_binary`
+ */
+intptr_t automerge_apply_changes_binary(Backend *backend);
+
 /**
  * # Safety
  * This must me called with a valid backend pointer
@@ -21,6 +102,14 @@ intptr_t automerge_apply_changes(Backend *backend);
  */
 intptr_t automerge_apply_local_change(Backend *backend, const char *request);
 
+/**
+ * # Safety
+ * This must me called with a valid backend pointer
+ * request must be a valid pointer pointing to a cstring
+ * The patch is returned in the binary patch encoding and read with `automerge_read_binary`
+ */
+intptr_t automerge_apply_local_change_binary(Backend *backend, const char *request);
+
 /**
  * # Safety
  * This must me called with a valid backend pointer
@@ -33,12 +122,123 @@ Backend *automerge_clone(Backend *backend);
  */
 intptr_t automerge_decode_change(Backend *backend, uintptr_t len, const uint8_t *change);
 
+/**
+ * # Safety
+ * This must me called with a valid document pointer
+ * change must point to a valid memory location with at least len bytes
+ */
+intptr_t automerge_document_apply_change(Document *doc, uintptr_t len, const uint8_t *change);
+
+/**
+ * # Safety
+ * This must me called with a valid document pointer
+ * path must be a valid pointer to a cstring holding a JSON path
+ */
+intptr_t automerge_document_delete(Document *doc, const char *path);
+
+/**
+ * # Safety
+ * This must me called with a valid document pointer
+ */
+void automerge_document_free(Document *doc);
+
+/**
+ * # Safety
+ * This must me called with a valid document pointer
+ * path must be a valid pointer to a cstring holding a JSON path and out must be a valid pointer
+ */
+intptr_t automerge_document_get_bool(Document *doc, const char *path, bool *out);
+
+/**
+ * # Safety
+ * This must me called with a valid document pointer
+ * path must be a valid pointer to a cstring holding a JSON path and out must be a valid pointer
+ */
+intptr_t automerge_document_get_double(Document *doc, const char *path, double *out);
+
+/**
+ * # Safety
+ * This must me called with a valid document pointer
+ * path must be a valid pointer to a cstring holding a JSON path and out must be a valid pointer
+ */
+intptr_t automerge_document_get_int(Document *doc, const char *path, int64_t *out);
+
+/**
+ * # Safety
+ * This must me called with a valid document pointer
+ * path must be a valid pointer to a cstring holding a JSON path
+ */
+intptr_t automerge_document_get_json(Document *doc, const char *path);
+
+/**
+ * # Safety
+ * This must me called with a valid document pointer
+ * path must be a valid pointer to a cstring holding a JSON path
+ */
+intptr_t automerge_document_increment(Document *doc, const char *path, uint32_t by);
+
+Document *automerge_document_init(void);
+
+/**
+ * # Safety
+ * This must me called with a valid document pointer
+ * path and value must be valid pointers to cstrings holding a JSON path and a JSON value
+ */
+intptr_t automerge_document_insert(Document *doc, const char *path, const char *value);
+
+/**
+ * # Safety
+ * data pointer must be a valid pointer to len bytes
+ */
+Document *automerge_document_load(uintptr_t len, const uint8_t *data);
+
+/**
+ * # Safety
+ * This must me called with a valid document pointer
+ * the buffer must be a valid pointer pointing to at least as much space as was
+ * required by the previous binary result call
+ */
+intptr_t automerge_document_read_binary(Document *doc, uint8_t *buffer);
+
+/**
+ * # Safety
+ * This must me called with a valid document pointer
+ * and buffer must be a valid pointer of at least the number of bytes returned by the previous
+ * call that generated a json result
+ */
+intptr_t automerge_document_read_json(Document *doc, char *buffer);
+
+/**
+ * # Safety
+ * This must me called with a valid document pointer
+ */
+intptr_t automerge_document_save(Document *doc);
+
+/**
+ * # Safety
+ * This must me called with a valid document pointer
+ * path and value must be valid pointers to cstrings holding a JSON path and a JSON value
+ */
+intptr_t automerge_document_set(Document *doc, const char *path, const char *value);
+
 /**
  * # Safety
  * This must me called with a valid pointer a json string of a change
  */
 intptr_t automerge_encode_change(Backend *backend, const char *change);
 
+/**
+ * Returns the message of the last error raised on this thread, or null if
+ * the last call succeeded. The pointer is valid until the next call.
+ */
+const char *automerge_error(void);
+
+/**
+ * Returns the code of the last error raised on this thread, or `Ok` if the
+ * last call succeeded
+ */
+AutomergeErrorCode automerge_error_code(void);
+
 /**
  * # Safety
  * This must me called with a valid backend pointer
@@ -58,6 +258,13 @@ intptr_t automerge_get_changes(Backend *backend, uintptr_t len, const uint8_t *b
  */
 intptr_t automerge_get_changes_for_actor(Backend *backend, const char *actor);
 
+/**
+ * # Safety
+ * This must me called with a valid backend pointer
+ * The change is read with `automerge_read_binary`, 0 is returned if there is no local change
+ */
+intptr_t automerge_get_last_local_change(Backend *backend);
+
 /**
  * # Safety
  * This must me called with a valid backend pointer
@@ -70,6 +277,13 @@ intptr_t automerge_get_missing_deps(Backend *backend);
  */
 intptr_t automerge_get_patch(Backend *backend);
 
+/**
+ * # Safety
+ * This must me called with a valid backend pointer
+ * The patch is returned in the binary patch encoding and read with `automerge_read_binary`
+ */
+intptr_t automerge_get_patch_binary(Backend *backend);
+
 Backend *automerge_init(void);
 
 /**
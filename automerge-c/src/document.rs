@@ -0,0 +1,338 @@
+use automerge_backend::Change;
+use automerge_frontend::{Frontend, InvalidChangeRequest, LocalChange, Path, Value};
+use automerge_protocol as amp;
+use std::convert::TryFrom;
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::ptr;
+
+use crate::error::{handle, AutomergeErrorCode, Error};
+use crate::from_buf_raw;
+
+/// A document which pairs a frontend with its own backend, so that C
+/// consumers can edit values by path and exchange binary changes with peers
+/// without implementing a frontend themselves
+pub struct Document {
+    frontend: Frontend,
+    backend: automerge_backend::Backend,
+    text: Option<String>,
+    binary: Option<Vec<u8>>,
+}
+
+impl Document {
+    fn init(backend: automerge_backend::Backend) -> Result<Document, Error> {
+        let mut frontend = Frontend::new();
+        frontend.apply_patch(backend.get_patch()?)?;
+        Ok(Document {
+            frontend,
+            backend,
+            text: None,
+            binary: None,
+        })
+    }
+
+    /// Apply a single local change, returning the length of the resulting
+    /// binary change
+    fn apply_local(&mut self, change: LocalChange) -> Result<isize, Error> {
+        let request = self
+            .frontend
+            .change::<_, InvalidChangeRequest>(None, |doc| doc.add_change(change))?
+            .ok_or_else(|| Error::new(AutomergeErrorCode::NoResult, "Change had no effect"))?;
+        let (patch, change) = self.backend.apply_local_change(request)?;
+        self.frontend.apply_patch(patch)?;
+        let len = change.bytes.len();
+        self.binary = Some(change.bytes.clone());
+        Ok(len as isize)
+    }
+
+    fn apply_remote(&mut self, change: Vec<u8>) -> Result<isize, Error> {
+        let change = Change::from_bytes(change)?;
+        let patch = self.backend.apply_changes(vec![change])?;
+        self.frontend.apply_patch(patch)?;
+        Ok(0)
+    }
+
+    fn value_at(&self, path: &Path) -> Result<Value, Error> {
+        self.frontend
+            .get_value(path)
+            .ok_or_else(|| Error::invalid_argument(format!("No value at path {:?}", path)))
+    }
+
+    fn scalar_at(&self, path: &Path) -> Result<amp::ScalarValue, Error> {
+        match self.value_at(path)? {
+            Value::Primitive(scalar) => Ok(scalar),
+            value => Err(type_mismatch(path, &value)),
+        }
+    }
+}
+
+fn type_mismatch(path: &Path, value: &Value) -> Error {
+    Error::invalid_argument(format!(
+        "Value at path {:?} has the wrong type: {:?}",
+        path, value
+    ))
+}
+
+impl From<Document> for *mut Document {
+    fn from(d: Document) -> Self {
+        Box::into_raw(Box::new(d))
+    }
+}
+
+/// Paths are JSON arrays of map keys and list indices, e.g. `["birds", 3, "name"]`
+unsafe fn parse_path(path: *const c_char) -> Result<Path, Error> {
+    let path = CStr::from_ptr(path).to_string_lossy();
+    let elems: Vec<serde_json::Value> = serde_json::from_str(&path)?;
+    let mut result = Path::root();
+    for elem in elems {
+        result = match elem {
+            serde_json::Value::String(key) => result.key(key),
+            serde_json::Value::Number(n) => match n.as_u64().map(u32::try_from) {
+                Some(Ok(index)) => result.index(index),
+                _ => return Err(Error::invalid_argument(format!("Invalid index: {}", n))),
+            },
+            elem => {
+                return Err(Error::invalid_argument(format!(
+                    "Invalid path element: {}",
+                    elem
+                )))
+            }
+        };
+    }
+    Ok(result)
+}
+
+unsafe fn parse_value(value: *const c_char) -> Result<Value, Error> {
+    let value = CStr::from_ptr(value).to_string_lossy();
+    let json: serde_json::Value = serde_json::from_str(&value)?;
+    Ok(Value::from_json(&json))
+}
+
+#[no_mangle]
+pub extern "C" fn automerge_document_init() -> *mut Document {
+    handle(ptr::null_mut(), || {
+        Ok(Document::init(automerge_backend::Backend::init())?.into())
+    })
+}
+
+/// # Safety
+/// data pointer must be a valid pointer to len bytes
+#[no_mangle]
+pub unsafe extern "C" fn automerge_document_load(len: usize, data: *const u8) -> *mut Document {
+    handle(ptr::null_mut(), || {
+        let bytes = from_buf_raw(data, len);
+        let backend = automerge_backend::Backend::load(bytes)?;
+        Ok(Document::init(backend)?.into())
+    })
+}
+
+/// # Safety
+/// This must me called with a valid document pointer
+#[no_mangle]
+pub unsafe extern "C" fn automerge_document_free(doc: *mut Document) {
+    let doc: Document = *Box::from_raw(doc);
+    drop(doc)
+}
+
+/// # Safety
+/// This must me called with a valid document pointer
+/// path and value must be valid pointers to cstrings holding a JSON path and a JSON value
+#[no_mangle]
+pub unsafe extern "C" fn automerge_document_set(
+    doc: *mut Document,
+    path: *const c_char,
+    value: *const c_char,
+) -> isize {
+    handle(-1, || {
+        let change = LocalChange::set(parse_path(path)?, parse_value(value)?);
+        (*doc).apply_local(change)
+    })
+}
+
+/// # Safety
+/// This must me called with a valid document pointer
+/// path and value must be valid pointers to cstrings holding a JSON path and a JSON value
+#[no_mangle]
+pub unsafe extern "C" fn automerge_document_insert(
+    doc: *mut Document,
+    path: *const c_char,
+    value: *const c_char,
+) -> isize {
+    handle(-1, || {
+        let change = LocalChange::insert(parse_path(path)?, parse_value(value)?);
+        (*doc).apply_local(change)
+    })
+}
+
+/// # Safety
+/// This must me called with a valid document pointer
+/// path must be a valid pointer to a cstring holding a JSON path
+#[no_mangle]
+pub unsafe extern "C" fn automerge_document_delete(
+    doc: *mut Document,
+    path: *const c_char,
+) -> isize {
+    handle(-1, || {
+        (*doc).apply_local(LocalChange::delete(parse_path(path)?))
+    })
+}
+
+/// # Safety
+/// This must me called with a valid document pointer
+/// path must be a valid pointer to a cstring holding a JSON path
+#[no_mangle]
+pub unsafe extern "C" fn automerge_document_increment(
+    doc: *mut Document,
+    path: *const c_char,
+    by: u32,
+) -> isize {
+    handle(-1, || {
+        (*doc).apply_local(LocalChange::increment_by(parse_path(path)?, by.into()))
+    })
+}
+
+/// # Safety
+/// This must me called with a valid document pointer
+/// change must point to a valid memory location with at least len bytes
+#[no_mangle]
+pub unsafe extern "C" fn automerge_document_apply_change(
+    doc: *mut Document,
+    len: usize,
+    change: *const u8,
+) -> isize {
+    handle(-1, || (*doc).apply_remote(from_buf_raw(change, len)))
+}
+
+/// # Safety
+/// This must me called with a valid document pointer
+/// path must be a valid pointer to a cstring holding a JSON path
+#[no_mangle]
+pub unsafe extern "C" fn automerge_document_get_json(
+    doc: *mut Document,
+    path: *const c_char,
+) -> isize {
+    handle(-1, || {
+        let value = (*doc).value_at(&parse_path(path)?)?;
+        let text = serde_json::to_string(&value.to_json())?;
+        let len = (text.len() + 1) as isize;
+        (*doc).text = Some(text);
+        Ok(len)
+    })
+}
+
+/// # Safety
+/// This must me called with a valid document pointer
+/// path must be a valid pointer to a cstring holding a JSON path and out must be a valid pointer
+#[no_mangle]
+pub unsafe extern "C" fn automerge_document_get_int(
+    doc: *mut Document,
+    path: *const c_char,
+    out: *mut i64,
+) -> isize {
+    handle(-1, || {
+        let path = parse_path(path)?;
+        *out = match (*doc).scalar_at(&path)? {
+            amp::ScalarValue::Int(i)
+            | amp::ScalarValue::Counter(i)
+            | amp::ScalarValue::Timestamp(i) => i,
+            amp::ScalarValue::Uint(u) if u <= i64::MAX as u64 => u as i64,
+            scalar => return Err(type_mismatch(&path, &Value::Primitive(scalar))),
+        };
+        Ok(0)
+    })
+}
+
+/// # Safety
+/// This must me called with a valid document pointer
+/// path must be a valid pointer to a cstring holding a JSON path and out must be a valid pointer
+#[no_mangle]
+pub unsafe extern "C" fn automerge_document_get_double(
+    doc: *mut Document,
+    path: *const c_char,
+    out: *mut f64,
+) -> isize {
+    handle(-1, || {
+        let path = parse_path(path)?;
+        *out = match (*doc).scalar_at(&path)? {
+            amp::ScalarValue::F64(f) => f,
+            amp::ScalarValue::F32(f) => f as f64,
+            scalar => return Err(type_mismatch(&path, &Value::Primitive(scalar))),
+        };
+        Ok(0)
+    })
+}
+
+/// # Safety
+/// This must me called with a valid document pointer
+/// path must be a valid pointer to a cstring holding a JSON path and out must be a valid pointer
+#[no_mangle]
+pub unsafe extern "C" fn automerge_document_get_bool(
+    doc: *mut Document,
+    path: *const c_char,
+    out: *mut bool,
+) -> isize {
+    handle(-1, || {
+        let path = parse_path(path)?;
+        *out = match (*doc).scalar_at(&path)? {
+            amp::ScalarValue::Boolean(b) => b,
+            scalar => return Err(type_mismatch(&path, &Value::Primitive(scalar))),
+        };
+        Ok(0)
+    })
+}
+
+/// # Safety
+/// This must me called with a valid document pointer
+#[no_mangle]
+pub unsafe extern "C" fn automerge_document_save(doc: *mut Document) -> isize {
+    handle(-1, || {
+        let data = (*doc).backend.save()?;
+        let len = data.len();
+        (*doc).binary = Some(data);
+        Ok(len as isize)
+    })
+}
+
+/// # Safety
+/// This must me called with a valid document pointer
+/// and buffer must be a valid pointer of at least the number of bytes returned by the previous
+/// call that generated a json result
+#[no_mangle]
+pub unsafe extern "C" fn automerge_document_read_json(
+    doc: *mut Document,
+    buffer: *mut c_char,
+) -> isize {
+    handle(-1, || {
+        if let Some(text) = (*doc).text.take() {
+            let len = text.len();
+            buffer.copy_from(text.as_ptr().cast(), len);
+            (*buffer.add(len)) = 0; // null terminate
+            Ok(0)
+        } else {
+            (*buffer) = 0;
+            Err(Error::new(
+                AutomergeErrorCode::NoResult,
+                "No JSON result to read",
+            ))
+        }
+    })
+}
+
+/// # Safety
+/// This must me called with a valid document pointer
+/// the buffer must be a valid pointer pointing to at least as much space as was
+/// required by the previous binary result call
+#[no_mangle]
+pub unsafe extern "C" fn automerge_document_read_binary(
+    doc: *mut Document,
+    buffer: *mut u8,
+) -> isize {
+    handle(-1, || {
+        let bin = (*doc)
+            .binary
+            .take()
+            .ok_or_else(|| Error::new(AutomergeErrorCode::NoResult, "No binary result to read"))?;
+        buffer.copy_from(bin.as_ptr(), bin.len());
+        Ok(0)
+    })
+}
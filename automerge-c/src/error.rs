@@ -0,0 +1,202 @@
+use automerge_backend::AutomergeError;
+use std::any::Any;
+use std::cell::RefCell;
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+/// The kind of error raised by the last failed call on the current thread.
+/// The numeric values are part of the C API and must not be reordered.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AutomergeErrorCode {
+    Ok = 0,
+    MissingObject = 1,
+    MissingIndex = 2,
+    MissingElement = 3,
+    NoPathToObject = 4,
+    CantExtractObject = 5,
+    SkipList = 6,
+    IndexOutOfBounds = 7,
+    InvalidOpId = 8,
+    InvalidObjectId = 9,
+    MissingValue = 10,
+    General = 11,
+    MissingNumberValue = 12,
+    UnknownVersion = 13,
+    DuplicateChange = 14,
+    DivergedState = 15,
+    ChangeDecompress = 16,
+    InvalidSeq = 17,
+    MapKeyInSeq = 18,
+    HeadToOpId = 19,
+    DocFormatUnimplemented = 20,
+    DivergentChange = 21,
+    EncodeFailed = 22,
+    DecodeFailed = 23,
+    InvalidChange = 24,
+    ChangeBadFormat = 25,
+    Encoding = 26,
+    TruncatedChange = 27,
+    ChecksumMismatch = 28,
+    /// A JSON argument could not be parsed or a result could not be serialized
+    InvalidJson = 100,
+    /// An argument was malformed, e.g. a bad actor ID, path or hash
+    InvalidArgument = 101,
+    /// A local edit was rejected by the frontend
+    InvalidChangeRequest = 102,
+    /// The frontend could not apply a patch from its backend
+    InvalidPatch = 103,
+    /// A read was attempted with no pending result to read
+    NoResult = 104,
+    /// The call panicked, which indicates a bug in automerge
+    Panic = 199,
+}
+
+impl From<&AutomergeError> for AutomergeErrorCode {
+    fn from(err: &AutomergeError) -> Self {
+        match err {
+            AutomergeError::MissingObjectError => AutomergeErrorCode::MissingObject,
+            AutomergeError::MissingIndex(_) => AutomergeErrorCode::MissingIndex,
+            AutomergeError::MissingElement(..) => AutomergeErrorCode::MissingElement,
+            AutomergeError::NoPathToObject(_) => AutomergeErrorCode::NoPathToObject,
+            AutomergeError::CantExtractObject(_) => AutomergeErrorCode::CantExtractObject,
+            AutomergeError::SkipListError(_) => AutomergeErrorCode::SkipList,
+            AutomergeError::IndexOutOfBounds(_) => AutomergeErrorCode::IndexOutOfBounds,
+            AutomergeError::InvalidOpID(_) => AutomergeErrorCode::InvalidOpId,
+            AutomergeError::InvalidObjectID(_) => AutomergeErrorCode::InvalidObjectId,
+            AutomergeError::MissingValue => AutomergeErrorCode::MissingValue,
+            AutomergeError::GeneralError(_) => AutomergeErrorCode::General,
+            AutomergeError::MissingNumberValue => AutomergeErrorCode::MissingNumberValue,
+            AutomergeError::UnknownVersion(_) => AutomergeErrorCode::UnknownVersion,
+            AutomergeError::DuplicateChange(_) => AutomergeErrorCode::DuplicateChange,
+            AutomergeError::DivergedState(_) => AutomergeErrorCode::DivergedState,
+            AutomergeError::ChangeDecompressError(_) => AutomergeErrorCode::ChangeDecompress,
+            AutomergeError::InvalidSeq(_) => AutomergeErrorCode::InvalidSeq,
+            AutomergeError::MapKeyInSeq => AutomergeErrorCode::MapKeyInSeq,
+            AutomergeError::HeadToOpID => AutomergeErrorCode::HeadToOpId,
+            AutomergeError::DocFormatUnimplemented => AutomergeErrorCode::DocFormatUnimplemented,
+            AutomergeError::DivergentChange(_) => AutomergeErrorCode::DivergentChange,
+            AutomergeError::EncodeFailed => AutomergeErrorCode::EncodeFailed,
+            AutomergeError::DecodeFailed => AutomergeErrorCode::DecodeFailed,
+            AutomergeError::InvalidChange { .. } => AutomergeErrorCode::InvalidChange,
+            AutomergeError::ChangeBadFormat { .. } => AutomergeErrorCode::ChangeBadFormat,
+            AutomergeError::EncodingError => AutomergeErrorCode::Encoding,
+            AutomergeError::TruncatedChange { .. } => AutomergeErrorCode::TruncatedChange,
+            AutomergeError::ChecksumMismatch { .. } => AutomergeErrorCode::ChecksumMismatch,
+        }
+    }
+}
+
+/// An error which is reported to C callers through `automerge_error` and
+/// `automerge_error_code`
+#[derive(Debug)]
+pub(crate) struct Error {
+    code: AutomergeErrorCode,
+    message: String,
+}
+
+impl Error {
+    pub(crate) fn new<S: Into<String>>(code: AutomergeErrorCode, message: S) -> Error {
+        Error {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub(crate) fn invalid_argument<S: Into<String>>(message: S) -> Error {
+        Error::new(AutomergeErrorCode::InvalidArgument, message)
+    }
+}
+
+impl From<AutomergeError> for Error {
+    fn from(err: AutomergeError) -> Self {
+        Error::new((&err).into(), err.to_string())
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::new(AutomergeErrorCode::InvalidJson, err.to_string())
+    }
+}
+
+impl From<automerge_frontend::InvalidChangeRequest> for Error {
+    fn from(err: automerge_frontend::InvalidChangeRequest) -> Self {
+        Error::new(AutomergeErrorCode::InvalidChangeRequest, err.to_string())
+    }
+}
+
+impl From<automerge_frontend::InvalidPatch> for Error {
+    fn from(err: automerge_frontend::InvalidPatch) -> Self {
+        Error::new(AutomergeErrorCode::InvalidPatch, err.to_string())
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<(AutomergeErrorCode, CString)>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(err: Error) {
+    // Interior nul bytes can't be represented in a C string
+    let message = CString::new(err.message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some((err.code, message)));
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        (*s).to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+/// Run `f`, recording any error it returns (or any panic it raises, which must
+/// not unwind into C) as the last error and returning `failure` instead
+pub(crate) fn handle<T, F>(failure: T, f: F) -> T
+where
+    F: FnOnce() -> Result<T, Error>,
+{
+    LAST_ERROR.with(|last| *last.borrow_mut() = None);
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(result)) => result,
+        Ok(Err(err)) => {
+            set_last_error(err);
+            failure
+        }
+        Err(payload) => {
+            set_last_error(Error::new(
+                AutomergeErrorCode::Panic,
+                panic_message(payload.as_ref()),
+            ));
+            failure
+        }
+    }
+}
+
+/// Returns the message of the last error raised on this thread, or null if
+/// the last call succeeded. The pointer is valid until the next call.
+#[no_mangle]
+pub extern "C" fn automerge_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map(|(_, message)| message.as_ptr())
+            .unwrap_or_else(ptr::null)
+    })
+}
+
+/// Returns the code of the last error raised on this thread, or `Ok` if the
+/// last call succeeded
+#[no_mangle]
+pub extern "C" fn automerge_error_code() -> AutomergeErrorCode {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map(|(code, _)| *code)
+            .unwrap_or(AutomergeErrorCode::Ok)
+    })
+}
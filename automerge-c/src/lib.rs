@@ -3,23 +3,28 @@ extern crate errno;
 extern crate libc;
 extern crate serde;
 
-use automerge_backend::{AutomergeError, Change};
-use automerge_protocol::UncompressedChange;
+use automerge_backend::Change;
+use automerge_protocol::{encode_patch, UncompressedChange};
 use errno::{set_errno, Errno};
 use serde::ser::Serialize;
 use std::convert::TryInto;
-use std::ffi::{CStr, CString};
+use std::ffi::CStr;
 use std::ops::{Deref, DerefMut};
 use std::os::raw::c_char;
 use std::ptr;
 
+mod document;
+mod error;
+pub use document::Document;
+pub use error::{automerge_error, automerge_error_code, AutomergeErrorCode};
+use error::{handle, Error};
+
 #[derive(Clone)]
 pub struct Backend {
     handle: automerge_backend::Backend,
     text: Option<String>,
     binary: Vec<Vec<u8>>,
     queue: Option<Vec<Vec<u8>>>,
-    error: Option<CString>,
 }
 
 impl Deref for Backend {
@@ -30,11 +35,16 @@ impl Deref for Backend {
     }
 }
 
-unsafe fn from_buf_raw<T>(ptr: *const T, elts: usize) -> Vec<T> {
-    let mut dst = Vec::with_capacity(elts);
-    dst.set_len(elts);
-    ptr::copy(ptr, dst.as_mut_ptr(), elts);
-    dst
+unsafe fn from_buf_raw(ptr: *const u8, elts: usize) -> Vec<u8> {
+    if elts == 0 {
+        // ptr may be null when there is nothing to read
+        return Vec::new();
+    }
+    std::slice::from_raw_parts(ptr, elts).to_vec()
+}
+
+unsafe fn from_cstr<'a>(s: *const c_char) -> std::borrow::Cow<'a, str> {
+    CStr::from_ptr(s).to_string_lossy()
 }
 
 impl Backend {
@@ -44,42 +54,37 @@ impl Backend {
             text: None,
             binary: Vec::new(),
             queue: None,
-            error: None,
         }
     }
 
-    fn generate_json<T: Serialize>(&mut self, val: Result<T, AutomergeError>) -> isize {
-        if let Ok(val) = val {
-            if let Ok(text) = serde_json::to_string(&val) {
-                let len = (text.len() + 1) as isize;
-                self.text = Some(text);
-                return len;
-            }
-        }
-        -1
+    fn generate_json<T: Serialize>(&mut self, val: T) -> Result<isize, Error> {
+        let text = serde_json::to_string(&val)?;
+        let len = (text.len() + 1) as isize;
+        self.text = Some(text);
+        Ok(len)
     }
 
-    fn handle_binary(&mut self, b: Result<Vec<u8>, AutomergeError>) -> isize {
-        // throw an error here if self.binary is not empty()
-        if let Ok(bin) = b {
-            let len = bin.len();
-            self.binary = vec![bin];
-            len as isize
-        } else {
-            -1
-        }
+    fn handle_binary(&mut self, bin: Vec<u8>) -> isize {
+        let len = bin.len();
+        self.binary = vec![bin];
+        len as isize
     }
 
-    fn handle_binaries(&mut self, b: Result<Vec<&Change>, AutomergeError>) -> isize {
-        // throw an error here if self.binary is not empty()
-        if let Ok(bin) = b {
-            let len = bin[0].bytes.len();
-            self.binary = bin.iter().map(|b| b.bytes.clone()).collect();
-            self.binary.reverse();
-            len as isize
-        } else {
-            -1
-        }
+    fn handle_binaries(&mut self, bin: Vec<&Change>) -> isize {
+        self.binary = bin.iter().map(|b| b.bytes.clone()).collect();
+        self.binary.reverse();
+        // an empty list of changes has nothing to read
+        self.binary.last().map(|b| b.len()).unwrap_or(0) as isize
+    }
+
+    fn take_queue(&mut self) -> Result<Vec<Change>, Error> {
+        let queue = self.queue.take().ok_or_else(|| {
+            Error::new(AutomergeErrorCode::NoResult, "No changes have been written")
+        })?;
+        Ok(queue
+            .into_iter()
+            .map(Change::from_bytes)
+            .collect::<Result<_, _>>()?)
     }
 }
 
@@ -108,13 +113,17 @@ impl From<Backend> for *mut Backend {
   getChangesForActor => automerge_get_changes_for_actor
   getChanges => automerge_get_changes
   getMissingDeps => automerge_get_missing_deps
+  getLastLocalChange => automerge_get_last_local_change
+  ack => automerge_ack
   getUndoStack => ..
   getRedoStack => ..
 */
 
 #[no_mangle]
 pub extern "C" fn automerge_init() -> *mut Backend {
-    Backend::init(automerge_backend::Backend::init()).into()
+    handle(ptr::null_mut(), || {
+        Ok(Backend::init(automerge_backend::Backend::init()).into())
+    })
 }
 
 /// # Safety
@@ -133,19 +142,28 @@ pub unsafe extern "C" fn automerge_apply_local_change(
     backend: *mut Backend,
     request: *const c_char,
 ) -> isize {
-    let request: &CStr = CStr::from_ptr(request);
-    let request = request.to_string_lossy();
-    let request: Result<UncompressedChange, _> = serde_json::from_str(&request);
-    if let Ok(request) = request {
+    handle(-1, || {
+        let request: UncompressedChange = serde_json::from_str(&from_cstr(request))?;
         // FIXME - need to update the c api to all receiving the binary change here
-        if let Ok((patch, _change)) = (*backend).apply_local_change(request) {
-            (*backend).generate_json(Ok(patch))
-        } else {
-            -1
-        }
-    } else {
-        -1
-    }
+        let (patch, _change) = (*backend).apply_local_change(request)?;
+        (*backend).generate_json(patch)
+    })
+}
+
+/// # Safety
+/// This must me called with a valid backend pointer
+/// request must be a valid pointer pointing to a cstring
+/// The patch is returned in the binary patch encoding and read with `automerge_read_binary`
+#[no_mangle]
+pub unsafe extern "C" fn automerge_apply_local_change_binary(
+    backend: *mut Backend,
+    request: *const c_char,
+) -> isize {
+    handle(-1, || {
+        let request: UncompressedChange = serde_json::from_str(&from_cstr(request))?;
+        let (patch, _change) = (*backend).apply_local_change(request)?;
+        Ok((*backend).handle_binary(encode_patch(&patch)))
+    })
 }
 
 /// # Safety
@@ -157,83 +175,98 @@ pub unsafe extern "C" fn automerge_write_change(
     len: usize,
     change: *const u8,
 ) {
-    let bytes = from_buf_raw(change, len);
-    if let Some(ref mut queue) = (*backend).queue {
-        queue.push(bytes)
-    } else {
-        (*backend).queue = Some(vec![bytes])
-    }
+    handle((), || {
+        let bytes = from_buf_raw(change, len);
+        (*backend).queue.get_or_insert_with(Vec::new).push(bytes);
+        Ok(())
+    })
 }
 
 /// # Safety
 /// This must me called with a valid backend pointer
 #[no_mangle]
 pub unsafe extern "C" fn automerge_apply_changes(backend: *mut Backend) -> isize {
-    if let Some(changes) = (*backend).queue.take() {
-        // FIXME
-        let changes = changes
-            .iter()
-            .map(|c| Change::from_bytes(c.to_vec()).unwrap())
-            .collect();
-        let patch = (*backend).apply_changes(changes);
+    handle(-1, || {
+        let changes = (*backend).take_queue()?;
+        let patch = (*backend).apply_changes(changes)?;
         (*backend).generate_json(patch)
-    } else {
-        -1
-    }
+    })
+}
+
+/// # Safety
+/// This must me called with a valid backend pointer
+/// The patch is returned in the binary patch encoding and read with `automerge_read_binary`
+#[no_mangle]
+pub unsafe extern "C" fn automerge_apply_changes_binary(backend: *mut Backend) -> isize {
+    handle(-1, || {
+        let changes = (*backend).take_queue()?;
+        let patch = (*backend).apply_changes(changes)?;
+        Ok((*backend).handle_binary(encode_patch(&patch)))
+    })
 }
 
 /// # Safety
 /// This must me called with a valid backend pointer
 #[no_mangle]
 pub unsafe extern "C" fn automerge_get_patch(backend: *mut Backend) -> isize {
-    let patch = (*backend).get_patch();
-    (*backend).generate_json(patch)
+    handle(-1, || {
+        let patch = (*backend).get_patch()?;
+        (*backend).generate_json(patch)
+    })
+}
+
+/// # Safety
+/// This must me called with a valid backend pointer
+/// The patch is returned in the binary patch encoding and read with `automerge_read_binary`
+#[no_mangle]
+pub unsafe extern "C" fn automerge_get_patch_binary(backend: *mut Backend) -> isize {
+    handle(-1, || {
+        let patch = (*backend).get_patch()?;
+        Ok((*backend).handle_binary(encode_patch(&patch)))
+    })
 }
 
 /// # Safety
 /// This must me called with a valid backend pointer
 #[no_mangle]
 pub unsafe extern "C" fn automerge_load_changes(backend: *mut Backend) -> isize {
-    if let Some(changes) = (*backend).queue.take() {
-        // FIXME
-        let changes = changes
-            .iter()
-            .map(|c| Change::from_bytes(c.to_vec()).unwrap())
-            .collect();
-        if (*backend).load_changes(changes).is_ok() {
-            return 0;
-        }
-    }
-    -1
+    handle(-1, || {
+        let changes = (*backend).take_queue()?;
+        (*backend).load_changes(changes)?;
+        Ok(0)
+    })
 }
 
 /// # Safety
 /// This must me called with a valid backend pointer
 #[no_mangle]
 pub unsafe extern "C" fn automerge_clone(backend: *mut Backend) -> *mut Backend {
-    (*backend).clone().into()
+    handle(ptr::null_mut(), || Ok((*backend).clone().into()))
 }
 
 /// # Safety
 /// This must me called with a valid backend pointer
 #[no_mangle]
 pub unsafe extern "C" fn automerge_save(backend: *mut Backend) -> isize {
-    let data = (*backend).save();
-    (*backend).handle_binary(data)
+    handle(-1, || {
+        let data = (*backend).save()?;
+        Ok((*backend).handle_binary(data))
+    })
 }
 
 /// # Safety
 /// data pointer must be a valid pointer to len bytes
 #[no_mangle]
 pub unsafe extern "C" fn automerge_load(len: usize, data: *const u8) -> *mut Backend {
-    let bytes = from_buf_raw(data, len);
-    let result = automerge_backend::Backend::load(bytes);
-    if let Ok(backend) = result {
-        Backend::init(backend).into()
-    } else {
+    let backend = handle(ptr::null_mut(), || {
+        let bytes = from_buf_raw(data, len);
+        let backend = automerge_backend::Backend::load(bytes)?;
+        Ok(Backend::init(backend).into())
+    });
+    if backend.is_null() {
         set_errno(Errno(1));
-        ptr::null_mut()
     }
+    backend
 }
 
 /// # Safety
@@ -243,15 +276,15 @@ pub unsafe extern "C" fn automerge_get_changes_for_actor(
     backend: *mut Backend,
     actor: *const c_char,
 ) -> isize {
-    let actor: &CStr = CStr::from_ptr(actor);
-    let actor = actor.to_string_lossy();
-    if let Ok(actor) = actor.as_ref().try_into() {
-        let changes = (*backend).get_changes_for_actor_id(&actor);
-        (*backend).handle_binaries(changes)
-    } else {
-        // bad actor error
-        -1
-    }
+    handle(-1, || {
+        let actor = from_cstr(actor);
+        let actor = actor
+            .as_ref()
+            .try_into()
+            .map_err(|_| Error::invalid_argument(format!("Invalid actor ID: {}", actor)))?;
+        let changes = (*backend).get_changes_for_actor_id(&actor)?;
+        Ok((*backend).handle_binaries(changes))
+    })
 }
 
 /// # Safety
@@ -262,9 +295,11 @@ pub unsafe extern "C" fn automerge_decode_change(
     len: usize,
     change: *const u8,
 ) -> isize {
-    let bytes = from_buf_raw(change, len);
-    let change = Change::from_bytes(bytes).unwrap();
-    (*backend).generate_json(Ok(change.decode()))
+    handle(-1, || {
+        let bytes = from_buf_raw(change, len);
+        let change = Change::from_bytes(bytes)?;
+        (*backend).generate_json(change.decode())
+    })
 }
 
 /// # Safety
@@ -274,11 +309,11 @@ pub unsafe extern "C" fn automerge_encode_change(
     backend: *mut Backend,
     change: *const c_char,
 ) -> isize {
-    let change: &CStr = CStr::from_ptr(change);
-    let change = change.to_string_lossy();
-    let uncomp_change: UncompressedChange = serde_json::from_str(&change).unwrap();
-    let change: Change = uncomp_change.try_into().unwrap();
-    (*backend).handle_binary(Ok(change.bytes))
+    handle(-1, || {
+        let uncomp_change: UncompressedChange = serde_json::from_str(&from_cstr(change))?;
+        let change: Change = uncomp_change.into();
+        Ok((*backend).handle_binary(change.bytes))
+    })
 }
 
 /// # Safety
@@ -290,35 +325,60 @@ pub unsafe extern "C" fn automerge_get_changes(
     len: usize,
     binary: *const u8,
 ) -> isize {
-    let mut have_deps = Vec::new();
-    for i in 0..len {
-        have_deps.push(
-            from_buf_raw(binary.offset(i as isize * 32), 32)
-                .as_slice()
-                .try_into()
-                .unwrap(),
-        )
-    }
-    let changes = (*backend).get_changes(&have_deps);
-    (*backend).handle_binaries(Ok(changes))
+    handle(-1, || {
+        let mut have_deps = Vec::new();
+        for i in 0..len {
+            have_deps.push(
+                from_buf_raw(binary.add(i * 32), 32)
+                    .as_slice()
+                    .try_into()
+                    .map_err(|_| Error::invalid_argument("Invalid change hash"))?,
+            )
+        }
+        let changes = (*backend).get_changes(&have_deps);
+        Ok((*backend).handle_binaries(changes))
+    })
 }
 
 /// # Safety
 /// This must me called with a valid backend pointer
+/// The change is read with `automerge_read_binary`, 0 is returned if there is no local change
 #[no_mangle]
-pub unsafe extern "C" fn automerge_get_missing_deps(backend: *mut Backend) -> isize {
-    let missing = (*backend).get_missing_deps();
-    (*backend).generate_json(Ok(missing))
+pub unsafe extern "C" fn automerge_get_last_local_change(backend: *mut Backend) -> isize {
+    handle(-1, || {
+        let change = (*backend).get_last_local_change();
+        Ok((*backend).handle_binaries(change.into_iter().collect()))
+    })
+}
+
+/// # Safety
+/// This must me called with a valid backend pointer
+/// actor must be a valid pointer pointing to a cstring
+#[no_mangle]
+pub unsafe extern "C" fn automerge_ack(
+    backend: *mut Backend,
+    actor: *const c_char,
+    seq: u64,
+) -> isize {
+    handle(-1, || {
+        let actor = from_cstr(actor);
+        let actor = actor
+            .as_ref()
+            .try_into()
+            .map_err(|_| Error::invalid_argument(format!("Invalid actor ID: {}", actor)))?;
+        (*backend).ack(&actor, seq)?;
+        Ok(0)
+    })
 }
 
 /// # Safety
 /// This must me called with a valid backend pointer
-pub unsafe extern "C" fn automerge_error(backend: *mut Backend) -> *const c_char {
-    (*backend)
-        .error
-        .as_ref()
-        .map(|e| e.as_ptr())
-        .unwrap_or_else(|| ptr::null_mut())
+#[no_mangle]
+pub unsafe extern "C" fn automerge_get_missing_deps(backend: *mut Backend) -> isize {
+    handle(-1, || {
+        let missing = (*backend).get_missing_deps();
+        (*backend).generate_json(missing)
+    })
 }
 
 /// # Safety
@@ -327,16 +387,20 @@ pub unsafe extern "C" fn automerge_error(backend: *mut Backend) -> *const c_char
 /// call that generated a json result
 #[no_mangle]
 pub unsafe extern "C" fn automerge_read_json(backend: *mut Backend, buffer: *mut c_char) -> isize {
-    if let Some(text) = &(*backend).text {
-        let len = text.len();
-        buffer.copy_from(text.as_ptr().cast(), len);
-        (*buffer.add(len)) = 0; // null terminate
-        (*backend).text = None;
-        0
-    } else {
-        (*buffer) = 0;
-        -1
-    }
+    handle(-1, || {
+        if let Some(text) = (*backend).text.take() {
+            let len = text.len();
+            buffer.copy_from(text.as_ptr().cast(), len);
+            (*buffer.add(len)) = 0; // null terminate
+            Ok(0)
+        } else {
+            (*buffer) = 0;
+            Err(Error::new(
+                AutomergeErrorCode::NoResult,
+                "No JSON result to read",
+            ))
+        }
+    })
 }
 
 /// # Safety
@@ -346,15 +410,12 @@ pub unsafe extern "C" fn automerge_read_json(backend: *mut Backend, buffer: *mut
 /// required by the previous binary result call
 #[no_mangle]
 pub unsafe extern "C" fn automerge_read_binary(backend: *mut Backend, buffer: *mut u8) -> isize {
-    if let Some(bin) = (*backend).binary.pop() {
-        let len = bin.len();
-        buffer.copy_from(bin.as_ptr(), len);
-        if let Some(next) = (*backend).binary.last() {
-            next.len() as isize
-        } else {
-            0
-        }
-    } else {
-        -1
-    }
+    handle(-1, || {
+        let bin = (*backend)
+            .binary
+            .pop()
+            .ok_or_else(|| Error::new(AutomergeErrorCode::NoResult, "No binary result to read"))?;
+        buffer.copy_from(bin.as_ptr(), bin.len());
+        Ok((*backend).binary.last().map(|next| next.len()).unwrap_or(0) as isize)
+    })
 }
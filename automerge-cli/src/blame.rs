@@ -0,0 +1,193 @@
+use anyhow::Result;
+use automerge_backend::{Backend, Blame};
+use automerge_protocol as amp;
+use serde_json::json;
+
+/// The plain JSON form of `value`, without annotations
+fn value_json(backend: &Backend, value: &amp::Value) -> Result<serde_json::Value> {
+    let (object_id, obj_type) = match value {
+        amp::Value::Primitive(value) => {
+            return Ok(automerge_frontend::Value::Primitive(value.clone()).to_json())
+        }
+        amp::Value::Object {
+            object_id,
+            obj_type,
+        } => (object_id, obj_type),
+    };
+    let entries = backend.entries(object_id)?;
+    Ok(match obj_type {
+        amp::ObjType::Map(_) => serde_json::Value::Object(
+            entries
+                .iter()
+                .map(|(key, value)| Ok((key.to_string(), value_json(backend, value)?)))
+                .collect::<Result<_>>()?,
+        ),
+        amp::ObjType::Sequence(amp::SequenceType::List) => serde_json::Value::Array(
+            entries
+                .iter()
+                .map(|(_, value)| value_json(backend, value))
+                .collect::<Result<_>>()?,
+        ),
+        amp::ObjType::Sequence(amp::SequenceType::Text) => serde_json::Value::String(
+            entries
+                .iter()
+                .filter_map(|(_, value)| match value {
+                    amp::Value::Primitive(amp::ScalarValue::Str(s)) => Some(s.as_str()),
+                    _ => None,
+                })
+                .collect(),
+        ),
+    })
+}
+
+fn blame_entry(blame: &Blame, value: serde_json::Value) -> serde_json::Value {
+    json!({
+        "value": value,
+        "opId": blame.op_id,
+        "actor": blame.actor,
+        "hash": blame.hash,
+        "time": blame.time,
+        "message": blame.message,
+    })
+}
+
+/// The value at `path` with where it came from. Maps and lists under the
+/// winning value are annotated in turn, conflicting values are shown as
+/// plain JSON.
+fn annotate(backend: &Backend, path: &mut Vec<amp::PathElement>) -> Result<serde_json::Value> {
+    let blame = backend.blame(path)?;
+    let (winner, conflicts) = match blame.split_first() {
+        Some(split) => split,
+        None => return Ok(serde_json::Value::Null),
+    };
+    let value = match &winner.value {
+        amp::Value::Object {
+            obj_type: amp::ObjType::Map(_),
+            ..
+        } => serde_json::Value::Object(annotate_children(backend, path, &winner.value)?),
+        amp::Value::Object {
+            obj_type: amp::ObjType::Sequence(amp::SequenceType::List),
+            ..
+        } => serde_json::Value::Array(
+            annotate_children(backend, path, &winner.value)?
+                .into_iter()
+                .map(|(_, value)| value)
+                .collect(),
+        ),
+        value => value_json(backend, value)?,
+    };
+    let mut entry = blame_entry(winner, value);
+    if !conflicts.is_empty() {
+        entry["conflicts"] = conflicts
+            .iter()
+            .map(|conflict| Ok(blame_entry(conflict, value_json(backend, &conflict.value)?)))
+            .collect::<Result<_>>()?;
+    }
+    Ok(entry)
+}
+
+/// Annotate each entry of the map or list `value`, which is found at `path`
+fn annotate_children(
+    backend: &Backend,
+    path: &mut Vec<amp::PathElement>,
+    value: &amp::Value,
+) -> Result<serde_json::Map<String, serde_json::Value>> {
+    let object_id = match value {
+        amp::Value::Object { object_id, .. } => object_id,
+        amp::Value::Primitive(_) => return Ok(serde_json::Map::new()),
+    };
+    let mut result = serde_json::Map::new();
+    for (prop, _) in backend.entries(object_id)? {
+        let key = prop.to_string();
+        path.push(prop);
+        result.insert(key, annotate(backend, path)?);
+        path.pop();
+    }
+    Ok(result)
+}
+
+fn blame_json(input_data: Vec<u8>) -> Result<serde_json::Value> {
+    let mut backend = Backend::init();
+    backend.load_changes(automerge_backend::Change::parse(&input_data)?)?;
+    let root = amp::Value::Object {
+        object_id: amp::ObjectID::Root,
+        obj_type: amp::ObjType::map(),
+    };
+    Ok(serde_json::Value::Object(annotate_children(
+        &backend,
+        &mut Vec::new(),
+        &root,
+    )?))
+}
+
+pub fn blame(
+    mut changes_reader: impl std::io::Read,
+    mut writer: impl std::io::Write,
+) -> Result<()> {
+    let mut input_data = vec![];
+    changes_reader.read_to_end(&mut input_data)?;
+
+    let blamed = blame_json(input_data)?;
+    writeln!(writer, "{}", serde_json::to_string_pretty(&blamed)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use automerge_frontend::{Frontend, LocalChange, Path, Value};
+
+    #[test]
+    fn cli_blame_with_empty_input() {
+        assert_eq!(blame_json(vec![]).unwrap(), json!({}))
+    }
+
+    #[test]
+    fn cli_blame_annotates_nested_values() {
+        let value = Value::from_json(&json!({"birds": {"wrens": 3.0}, "tags": ["a"]}));
+        let (mut frontend, initial_change) = Frontend::new_with_initial_state(value).unwrap();
+        let mut backend = Backend::init();
+        backend.apply_local_change(initial_change).unwrap();
+        let request = frontend
+            .change(Some("more wrens".into()), |doc| {
+                doc.add_change(LocalChange::set(
+                    Path::root().key("birds").key("wrens"),
+                    Value::Primitive(amp::ScalarValue::F64(4.0)),
+                ))
+            })
+            .unwrap()
+            .unwrap();
+        backend.apply_local_change(request).unwrap();
+        let changes = backend.get_changes(&[]);
+        let actor = frontend.actor_id.to_string();
+
+        let result = blame_json(backend.save().unwrap()).unwrap();
+        let birds = &result["birds"];
+        assert_eq!(birds["hash"], json!(changes[0].hash));
+        assert_eq!(birds["actor"], json!(actor));
+        assert_eq!(birds["message"], json!("Initialization"));
+        let wrens = &birds["value"]["wrens"];
+        assert_eq!(wrens["value"], json!(4.0));
+        assert_eq!(wrens["hash"], json!(changes[1].hash));
+        assert_eq!(wrens["message"], json!("more wrens"));
+        assert_eq!(result["tags"]["value"][0]["value"], json!("a"));
+        assert!(wrens.get("conflicts").is_none());
+    }
+
+    #[test]
+    fn cli_blame_shows_conflicts() {
+        let mut backend = Backend::init();
+        for name in &["magpie", "crow"] {
+            let value = Value::from_json(&json!({ "bird": name }));
+            let (_, change) = Frontend::new_with_initial_state(value).unwrap();
+            backend.apply_local_change(change).unwrap();
+        }
+        let result = blame_json(backend.save().unwrap()).unwrap();
+        let bird = &result["bird"];
+        let conflicts = bird["conflicts"].as_array().unwrap();
+        assert_eq!(conflicts.len(), 1);
+        let mut values = vec![bird["value"].clone(), conflicts[0]["value"].clone()];
+        values.sort_by_key(|v| v.to_string());
+        assert_eq!(values, vec![json!("crow"), json!("magpie")]);
+    }
+}
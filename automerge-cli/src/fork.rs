@@ -0,0 +1,151 @@
+use anyhow::{anyhow, Result};
+use automerge_backend::Change;
+use automerge_protocol as amp;
+use std::collections::{HashMap, HashSet};
+
+/// Find the unique change whose hex encoded hash starts with `prefix`
+fn resolve_hash_prefix(changes: &[Change], prefix: &str) -> Result<amp::ChangeHash> {
+    let prefix = prefix.to_lowercase();
+    if prefix.is_empty() || !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(anyhow!("Invalid change hash prefix: {}", prefix));
+    }
+    let mut matches: Vec<amp::ChangeHash> = changes
+        .iter()
+        .map(|c| c.hash)
+        .filter(|h| hex::encode(h.0).starts_with(&prefix))
+        .collect();
+    matches.sort_unstable();
+    matches.dedup();
+    match matches.as_slice() {
+        [] => Err(anyhow!("No change found with hash prefix: {}", prefix)),
+        [hash] => Ok(*hash),
+        _ => Err(anyhow!(
+            "Ambiguous change hash prefix {}, candidates are: {}",
+            prefix,
+            matches
+                .iter()
+                .map(|h| hex::encode(h.0))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
+
+/// Keep only the change with hash `head` and its causal ancestors, preserving
+/// the order in which the changes appeared in the input
+fn changes_up_to(changes: Vec<Change>, head: amp::ChangeHash) -> Result<Vec<Change>> {
+    let by_hash: HashMap<amp::ChangeHash, &Change> = changes.iter().map(|c| (c.hash, c)).collect();
+    let mut ancestors = HashSet::new();
+    let mut stack = vec![head];
+    while let Some(hash) = stack.pop() {
+        if ancestors.insert(hash) {
+            let change = by_hash
+                .get(&hash)
+                .ok_or_else(|| anyhow!("Missing dependency: {}", hex::encode(hash.0)))?;
+            stack.extend(change.deps.iter().cloned());
+        }
+    }
+    Ok(changes
+        .into_iter()
+        .filter(|c| ancestors.contains(&c.hash))
+        .collect())
+}
+
+fn fork_changes(input_data: Vec<u8>, hash_prefix: &str) -> Result<Vec<u8>> {
+    let changes = Change::parse(&input_data)?;
+    let head = resolve_hash_prefix(&changes, hash_prefix)?;
+    let forked: Vec<u8> = changes_up_to(changes, head)?
+        .into_iter()
+        .flat_map(|c| c.bytes)
+        .collect();
+    Ok(forked)
+}
+
+pub fn fork(
+    hash_prefix: &str,
+    mut changes_reader: impl std::io::Read,
+    mut writer: impl std::io::Write,
+) -> Result<()> {
+    let mut input_data = vec![];
+    changes_reader.read_to_end(&mut input_data)?;
+
+    let forked = fork_changes(input_data, hash_prefix)?;
+    writer.write_all(&forked)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use automerge_frontend::{Frontend, InvalidChangeRequest, LocalChange, Path, Value};
+
+    fn history(num_changes: usize) -> (Vec<u8>, Vec<amp::ChangeHash>) {
+        let mut backend = automerge_backend::Backend::init();
+        let mut frontend = Frontend::new();
+        let mut hashes = Vec::new();
+        for i in 0..num_changes {
+            let change = frontend
+                .change::<_, InvalidChangeRequest>(None, |doc| {
+                    doc.add_change(LocalChange::set(
+                        Path::root().key("birds"),
+                        Value::Primitive(amp::ScalarValue::Uint(i as u64)),
+                    ))
+                })
+                .unwrap()
+                .unwrap();
+            let (patch, change) = backend.apply_local_change(change).unwrap();
+            frontend.apply_patch(patch).unwrap();
+            hashes.push(change.hash);
+        }
+        (backend.save().unwrap(), hashes)
+    }
+
+    #[test]
+    fn cli_fork_keeps_ancestors_only() {
+        let (data, hashes) = history(3);
+        let prefix = hex::encode(hashes[1].0)[0..12].to_string();
+        let forked = fork_changes(data, &prefix).unwrap();
+
+        let changes = Change::parse(&forked).unwrap();
+        let forked_hashes: Vec<_> = changes.iter().map(|c| c.hash).collect();
+        assert_eq!(forked_hashes, hashes[0..2].to_vec());
+
+        let backend = automerge_backend::Backend::load(forked).unwrap();
+        let mut frontend = Frontend::new();
+        frontend.apply_patch(backend.get_patch().unwrap()).unwrap();
+        assert_eq!(frontend.state().to_json(), serde_json::json!({"birds": 1}));
+    }
+
+    #[test]
+    fn cli_fork_with_unknown_prefix() {
+        let (data, hashes) = history(20);
+        // 20 changes cannot cover all 256 two digit prefixes
+        let unused = (0..=255u8)
+            .map(|i| hex::encode([i]))
+            .find(|p| !hashes.iter().any(|h| hex::encode(h.0).starts_with(p)))
+            .unwrap();
+        let err = fork_changes(data.clone(), &unused).unwrap_err();
+        assert!(err.to_string().starts_with("No change found"));
+
+        let err = fork_changes(data, "not-hex").unwrap_err();
+        assert!(err.to_string().starts_with("Invalid change hash prefix"));
+    }
+
+    #[test]
+    fn cli_fork_with_ambiguous_prefix() {
+        let (data, hashes) = history(20);
+        // 20 changes must share at least one of the 16 single digit prefixes
+        let shared = (0..16)
+            .map(|i| format!("{:x}", i))
+            .find(|p| {
+                hashes
+                    .iter()
+                    .filter(|h| hex::encode(h.0).starts_with(p))
+                    .count()
+                    > 1
+            })
+            .unwrap();
+        let err = fork_changes(data, &shared).unwrap_err();
+        assert!(err.to_string().starts_with("Ambiguous change hash prefix"));
+    }
+}
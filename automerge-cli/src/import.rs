@@ -1,6 +1,6 @@
 use anyhow::Result;
 use automerge_backend::Backend;
-use automerge_frontend::{Frontend, Value};
+use automerge_frontend::{Frontend, InvalidChangeRequest, Path, Value};
 
 fn initialize_from_json(json_value: &serde_json::Value) -> Result<Vec<u8>> {
     let value: Value = Value::from_json(&json_value);
@@ -12,6 +12,25 @@ fn initialize_from_json(json_value: &serde_json::Value) -> Result<Vec<u8>> {
     Ok(backend.save()?)
 }
 
+/// Apply a single change to the document in `existing_data` which makes its
+/// state match `json_value`, returning the updated document
+fn update_from_json(existing_data: Vec<u8>, json_value: &serde_json::Value) -> Result<Vec<u8>> {
+    let target = Value::from_json(json_value);
+
+    let mut backend = Backend::load(existing_data)?;
+    let mut frontend = Frontend::new();
+    frontend.apply_patch(backend.get_patch()?)?;
+
+    let change = frontend.change::<_, InvalidChangeRequest>(Some("Import".into()), |doc| {
+        doc.update_to(&Path::root(), &target)
+    })?;
+    if let Some(change) = change {
+        backend.apply_local_change(change)?;
+    }
+
+    Ok(backend.save()?)
+}
+
 pub fn import_json(mut reader: impl std::io::Read, mut writer: impl std::io::Write) -> Result<()> {
     let mut buffer = String::new();
     reader.read_to_string(&mut buffer)?;
@@ -21,3 +40,80 @@ pub fn import_json(mut reader: impl std::io::Read, mut writer: impl std::io::Wri
     writer.write_all(&changes_bytes)?;
     Ok(())
 }
+
+pub fn import_json_into(
+    existing_data: Vec<u8>,
+    mut reader: impl std::io::Read,
+    mut writer: impl std::io::Write,
+) -> Result<()> {
+    let mut buffer = String::new();
+    reader.read_to_string(&mut buffer)?;
+
+    let json_value: serde_json::Value = serde_json::from_str(&buffer)?;
+    let changes_bytes = update_from_json(existing_data, &json_value)?;
+    writer.write_all(&changes_bytes)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use automerge_backend::Change;
+    use automerge_protocol as amp;
+    use serde_json::json;
+
+    fn state_json(data: &[u8]) -> serde_json::Value {
+        let backend = Backend::load(data.to_vec()).unwrap();
+        let mut frontend = Frontend::new();
+        frontend.apply_patch(backend.get_patch().unwrap()).unwrap();
+        frontend.state().to_json()
+    }
+
+    fn import_twice(initial: serde_json::Value, updated: serde_json::Value) -> Vec<amp::Op> {
+        let data = initialize_from_json(&initial).unwrap();
+        let data = update_from_json(data, &updated).unwrap();
+        let changes = Change::parse(&data).unwrap();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(state_json(&data), updated);
+        changes[1].decode().operations
+    }
+
+    #[test]
+    fn cli_import_into_updates_changed_keys_only() {
+        let ops = import_twice(
+            json!({"birds": {"wrens": 3.0, "sparrows": 15.0}, "name": "birdwatch"}),
+            json!({"birds": {"wrens": 4.0, "magpies": 1.0}, "name": "birdwatch"}),
+        );
+        // set wrens, set magpies, delete sparrows
+        assert_eq!(ops.len(), 3);
+    }
+
+    #[test]
+    fn cli_import_into_keeps_list_elements() {
+        let ops = import_twice(
+            json!({"birds": ["wren", "sparrow", "magpie"]}),
+            json!({"birds": ["robin", "wren", "magpie", "jay"]}),
+        );
+        // insert robin, delete sparrow, insert jay
+        assert_eq!(ops.len(), 3);
+        assert_eq!(ops.iter().filter(|op| op.insert).count(), 2);
+    }
+
+    #[test]
+    fn cli_import_into_updates_list_elements_in_place() {
+        let ops = import_twice(
+            json!({"birds": [{"name": "wren", "count": 1.0}, {"name": "jay", "count": 2.0}]}),
+            json!({"birds": [{"name": "wren", "count": 2.0}, {"name": "jay", "count": 2.0}]}),
+        );
+        assert_eq!(ops.len(), 1);
+        assert_eq!(ops[0].key, "count".into());
+    }
+
+    #[test]
+    fn cli_import_into_unchanged_document() {
+        let initial = json!({"birds": ["wren"]});
+        let data = initialize_from_json(&initial).unwrap();
+        let updated = update_from_json(data.clone(), &initial).unwrap();
+        assert_eq!(data, updated);
+    }
+}
@@ -0,0 +1,146 @@
+use anyhow::Result;
+use automerge_backend::ChangeRef;
+use automerge_protocol as amp;
+use serde_json::json;
+
+fn change_summary(offset: usize, change: &ChangeRef) -> serde_json::Value {
+    let columns: Vec<serde_json::Value> = change
+        .column_ranges()
+        .into_iter()
+        .map(|(id, range)| {
+            json!({
+                "id": id,
+                "range": [offset + range.start, offset + range.end],
+            })
+        })
+        .collect();
+    json!({
+        "offset": offset,
+        "length": change.bytes.len(),
+        "hash": change.hash,
+        "checksum": {
+            "header": hex::encode(change.checksum()),
+            "computed": hex::encode(&change.hash.0[0..4]),
+            "valid": change.checksum_is_valid(),
+        },
+        "columns": columns,
+        "change": amp::UncompressedChange::from(change),
+    })
+}
+
+fn inspect_changes(input_data: &[u8], raw: bool) -> Result<serde_json::Value> {
+    let mut result = Vec::new();
+    // Raw output reports a checksum mismatch alongside the rest of the chunk
+    // rather than in place of it
+    let chunks = if raw {
+        ChangeRef::parse_chunks_unverified(input_data)
+    } else {
+        ChangeRef::parse_chunks(input_data)
+    };
+    for (offset, chunk) in chunks {
+        let entry = match chunk {
+            Ok(change) if raw => change_summary(offset, &change),
+            Ok(change) => serde_json::to_value(change.decode())?,
+            Err(e) => json!({
+                "offset": offset,
+                "error": e.to_string(),
+            }),
+        };
+        result.push(entry);
+    }
+    Ok(serde_json::Value::Array(result))
+}
+
+pub fn inspect(
+    raw: bool,
+    mut changes_reader: impl std::io::Read,
+    mut writer: impl std::io::Write,
+) -> Result<()> {
+    let mut input_data = vec![];
+    changes_reader.read_to_end(&mut input_data)?;
+
+    let changes = inspect_changes(&input_data, raw)?;
+    writeln!(writer, "{}", serde_json::to_string_pretty(&changes)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use automerge_backend::Change;
+
+    fn initial_change() -> Change {
+        let value =
+            automerge_frontend::Value::from_json(&serde_json::json!({"birds": {"wrens": 3.0}}));
+        let (_, change) = automerge_frontend::Frontend::new_with_initial_state(value).unwrap();
+        change.into()
+    }
+
+    #[test]
+    fn cli_inspect_decodes_changes() {
+        let change = initial_change();
+        let result = inspect_changes(&change.bytes, false).unwrap();
+        assert_eq!(
+            result,
+            json!([serde_json::to_value(change.decode()).unwrap()])
+        );
+    }
+
+    #[test]
+    fn cli_inspect_raw_shows_layout() {
+        let change = initial_change();
+        let mut data = change.bytes.clone();
+        data.extend(&change.bytes);
+        let result = inspect_changes(&data, true).unwrap();
+        let second = &result[1];
+        assert_eq!(second["offset"], json!(change.bytes.len()));
+        assert_eq!(second["checksum"]["valid"], json!(true));
+        let (first_col, first_range) = change.column_ranges()[0].clone();
+        assert_eq!(second["columns"][0]["id"], json!(first_col));
+        assert_eq!(
+            second["columns"][0]["range"],
+            json!([
+                change.bytes.len() + first_range.start,
+                change.bytes.len() + first_range.end
+            ])
+        );
+    }
+
+    #[test]
+    fn cli_inspect_raw_shows_checksum_mismatch() {
+        let change = initial_change();
+        let mut data = change.bytes.clone();
+        data.extend(&change.bytes);
+        let second = change.bytes.len();
+        data[second + 4] ^= 0xff;
+
+        let result = inspect_changes(&data, true).unwrap();
+        assert_eq!(result[0]["checksum"]["valid"], json!(true));
+        assert_eq!(result[1]["offset"], json!(second));
+        assert_eq!(result[1]["checksum"]["valid"], json!(false));
+        assert_eq!(
+            result[1]["checksum"]["header"],
+            json!(hex::encode(&data[second + 4..second + 8]))
+        );
+        assert_eq!(
+            result[1]["checksum"]["computed"],
+            json!(hex::encode(&change.hash.0[0..4]))
+        );
+
+        let result = inspect_changes(&data, false).unwrap();
+        assert_eq!(result[1]["offset"], json!(second));
+        assert!(result[1]["error"].is_string());
+    }
+
+    #[test]
+    fn cli_inspect_reports_malformed_chunks() {
+        let change = initial_change();
+        let mut data = change.bytes.clone();
+        data[0] = 0;
+        data.extend(&change.bytes);
+        let result = inspect_changes(&data, false).unwrap();
+        assert_eq!(result[0]["offset"], json!(0));
+        assert!(result[0]["error"].is_string());
+        assert_eq!(result[1], serde_json::to_value(change.decode()).unwrap());
+    }
+}
@@ -4,8 +4,11 @@ use std::fs::File;
 use std::path::PathBuf;
 use std::str::FromStr;
 
+mod blame;
 mod export;
+mod fork;
 mod import;
+mod inspect;
 
 #[derive(Debug, Clap)]
 #[clap(about = "Automerge CLI")]
@@ -56,6 +59,46 @@ enum Command {
         /// Path to write Automerge changes to
         #[clap(parse(from_os_str), long("out"), short('o'))]
         changes_file: Option<PathBuf>,
+
+        /// Existing document to update with a single change instead of
+        /// creating a new document. The updated document is written back to
+        /// this path unless `--out` is given
+        #[clap(parse(from_os_str), long("into"))]
+        into_file: Option<PathBuf>,
+    },
+
+    /// Print the decoded contents of the changes in an Automerge document as JSON
+    Inspect {
+        /// Show the byte layout and checksum of each change, including changes
+        /// whose checksum does not match
+        #[clap(long)]
+        raw: bool,
+
+        /// Path that contains Automerge changes
+        #[clap(parse(from_os_str))]
+        changes_file: Option<PathBuf>,
+    },
+
+    /// Cut a document back to the state at a given change
+    Fork {
+        /// Hash, or unique prefix of the hash, of the change to fork at
+        hash: String,
+
+        /// Path that contains Automerge changes
+        #[clap(parse(from_os_str))]
+        changes_file: Option<PathBuf>,
+
+        /// Path to write the forked Automerge changes to
+        #[clap(parse(from_os_str), long("out"), short('o'))]
+        out_file: Option<PathBuf>,
+    },
+
+    /// Print the document as JSON with the op, actor, change hash, time and
+    /// message which produced each value, along with any conflicting values
+    Blame {
+        /// Path that contains Automerge changes
+        #[clap(parse(from_os_str))]
+        changes_file: Option<PathBuf>,
     },
 }
 
@@ -103,13 +146,41 @@ fn main() -> Result<()> {
             format,
             input_file,
             changes_file,
+            into_file,
         } => match format {
             ExportFormat::JSON => {
-                let mut out_buffer = create_file_or_stdout(changes_file)?;
-                let mut in_buffer = open_file_or_stdin(input_file)?;
-                import::import_json(&mut in_buffer, &mut out_buffer)
+                if let Some(into_file) = into_file {
+                    let existing_data = std::fs::read(&into_file)?;
+                    let mut in_buffer = open_file_or_stdin(input_file)?;
+                    let out_buffer = File::create(changes_file.unwrap_or(into_file))?;
+                    import::import_json_into(existing_data, &mut in_buffer, out_buffer)
+                } else {
+                    let mut out_buffer = create_file_or_stdout(changes_file)?;
+                    let mut in_buffer = open_file_or_stdin(input_file)?;
+                    import::import_json(&mut in_buffer, &mut out_buffer)
+                }
             }
             ExportFormat::TOML => unimplemented!(),
         },
+
+        Command::Inspect { raw, changes_file } => {
+            let mut in_buffer = open_file_or_stdin(changes_file)?;
+            inspect::inspect(raw, &mut in_buffer, &mut std::io::stdout())
+        }
+
+        Command::Fork {
+            hash,
+            changes_file,
+            out_file,
+        } => {
+            let mut out_buffer = create_file_or_stdout(out_file)?;
+            let mut in_buffer = open_file_or_stdin(changes_file)?;
+            fork::fork(&hash, &mut in_buffer, &mut out_buffer)
+        }
+
+        Command::Blame { changes_file } => {
+            let mut in_buffer = open_file_or_stdin(changes_file)?;
+            blame::blame(&mut in_buffer, &mut std::io::stdout())
+        }
     }
 }
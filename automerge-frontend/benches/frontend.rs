@@ -0,0 +1,53 @@
+use automerge_bench_fixtures as fixtures;
+use automerge_frontend::Frontend;
+use automerge_protocol as amp;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+
+fn apply_initial_patch(c: &mut Criterion) {
+    let workloads = vec![
+        ("typing 2000 keystrokes", fixtures::typing_trace(2_000)),
+        ("map of 10000 keys", fixtures::large_map(10_000, 100)),
+        ("20 concurrent actors", fixtures::concurrent_actors(20, 20)),
+    ];
+    let mut group = c.benchmark_group("Frontend::apply_patch initial");
+    group.sample_size(10);
+    for (name, changes) in workloads {
+        let patch = fixtures::backend_with(&changes).get_patch().unwrap();
+        group.bench_function(name, |b| {
+            b.iter_batched(
+                || patch.clone(),
+                |patch| Frontend::new().apply_patch(patch).unwrap(),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn apply_remote_typing(c: &mut Criterion) {
+    // The patches a frontend receives while another actor types
+    let mut peer = fixtures::Peer::new();
+    let patches: Vec<amp::Patch> = fixtures::typing_trace(1_000)
+        .into_iter()
+        .map(|change| peer.backend.apply_changes(vec![change]).unwrap())
+        .collect();
+    let mut group = c.benchmark_group("Frontend::apply_patch incremental");
+    group.sample_size(10);
+    group.bench_function("1000 remote keystrokes", |b| {
+        b.iter_batched(
+            || patches.clone(),
+            |patches| {
+                let mut frontend = Frontend::new();
+                for patch in patches {
+                    frontend.apply_patch(patch).unwrap();
+                }
+                frontend
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, apply_initial_patch, apply_remote_typing);
+criterion_main!(benches);
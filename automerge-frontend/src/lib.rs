@@ -6,6 +6,7 @@ mod error;
 mod mutation;
 mod path;
 mod state_tree;
+mod tree_diff;
 mod value;
 
 pub use error::{
@@ -308,6 +309,7 @@ impl Frontend {
                     deps: Vec::new(),
                     operations: init_ops,
                     extra_bytes: Vec::new(),
+                    unknown_columns: Vec::new(),
                 };
                 // Unwrap here is fine because it should be impossible to
                 // cause an error applying a local change from a `Value`. If
@@ -336,8 +338,9 @@ impl Frontend {
         F: FnOnce(&mut dyn MutableDocument) -> Result<(), E>,
     {
         let start_op = self.state.as_ref().unwrap().max_op() + 1;
-        // TODO this leaves the `state` as `None` if there's an error, it shouldn't
-        let change_result = self.state.take().unwrap().optimistically_apply_change(
+        // The state trees are persistent data structures so cloning them is
+        // cheap, and it means that the state is left untouched on error
+        let change_result = self.state.clone().unwrap().optimistically_apply_change(
             &self.actor_id,
             change_closure,
             self.seq + 1,
@@ -355,6 +358,7 @@ impl Frontend {
                 deps: change_result.deps,
                 operations: ops,
                 extra_bytes: Vec::new(),
+                unknown_columns: Vec::new(),
             };
             Ok(Some(change))
         } else {
@@ -363,10 +367,9 @@ impl Frontend {
     }
 
     pub fn apply_patch(&mut self, patch: Patch) -> Result<(), InvalidPatch> {
-        // TODO this leaves the `state` as `None` if there's an error, it shouldn't
         let (new_cached_value, new_state) = self
             .state
-            .take()
+            .clone()
             .unwrap()
             .apply_remote_patch(&self.actor_id, &patch)?;
         self.state = Some(new_state);
@@ -392,6 +395,16 @@ impl Frontend {
             .unwrap_or_default()
     }
 
+    /// The seq of the latest local change whose patch has been applied, every
+    /// earlier local change has been applied too. When the backend runs
+    /// elsewhere pass this to `Backend::ack` after each `apply_patch`.
+    pub fn acked_seq(&self) -> u64 {
+        match self.in_flight_requests().first() {
+            Some(seq) => seq - 1,
+            None => self.seq,
+        }
+    }
+
     /// Gets the set of values for `path`, returns None if the path does not
     /// exist
     pub fn get_conflicts(&self, path: &Path) -> Option<HashMap<OpID, Value>> {
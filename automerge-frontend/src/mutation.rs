@@ -7,12 +7,24 @@ use automerge_protocol as amp;
 pub trait MutableDocument {
     fn value_at_path(&self, path: &Path) -> Option<Value>;
     fn add_change(&mut self, change: LocalChange) -> Result<(), InvalidChangeRequest>;
+
+    /// Make the value at `path` equal to `value` using as few changes as
+    /// possible. Maps are updated key by key, lists and text are diffed so
+    /// that elements which are present in both are kept, and counters are
+    /// incremented rather than overwritten. Subtrees which are unchanged
+    /// generate no changes at all.
+    fn update_to(&mut self, path: &Path, value: &Value) -> Result<(), InvalidChangeRequest> {
+        match self.value_at_path(path) {
+            Some(current) => crate::tree_diff::update_value(self, path.clone(), &current, value),
+            None => self.add_change(LocalChange::set(path.clone(), value.clone())),
+        }
+    }
 }
 
 pub(crate) enum LocalOperation {
     Set(Value),
     Delete,
-    Increment(u32),
+    Increment(i64),
     Insert(Value),
 }
 
@@ -47,7 +59,7 @@ impl LocalChange {
     }
 
     /// Increment the counter at path by a (possibly negative) amount `by`
-    pub fn increment_by(path: Path, by: u32) -> LocalChange {
+    pub fn increment_by(path: Path, by: i64) -> LocalChange {
         LocalChange {
             path,
             operation: LocalOperation::Increment(by),
@@ -272,7 +284,7 @@ impl MutableDocument for MutationTracker {
                     if let Some(pr) = self.state.resolve_path(&change.path) {
                         match pr {
                             ResolvedPath::Counter(counter_target) => {
-                                self.apply_state_change(counter_target.increment(*by as i64));
+                                self.apply_state_change(counter_target.increment(*by));
                                 Ok(())
                             }
                             _ => Err(InvalidChangeRequest::IncrementForNonCounterObject {
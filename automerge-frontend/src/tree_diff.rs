@@ -0,0 +1,207 @@
+use crate::{InvalidChangeRequest, LocalChange, MutableDocument, Path, Value};
+use automerge_protocol as amp;
+
+/// A single step in the edit script which turns one sequence into another
+#[derive(Debug, PartialEq)]
+pub(crate) enum SeqEdit {
+    Keep,
+    Delete,
+    Insert(usize),
+}
+
+/// Compute an edit script turning `current` into `target` using the longest
+/// common subsequence of the two, so that elements which are present in both
+/// are kept rather than deleted and reinserted
+pub(crate) fn lcs_edits<T: PartialEq>(current: &[T], target: &[T]) -> Vec<SeqEdit> {
+    let prefix = current
+        .iter()
+        .zip(target.iter())
+        .take_while(|(c, t)| c == t)
+        .count();
+    let suffix = current[prefix..]
+        .iter()
+        .rev()
+        .zip(target[prefix..].iter().rev())
+        .take_while(|(c, t)| c == t)
+        .count();
+    let old = &current[prefix..current.len() - suffix];
+    let new = &target[prefix..target.len() - suffix];
+
+    // lengths[i][j] is the length of the LCS of old[i..] and new[j..]
+    let width = new.len() + 1;
+    let mut lengths = vec![0; (old.len() + 1) * width];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i * width + j] = if old[i] == new[j] {
+                lengths[(i + 1) * width + j + 1] + 1
+            } else {
+                lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut edits: Vec<SeqEdit> = (0..prefix).map(|_| SeqEdit::Keep).collect();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            edits.push(SeqEdit::Keep);
+            i += 1;
+            j += 1;
+        } else if j == new.len()
+            || (i < old.len() && lengths[(i + 1) * width + j] >= lengths[i * width + j + 1])
+        {
+            edits.push(SeqEdit::Delete);
+            i += 1;
+        } else {
+            edits.push(SeqEdit::Insert(prefix + j));
+            j += 1;
+        }
+    }
+    edits.extend((0..suffix).map(|_| SeqEdit::Keep));
+    edits
+}
+
+fn update_sequence<D: MutableDocument + ?Sized>(
+    doc: &mut D,
+    path: &Path,
+    current: &[Value],
+    target: &[Value],
+) -> Result<(), InvalidChangeRequest> {
+    let edits = lcs_edits(current, target);
+    let mut current_index = 0;
+    let mut index: u32 = 0;
+    let mut edits = edits.iter().peekable();
+    while let Some(edit) = edits.next() {
+        if let SeqEdit::Keep = edit {
+            current_index += 1;
+            index += 1;
+            continue;
+        }
+        // Gather the run of deletions and insertions between two kept
+        // elements. Pairs of deleted and inserted elements are updated in
+        // place so that a modified element keeps its identity.
+        let mut deleted = Vec::new();
+        let mut inserted = Vec::new();
+        let mut next = Some(edit);
+        while let Some(edit) = next {
+            match edit {
+                SeqEdit::Delete => {
+                    deleted.push(&current[current_index]);
+                    current_index += 1;
+                }
+                SeqEdit::Insert(target_index) => inserted.push(&target[*target_index]),
+                SeqEdit::Keep => unreachable!(),
+            }
+            next = edits.next_if(|e| **e != SeqEdit::Keep);
+        }
+        for (old, new) in deleted.iter().zip(inserted.iter()) {
+            update_value(doc, path.clone().index(index), old, new)?;
+            index += 1;
+        }
+        for _ in inserted.len()..deleted.len() {
+            doc.add_change(LocalChange::delete(path.clone().index(index)))?;
+        }
+        for new in inserted.iter().skip(deleted.len()) {
+            doc.add_change(LocalChange::insert(
+                path.clone().index(index),
+                (*new).clone(),
+            ))?;
+            index += 1;
+        }
+    }
+    Ok(())
+}
+
+/// Generate the changes required to turn the `current` value at `path` into
+/// the `target` value
+pub(crate) fn update_value<D: MutableDocument + ?Sized>(
+    doc: &mut D,
+    path: Path,
+    current: &Value,
+    target: &Value,
+) -> Result<(), InvalidChangeRequest> {
+    match (current, target) {
+        _ if current == target => Ok(()),
+        (Value::Map(current_kvs, current_type), Value::Map(target_kvs, target_type))
+            if current_type == target_type =>
+        {
+            let mut removed: Vec<_> = current_kvs
+                .keys()
+                .filter(|k| !target_kvs.contains_key(*k))
+                .collect();
+            removed.sort();
+            for key in removed {
+                doc.add_change(LocalChange::delete(path.clone().key(key.as_str())))?;
+            }
+            let mut keys: Vec<_> = target_kvs.keys().collect();
+            keys.sort();
+            for key in keys {
+                let target_value = &target_kvs[key];
+                match current_kvs.get(key) {
+                    Some(current_value) => update_value(
+                        doc,
+                        path.clone().key(key.as_str()),
+                        current_value,
+                        target_value,
+                    )?,
+                    None => doc.add_change(LocalChange::set(
+                        path.clone().key(key.as_str()),
+                        target_value.clone(),
+                    ))?,
+                }
+            }
+            Ok(())
+        }
+        (
+            Value::Primitive(amp::ScalarValue::Counter(current_count)),
+            Value::Primitive(amp::ScalarValue::Counter(target_count)),
+        ) => {
+            // Counters can only be incremented, so counters too far apart
+            // for the difference to fit in an `i64` are an error rather than
+            // an overwrite
+            let by = target_count.checked_sub(*current_count).ok_or_else(|| {
+                InvalidChangeRequest::CannotOverwriteCounter { path: path.clone() }
+            })?;
+            doc.add_change(LocalChange::increment_by(path, by))
+        }
+        (Value::Sequence(current_elems), Value::Sequence(target_elems)) => {
+            update_sequence(doc, &path, current_elems, target_elems)
+        }
+        (Value::Text(current_chars), Value::Text(target_chars)) => {
+            let to_values = |chars: &[char]| -> Vec<Value> {
+                chars
+                    .iter()
+                    .map(|c| Value::from(c.to_string().as_str()))
+                    .collect()
+            };
+            update_sequence(
+                doc,
+                &path,
+                &to_values(current_chars),
+                &to_values(target_chars),
+            )
+        }
+        _ => doc.add_change(LocalChange::set(path, target.clone())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lcs_edit_script() {
+        let edits = lcs_edits(&['a', 'b', 'c', 'd'], &['a', 'x', 'c', 'd', 'e']);
+        assert_eq!(
+            edits,
+            vec![
+                SeqEdit::Keep,
+                SeqEdit::Delete,
+                SeqEdit::Insert(1),
+                SeqEdit::Keep,
+                SeqEdit::Keep,
+                SeqEdit::Insert(4),
+            ]
+        );
+    }
+}
@@ -67,6 +67,7 @@ fn use_version_and_sequence_number_from_backend() {
             pred: Vec::new(),
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     };
 
     assert_eq!(req, expected_change_request);
@@ -507,6 +508,7 @@ fn allow_interleaving_of_patches_and_changes() {
                 pred: Vec::new(),
             }],
             extra_bytes: Vec::new(),
+            unknown_columns: Vec::new(),
         }
     );
 
@@ -527,6 +529,7 @@ fn allow_interleaving_of_patches_and_changes() {
                 pred: vec![doc.actor_id.op_id_at(1)],
             }],
             extra_bytes: Vec::new(),
+            unknown_columns: Vec::new(),
         }
     );
 
@@ -562,6 +565,7 @@ fn allow_interleaving_of_patches_and_changes() {
                 pred: vec![doc.actor_id.op_id_at(2)],
             }],
             extra_bytes: Vec::new(),
+            unknown_columns: Vec::new(),
         }
     );
 }
@@ -655,6 +659,7 @@ fn test_deps_are_filled_in_if_frontend_does_not_have_latest_patch() {
             pred: vec![doc.actor_id.op_id_at(1)],
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     };
     assert_eq!(change2, expected_change2);
 
@@ -673,6 +678,7 @@ fn test_deps_are_filled_in_if_frontend_does_not_have_latest_patch() {
             pred: vec![doc2.actor_id.op_id_at(2)],
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     };
     assert_eq!(change3, expected_change3);
 
@@ -713,6 +719,7 @@ fn test_deps_are_filled_in_if_frontend_does_not_have_latest_patch() {
             pred: vec![doc2.actor_id.op_id_at(3)],
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     };
     assert_eq!(change4, expected_change4);
 }
@@ -72,6 +72,7 @@ fn test_set_root_object_properties() {
             pred: Vec::new(),
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     };
     assert_eq!(change_request, Some(expected_change));
 }
@@ -125,6 +126,7 @@ fn it_should_create_nested_maps() {
             },
         ],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     };
     assert_eq!(change_request, expected_change);
 }
@@ -189,6 +191,7 @@ fn apply_updates_inside_nested_maps() {
             pred: Vec::new(),
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     };
 
     assert_eq!(req2, expected_change_request);
@@ -245,6 +248,7 @@ fn delete_keys_in_a_map() {
             pred: vec![doc.actor_id.op_id_at(1)],
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     };
 
     assert_eq!(req2, expected_change_request);
@@ -308,6 +312,7 @@ fn create_lists() {
             },
         ],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     };
 
     assert_eq!(req1, expected_change_request);
@@ -362,6 +367,7 @@ fn apply_updates_inside_lists() {
             pred: vec![doc.actor_id.op_id_at(2)],
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     };
 
     assert_eq!(req2, expected_change_request);
@@ -413,6 +419,7 @@ fn delete_list_elements() {
             pred: vec![doc.actor_id.op_id_at(2)],
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     };
 
     assert_eq!(req2, expected_change_request);
@@ -477,6 +484,7 @@ fn handle_counters_inside_maps() {
             pred: Vec::new(),
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     };
     assert_eq!(req1, expected_change_request_1);
 
@@ -495,6 +503,7 @@ fn handle_counters_inside_maps() {
             pred: vec![doc.actor_id.op_id_at(1)],
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     };
     assert_eq!(req2, expected_change_request_2);
 }
@@ -572,6 +581,7 @@ fn handle_counters_inside_lists() {
             },
         ],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     };
     assert_eq!(req1, expected_change_request_1);
 
@@ -590,6 +600,7 @@ fn handle_counters_inside_lists() {
             pred: vec![doc.actor_id.op_id_at(2)],
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     };
     assert_eq!(req2, expected_change_request_2);
 }
@@ -664,6 +675,7 @@ fn test_sets_characters_in_text() {
             pred: vec![doc.actor_id.op_id_at(3)],
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     };
     assert_eq!(request, expected_change_request);
 
@@ -718,6 +730,7 @@ fn test_inserts_characters_in_text() {
             pred: Vec::new(),
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     };
     assert_eq!(request, expected_change_request);
 
@@ -772,6 +785,7 @@ fn test_inserts_characters_at_start_of_text() {
             pred: Vec::new(),
         }],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     };
     assert_eq!(request, expected_change_request);
 
@@ -839,6 +853,7 @@ fn test_inserts_at_end_of_lists() {
             },
         ],
         extra_bytes: Vec::new(),
+        unknown_columns: Vec::new(),
     };
     assert_eq!(request, expected_change_request);
 
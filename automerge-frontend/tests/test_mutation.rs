@@ -40,3 +40,25 @@ fn test_delete_index_in_mutation() {
         })
         .unwrap();
 }
+
+#[test]
+fn test_failed_mutation_leaves_frontend_usable() {
+    let mut frontend = Frontend::new();
+    let result = frontend.change::<_, InvalidChangeRequest>(None, |doc| {
+        doc.add_change(LocalChange::delete(Path::root().key("missing").index(0)))
+    });
+    assert!(result.is_err());
+
+    frontend
+        .change::<_, InvalidChangeRequest>(None, |doc| {
+            doc.add_change(LocalChange::set(
+                Path::root().key("bird"),
+                Value::Primitive("magpie".into()),
+            ))
+        })
+        .unwrap();
+    assert_eq!(
+        frontend.state().to_json(),
+        serde_json::json!({"bird": "magpie"})
+    );
+}
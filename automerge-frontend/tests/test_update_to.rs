@@ -0,0 +1,122 @@
+use automerge_frontend::{Frontend, InvalidChangeRequest, Path, Value};
+use automerge_protocol as amp;
+use maplit::hashmap;
+
+fn frontend_with(value: Value) -> Frontend {
+    let (frontend, _) = Frontend::new_with_initial_state(value).unwrap();
+    frontend
+}
+
+fn update_ops(frontend: &mut Frontend, path: Path, target: &Value) -> Vec<amp::Op> {
+    frontend
+        .change::<_, InvalidChangeRequest>(None, |doc| doc.update_to(&path, target))
+        .unwrap()
+        .map(|change| change.operations)
+        .unwrap_or_default()
+}
+
+#[test]
+fn test_update_to_only_changes_modified_keys() {
+    let mut frontend = frontend_with(Value::from_json(&serde_json::json!({
+        "birds": {"wrens": 3.0, "sparrows": 15.0},
+        "name": "birdwatch",
+    })));
+    let target = Value::from_json(&serde_json::json!({
+        "birds": {"wrens": 4.0, "magpies": 1.0},
+        "name": "birdwatch",
+    }));
+    let ops = update_ops(&mut frontend, Path::root(), &target);
+    // delete sparrows, set magpies, set wrens
+    assert_eq!(ops.len(), 3);
+    assert_eq!(frontend.state(), &target);
+}
+
+#[test]
+fn test_update_to_unchanged_value_makes_no_change() {
+    let value = Value::from_json(&serde_json::json!({"birds": ["wren", {"name": "jay"}]}));
+    let mut frontend = frontend_with(value.clone());
+    let change = frontend
+        .change::<_, InvalidChangeRequest>(None, |doc| doc.update_to(&Path::root(), &value))
+        .unwrap();
+    assert!(change.is_none());
+}
+
+#[test]
+fn test_update_to_keeps_list_elements() {
+    let mut frontend = frontend_with(Value::from_json(&serde_json::json!({
+        "birds": ["wren", "sparrow", "magpie"],
+    })));
+    let target = Value::from_json(&serde_json::json!(["robin", "wren", "magpie", "jay"]));
+    let ops = update_ops(&mut frontend, Path::root().key("birds"), &target);
+    // insert robin, delete sparrow, insert jay
+    assert_eq!(ops.len(), 3);
+    assert_eq!(ops.iter().filter(|op| op.insert).count(), 2);
+    assert_eq!(frontend.get_value(&Path::root().key("birds")), Some(target));
+}
+
+#[test]
+fn test_update_to_diffs_text() {
+    let mut frontend = frontend_with(Value::Map(
+        hashmap! {"text".to_string() => Value::Text("magpie".chars().collect())},
+        amp::MapType::Map,
+    ));
+    let target = Value::Text("maple".chars().collect());
+    let ops = update_ops(&mut frontend, Path::root().key("text"), &target);
+    // "magpie" -> "maple": delete g, overwrite i with l
+    assert_eq!(ops.len(), 2);
+    assert_eq!(frontend.get_value(&Path::root().key("text")), Some(target));
+}
+
+#[test]
+fn test_update_to_increments_counters() {
+    let mut frontend = frontend_with(Value::Map(
+        hashmap! {"count".to_string() => Value::Primitive(amp::ScalarValue::Counter(3))},
+        amp::MapType::Map,
+    ));
+    let target = Value::Primitive(amp::ScalarValue::Counter(5));
+    let ops = update_ops(&mut frontend, Path::root().key("count"), &target);
+    assert_eq!(ops.len(), 1);
+    assert_eq!(ops[0].action, amp::OpType::Inc(2));
+}
+
+#[test]
+fn test_update_to_decrements_counters() {
+    let mut frontend = frontend_with(Value::Map(
+        hashmap! {"count".to_string() => Value::Primitive(amp::ScalarValue::Counter(3))},
+        amp::MapType::Map,
+    ));
+    let target = Value::Primitive(amp::ScalarValue::Counter(-4));
+    let ops = update_ops(&mut frontend, Path::root().key("count"), &target);
+    assert_eq!(ops.len(), 1);
+    assert_eq!(ops[0].action, amp::OpType::Inc(-7));
+    assert_eq!(frontend.get_value(&Path::root().key("count")), Some(target));
+}
+
+#[test]
+fn test_update_to_counters_far_apart_is_an_error() {
+    let mut frontend = frontend_with(Value::Map(
+        hashmap! {"count".to_string() => Value::Primitive(amp::ScalarValue::Counter(i64::MIN))},
+        amp::MapType::Map,
+    ));
+    let result = frontend.change::<_, InvalidChangeRequest>(None, |doc| {
+        doc.update_to(
+            &Path::root().key("count"),
+            &Value::Primitive(amp::ScalarValue::Counter(1)),
+        )
+    });
+    assert_eq!(
+        result,
+        Err(InvalidChangeRequest::CannotOverwriteCounter {
+            path: Path::root().key("count")
+        })
+    );
+}
+
+#[test]
+fn test_update_to_missing_path_sets_value() {
+    let mut frontend = Frontend::new();
+    let target = Value::from_json(&serde_json::json!({"name": "wren"}));
+    let ops = update_ops(&mut frontend, Path::root().key("bird"), &target);
+    assert_eq!(ops.len(), 2);
+    assert_eq!(frontend.get_value(&Path::root().key("bird")), Some(target));
+}
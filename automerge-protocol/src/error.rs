@@ -29,3 +29,23 @@ pub struct InvalidScalarValue {
     pub unexpected: String,
     pub expected: String,
 }
+
+#[derive(Error, Debug, PartialEq)]
+pub enum InvalidPatchEncoding {
+    #[error("Unknown patch encoding version: {0}")]
+    UnknownVersion(u8),
+    #[error("Unexpected end of encoded patch")]
+    UnexpectedEnd,
+    #[error("{0} unexpected bytes after the end of the encoded patch")]
+    TrailingBytes(usize),
+    #[error("Invalid tag in encoded patch: {tag}")]
+    InvalidTag { tag: u8 },
+    #[error("Actor index {0} is out of range")]
+    InvalidActorIndex(usize),
+    #[error("Object type does not match the type of diff")]
+    MismatchedObjType,
+    #[error("Integer overflow in encoded patch")]
+    Overflow,
+    #[error("Invalid UTF-8 string in encoded patch")]
+    InvalidUtf8,
+}
@@ -1,6 +1,8 @@
 pub mod error;
+mod patch_encoding;
 mod serde_impls;
 mod utility_impls;
+pub use patch_encoding::{decode_diff, decode_patch, encode_diff, encode_patch};
 use std::convert::TryFrom;
 
 use serde::{ser::SerializeMap, Deserialize, Serialize, Serializer};
@@ -153,6 +155,14 @@ impl Key {
     }
 }
 
+/// A step along a path from the root of a document, a key in a map or an
+/// index in a list or text object
+#[derive(PartialEq, Eq, Debug, Hash, Clone)]
+pub enum PathElement {
+    Key(String),
+    Index(u32),
+}
+
 #[derive(Deserialize, Serialize, PartialEq, Debug, Clone, Copy)]
 pub enum DataType {
     #[serde(rename = "counter")]
@@ -254,6 +264,16 @@ pub enum OpType {
     Del,
     Inc(i64),
     Set(ScalarValue),
+    /// An op written by a newer implementation which this one does not
+    /// understand, either because the action code is unknown or because the
+    /// value is of an unknown type. The action, value type and the raw bytes
+    /// of the value are kept as they were found so the op can be encoded again
+    /// unchanged.
+    Unknown {
+        action: u64,
+        value_type: u8,
+        value: Vec<u8>,
+    },
 }
 
 #[derive(PartialEq, Debug, Clone)]
@@ -350,6 +370,17 @@ pub struct ObjDiff {
     pub obj_type: ObjType,
 }
 
+/// A value read directly from a backend, either a primitive or a reference
+/// to a nested object whose contents can be read in turn using its ID
+#[derive(Debug, PartialEq, Clone)]
+pub enum Value {
+    Primitive(ScalarValue),
+    Object {
+        object_id: ObjectID,
+        obj_type: ObjType,
+    },
+}
+
 #[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
 #[serde(rename_all = "camelCase", tag = "action")]
 pub enum DiffEdit {
@@ -395,6 +426,22 @@ pub struct UncompressedChange {
     pub deps: Vec<ChangeHash>,
     #[serde(skip_serializing_if = "Vec::is_empty", default = "Default::default")]
     pub extra_bytes: Vec<u8>,
+    #[serde(
+        rename = "unknownColumns",
+        skip_serializing_if = "Vec::is_empty",
+        default = "Default::default"
+    )]
+    pub unknown_columns: Vec<UnknownColumn>,
+}
+
+/// An op column of a change which this implementation does not know how to
+/// interpret. It is kept exactly as it was found, so it is only meaningful
+/// alongside the ops it was decoded with.
+#[derive(Deserialize, Serialize, PartialEq, Debug, Clone)]
+pub struct UnknownColumn {
+    /// The column ID, including the flag which marks compressed columns
+    pub id: u32,
+    pub data: Vec<u8>,
 }
 
 impl UncompressedChange {
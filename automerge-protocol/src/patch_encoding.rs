@@ -0,0 +1,547 @@
+//! A compact binary encoding of `Patch` and `Diff`
+//!
+//! Patches which cross a language boundary are usually serialized as JSON,
+//! which is slow for large patches. This encoding is much cheaper to produce
+//! and parse. Actor IDs are written once to a table at the start of the
+//! encoding and referred to by index, integers are LEB128 encoded and strings
+//! are length prefixed UTF-8.
+//!
+//! ```text
+//! patch    := VERSION actors body
+//! actors   := uleb(count) (uleb(len) bytes)*
+//! body     := opt(actor_idx) opt(uleb seq) clock deps uleb(max_op) opt(diff)
+//! diff     := MAP objid map_type uleb(count) (string props)*
+//!           | SEQ objid seq_type edits uleb(count) (uleb(index) props)*
+//!           | UNCHANGED objid obj_type
+//!           | VALUE scalar
+//! props    := uleb(count) (opid diff)*
+//! ```
+use crate::error::InvalidPatchEncoding;
+use crate::{
+    ActorID, ChangeHash, Diff, DiffEdit, ElementID, MapDiff, MapType, ObjDiff, ObjType, ObjectID,
+    OpID, Patch, ScalarValue, SeqDiff, SequenceType,
+};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::str;
+
+const VERSION: u8 = 1;
+
+const DIFF_MAP: u8 = 0;
+const DIFF_SEQ: u8 = 1;
+const DIFF_UNCHANGED: u8 = 2;
+const DIFF_VALUE: u8 = 3;
+
+const EDIT_INSERT: u8 = 0;
+const EDIT_REMOVE: u8 = 1;
+
+const VALUE_STR: u8 = 0;
+const VALUE_INT: u8 = 1;
+const VALUE_UINT: u8 = 2;
+const VALUE_F64: u8 = 3;
+const VALUE_F32: u8 = 4;
+const VALUE_COUNTER: u8 = 5;
+const VALUE_TIMESTAMP: u8 = 6;
+const VALUE_BOOLEAN: u8 = 7;
+const VALUE_NULL: u8 = 8;
+
+/// Encode `patch` using the binary patch encoding
+pub fn encode_patch(patch: &Patch) -> Vec<u8> {
+    let mut encoder = Encoder::default();
+    encoder.option(patch.actor.as_ref(), |e, actor| e.actor(actor));
+    encoder.option(patch.seq, |e, seq| e.uleb(seq));
+    let mut clock: Vec<_> = patch.clock.iter().collect();
+    clock.sort();
+    encoder.usize(clock.len());
+    for (actor, seq) in clock {
+        encoder.actor(actor);
+        encoder.uleb(*seq);
+    }
+    encoder.usize(patch.deps.len());
+    for dep in &patch.deps {
+        encoder.body.extend_from_slice(&dep.0);
+    }
+    encoder.uleb(patch.max_op);
+    encoder.option(patch.diffs.as_ref(), |e, diff| e.diff(diff));
+    encoder.finish()
+}
+
+/// Decode a patch which was encoded with `encode_patch`
+pub fn decode_patch(bytes: &[u8]) -> Result<Patch, InvalidPatchEncoding> {
+    let mut decoder = Decoder::new(bytes)?;
+    let actor = decoder.option(|d| d.actor())?;
+    let seq = decoder.option(|d| d.uleb())?;
+    let mut clock = HashMap::new();
+    for _ in 0..decoder.uleb()? {
+        let actor = decoder.actor()?;
+        clock.insert(actor, decoder.uleb()?);
+    }
+    let mut deps = Vec::new();
+    for _ in 0..decoder.uleb()? {
+        let bytes = decoder.take(32)?;
+        // take returned exactly 32 bytes
+        deps.push(ChangeHash::try_from(bytes).unwrap());
+    }
+    let max_op = decoder.uleb()?;
+    let diffs = decoder.option(|d| d.diff())?;
+    decoder.finish()?;
+    Ok(Patch {
+        actor,
+        seq,
+        clock,
+        deps,
+        max_op,
+        diffs,
+    })
+}
+
+/// Encode a single `Diff` using the binary patch encoding
+pub fn encode_diff(diff: &Diff) -> Vec<u8> {
+    let mut encoder = Encoder::default();
+    encoder.diff(diff);
+    encoder.finish()
+}
+
+/// Decode a diff which was encoded with `encode_diff`
+pub fn decode_diff(bytes: &[u8]) -> Result<Diff, InvalidPatchEncoding> {
+    let mut decoder = Decoder::new(bytes)?;
+    let diff = decoder.diff()?;
+    decoder.finish()?;
+    Ok(diff)
+}
+
+#[derive(Default)]
+struct Encoder {
+    actors: Vec<ActorID>,
+    actor_indices: HashMap<ActorID, usize>,
+    body: Vec<u8>,
+}
+
+impl Encoder {
+    fn finish(self) -> Vec<u8> {
+        let mut result = vec![VERSION];
+        let mut header = Encoder::default();
+        header.usize(self.actors.len());
+        for actor in &self.actors {
+            let bytes = actor.to_bytes();
+            header.usize(bytes.len());
+            header.body.extend(bytes);
+        }
+        result.extend(header.body);
+        result.extend(self.body);
+        result
+    }
+
+    fn u8(&mut self, val: u8) {
+        self.body.push(val)
+    }
+
+    fn uleb(&mut self, mut val: u64) {
+        loop {
+            let byte = (val & 0x7f) as u8;
+            val >>= 7;
+            if val == 0 {
+                self.body.push(byte);
+                return;
+            }
+            self.body.push(byte | 0x80);
+        }
+    }
+
+    fn usize(&mut self, val: usize) {
+        self.uleb(val as u64)
+    }
+
+    fn sleb(&mut self, val: i64) {
+        // zigzag encoding so that small negative numbers stay small
+        self.uleb(((val << 1) ^ (val >> 63)) as u64)
+    }
+
+    fn string(&mut self, s: &str) {
+        self.usize(s.len());
+        self.body.extend_from_slice(s.as_bytes());
+    }
+
+    fn option<T, F: FnOnce(&mut Self, T)>(&mut self, val: Option<T>, f: F) {
+        match val {
+            Some(val) => {
+                self.u8(1);
+                f(self, val)
+            }
+            None => self.u8(0),
+        }
+    }
+
+    fn actor(&mut self, actor: &ActorID) {
+        let index = match self.actor_indices.get(actor) {
+            Some(index) => *index,
+            None => {
+                let index = self.actors.len();
+                self.actors.push(actor.clone());
+                self.actor_indices.insert(actor.clone(), index);
+                index
+            }
+        };
+        self.usize(index)
+    }
+
+    fn opid(&mut self, opid: &OpID) {
+        self.uleb(opid.0);
+        self.actor(&opid.1);
+    }
+
+    fn object_id(&mut self, object_id: &ObjectID) {
+        let opid = match object_id {
+            ObjectID::Root => None,
+            ObjectID::ID(opid) => Some(opid),
+        };
+        self.option(opid, |e, opid| e.opid(opid))
+    }
+
+    fn element_id(&mut self, element_id: &ElementID) {
+        let opid = match element_id {
+            ElementID::Head => None,
+            ElementID::ID(opid) => Some(opid),
+        };
+        self.option(opid, |e, opid| e.opid(opid))
+    }
+
+    fn obj_type(&mut self, obj_type: &ObjType) {
+        self.u8(match obj_type {
+            ObjType::Map(MapType::Map) => 0,
+            ObjType::Map(MapType::Table) => 1,
+            ObjType::Sequence(SequenceType::List) => 2,
+            ObjType::Sequence(SequenceType::Text) => 3,
+        })
+    }
+
+    fn props(&mut self, props: &HashMap<OpID, Diff>) {
+        self.usize(props.len());
+        for (opid, diff) in props {
+            self.opid(opid);
+            self.diff(diff);
+        }
+    }
+
+    fn diff(&mut self, diff: &Diff) {
+        match diff {
+            Diff::Map(MapDiff {
+                object_id,
+                obj_type,
+                props,
+            }) => {
+                self.u8(DIFF_MAP);
+                self.object_id(object_id);
+                self.obj_type(&ObjType::Map(*obj_type));
+                self.usize(props.len());
+                for (key, values) in props {
+                    self.string(key);
+                    self.props(values);
+                }
+            }
+            Diff::Seq(SeqDiff {
+                object_id,
+                obj_type,
+                edits,
+                props,
+            }) => {
+                self.u8(DIFF_SEQ);
+                self.object_id(object_id);
+                self.obj_type(&ObjType::Sequence(*obj_type));
+                self.usize(edits.len());
+                for edit in edits {
+                    match edit {
+                        DiffEdit::Insert { index, elem_id } => {
+                            self.u8(EDIT_INSERT);
+                            self.usize(*index);
+                            self.element_id(elem_id);
+                        }
+                        DiffEdit::Remove { index } => {
+                            self.u8(EDIT_REMOVE);
+                            self.usize(*index);
+                        }
+                    }
+                }
+                self.usize(props.len());
+                for (index, values) in props {
+                    self.usize(*index);
+                    self.props(values);
+                }
+            }
+            Diff::Unchanged(ObjDiff {
+                object_id,
+                obj_type,
+            }) => {
+                self.u8(DIFF_UNCHANGED);
+                self.object_id(object_id);
+                self.obj_type(obj_type);
+            }
+            Diff::Value(value) => {
+                self.u8(DIFF_VALUE);
+                self.scalar(value);
+            }
+        }
+    }
+
+    fn scalar(&mut self, value: &ScalarValue) {
+        match value {
+            ScalarValue::Str(s) => {
+                self.u8(VALUE_STR);
+                self.string(s);
+            }
+            ScalarValue::Int(i) => {
+                self.u8(VALUE_INT);
+                self.sleb(*i);
+            }
+            ScalarValue::Uint(u) => {
+                self.u8(VALUE_UINT);
+                self.uleb(*u);
+            }
+            ScalarValue::F64(f) => {
+                self.u8(VALUE_F64);
+                self.body.extend_from_slice(&f.to_le_bytes());
+            }
+            ScalarValue::F32(f) => {
+                self.u8(VALUE_F32);
+                self.body.extend_from_slice(&f.to_le_bytes());
+            }
+            ScalarValue::Counter(c) => {
+                self.u8(VALUE_COUNTER);
+                self.sleb(*c);
+            }
+            ScalarValue::Timestamp(t) => {
+                self.u8(VALUE_TIMESTAMP);
+                self.sleb(*t);
+            }
+            ScalarValue::Boolean(b) => {
+                self.u8(VALUE_BOOLEAN);
+                self.u8(*b as u8);
+            }
+            ScalarValue::Null => self.u8(VALUE_NULL),
+        }
+    }
+}
+
+struct Decoder<'a> {
+    actors: Vec<ActorID>,
+    bytes: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    fn new(bytes: &'a [u8]) -> Result<Decoder<'a>, InvalidPatchEncoding> {
+        let mut decoder = Decoder {
+            actors: Vec::new(),
+            bytes,
+        };
+        let version = decoder.u8()?;
+        if version != VERSION {
+            return Err(InvalidPatchEncoding::UnknownVersion(version));
+        }
+        for _ in 0..decoder.uleb()? {
+            let len = decoder.usize()?;
+            let actor = ActorID::from_bytes(decoder.take(len)?);
+            decoder.actors.push(actor);
+        }
+        Ok(decoder)
+    }
+
+    fn finish(self) -> Result<(), InvalidPatchEncoding> {
+        if self.bytes.is_empty() {
+            Ok(())
+        } else {
+            Err(InvalidPatchEncoding::TrailingBytes(self.bytes.len()))
+        }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], InvalidPatchEncoding> {
+        if self.bytes.len() < len {
+            return Err(InvalidPatchEncoding::UnexpectedEnd);
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, InvalidPatchEncoding> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn uleb(&mut self) -> Result<u64, InvalidPatchEncoding> {
+        let mut result: u64 = 0;
+        let mut shift = 0;
+        loop {
+            let byte = self.u8()?;
+            if shift == 63 && byte > 1 {
+                return Err(InvalidPatchEncoding::Overflow);
+            }
+            result |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(result);
+            }
+            shift += 7;
+            if shift > 63 {
+                return Err(InvalidPatchEncoding::Overflow);
+            }
+        }
+    }
+
+    fn usize(&mut self) -> Result<usize, InvalidPatchEncoding> {
+        usize::try_from(self.uleb()?).map_err(|_| InvalidPatchEncoding::Overflow)
+    }
+
+    fn sleb(&mut self) -> Result<i64, InvalidPatchEncoding> {
+        let val = self.uleb()?;
+        Ok(((val >> 1) as i64) ^ -((val & 1) as i64))
+    }
+
+    fn string(&mut self) -> Result<String, InvalidPatchEncoding> {
+        let len = self.usize()?;
+        str::from_utf8(self.take(len)?)
+            .map(|s| s.to_string())
+            .map_err(|_| InvalidPatchEncoding::InvalidUtf8)
+    }
+
+    fn option<T, F>(&mut self, f: F) -> Result<Option<T>, InvalidPatchEncoding>
+    where
+        F: FnOnce(&mut Self) -> Result<T, InvalidPatchEncoding>,
+    {
+        match self.u8()? {
+            0 => Ok(None),
+            1 => Ok(Some(f(self)?)),
+            tag => Err(InvalidPatchEncoding::InvalidTag { tag }),
+        }
+    }
+
+    fn actor(&mut self) -> Result<ActorID, InvalidPatchEncoding> {
+        let index = self.usize()?;
+        self.actors
+            .get(index)
+            .cloned()
+            .ok_or(InvalidPatchEncoding::InvalidActorIndex(index))
+    }
+
+    fn opid(&mut self) -> Result<OpID, InvalidPatchEncoding> {
+        let counter = self.uleb()?;
+        Ok(OpID(counter, self.actor()?))
+    }
+
+    fn object_id(&mut self) -> Result<ObjectID, InvalidPatchEncoding> {
+        Ok(self
+            .option(|d| d.opid())?
+            .map(ObjectID::ID)
+            .unwrap_or(ObjectID::Root))
+    }
+
+    fn element_id(&mut self) -> Result<ElementID, InvalidPatchEncoding> {
+        Ok(self
+            .option(|d| d.opid())?
+            .map(ElementID::ID)
+            .unwrap_or(ElementID::Head))
+    }
+
+    fn obj_type(&mut self) -> Result<ObjType, InvalidPatchEncoding> {
+        match self.u8()? {
+            0 => Ok(ObjType::Map(MapType::Map)),
+            1 => Ok(ObjType::Map(MapType::Table)),
+            2 => Ok(ObjType::Sequence(SequenceType::List)),
+            3 => Ok(ObjType::Sequence(SequenceType::Text)),
+            tag => Err(InvalidPatchEncoding::InvalidTag { tag }),
+        }
+    }
+
+    fn props(&mut self) -> Result<HashMap<OpID, Diff>, InvalidPatchEncoding> {
+        let mut props = HashMap::new();
+        for _ in 0..self.uleb()? {
+            let opid = self.opid()?;
+            props.insert(opid, self.diff()?);
+        }
+        Ok(props)
+    }
+
+    fn diff(&mut self) -> Result<Diff, InvalidPatchEncoding> {
+        match self.u8()? {
+            DIFF_MAP => {
+                let object_id = self.object_id()?;
+                let obj_type = match self.obj_type()? {
+                    ObjType::Map(map_type) => map_type,
+                    ObjType::Sequence(_) => return Err(InvalidPatchEncoding::MismatchedObjType),
+                };
+                let mut props = HashMap::new();
+                for _ in 0..self.uleb()? {
+                    let key = self.string()?;
+                    props.insert(key, self.props()?);
+                }
+                Ok(Diff::Map(MapDiff {
+                    object_id,
+                    obj_type,
+                    props,
+                }))
+            }
+            DIFF_SEQ => {
+                let object_id = self.object_id()?;
+                let obj_type = match self.obj_type()? {
+                    ObjType::Sequence(seq_type) => seq_type,
+                    ObjType::Map(_) => return Err(InvalidPatchEncoding::MismatchedObjType),
+                };
+                let mut edits = Vec::new();
+                for _ in 0..self.uleb()? {
+                    let edit = match self.u8()? {
+                        EDIT_INSERT => DiffEdit::Insert {
+                            index: self.usize()?,
+                            elem_id: self.element_id()?,
+                        },
+                        EDIT_REMOVE => DiffEdit::Remove {
+                            index: self.usize()?,
+                        },
+                        tag => return Err(InvalidPatchEncoding::InvalidTag { tag }),
+                    };
+                    edits.push(edit);
+                }
+                let mut props = HashMap::new();
+                for _ in 0..self.uleb()? {
+                    let index = self.usize()?;
+                    props.insert(index, self.props()?);
+                }
+                Ok(Diff::Seq(SeqDiff {
+                    object_id,
+                    obj_type,
+                    edits,
+                    props,
+                }))
+            }
+            DIFF_UNCHANGED => Ok(Diff::Unchanged(ObjDiff {
+                object_id: self.object_id()?,
+                obj_type: self.obj_type()?,
+            })),
+            DIFF_VALUE => Ok(Diff::Value(self.scalar()?)),
+            tag => Err(InvalidPatchEncoding::InvalidTag { tag }),
+        }
+    }
+
+    fn scalar(&mut self) -> Result<ScalarValue, InvalidPatchEncoding> {
+        match self.u8()? {
+            VALUE_STR => Ok(ScalarValue::Str(self.string()?)),
+            VALUE_INT => Ok(ScalarValue::Int(self.sleb()?)),
+            VALUE_UINT => Ok(ScalarValue::Uint(self.uleb()?)),
+            VALUE_F64 => {
+                let mut bytes = [0; 8];
+                bytes.copy_from_slice(self.take(8)?);
+                Ok(ScalarValue::F64(f64::from_le_bytes(bytes)))
+            }
+            VALUE_F32 => {
+                let mut bytes = [0; 4];
+                bytes.copy_from_slice(self.take(4)?);
+                Ok(ScalarValue::F32(f32::from_le_bytes(bytes)))
+            }
+            VALUE_COUNTER => Ok(ScalarValue::Counter(self.sleb()?)),
+            VALUE_TIMESTAMP => Ok(ScalarValue::Timestamp(self.sleb()?)),
+            VALUE_BOOLEAN => match self.u8()? {
+                0 => Ok(ScalarValue::Boolean(false)),
+                1 => Ok(ScalarValue::Boolean(true)),
+                tag => Err(InvalidPatchEncoding::InvalidTag { tag }),
+            },
+            VALUE_NULL => Ok(ScalarValue::Null),
+            tag => Err(InvalidPatchEncoding::InvalidTag { tag }),
+        }
+    }
+}
@@ -23,6 +23,7 @@ impl Serialize for Op {
             OpType::Set(ScalarValue::Timestamp(_)) => fields += 2,
             OpType::Set(ScalarValue::Counter(_)) => fields += 2,
             OpType::Inc(_) | OpType::Set(_) => fields += 1,
+            OpType::Unknown { .. } => fields += 2,
             _ => {}
         }
 
@@ -51,6 +52,12 @@ impl Serialize for Op {
                 op.serialize_field("datatype", &DataType::Timestamp)?;
             }
             OpType::Set(value) => op.serialize_field("value", &value)?,
+            OpType::Unknown {
+                value_type, value, ..
+            } => {
+                op.serialize_field("valueType", value_type)?;
+                op.serialize_field("rawValue", value)?;
+            }
             _ => {}
         }
         op.serialize_field("pred", &self.pred)?;
@@ -70,12 +77,32 @@ pub enum RawOpType {
     Set,
 }
 
+/// The action of an op is either one of the names above or, for ops this
+/// implementation does not understand, the numeric action code
+#[derive(Deserialize, PartialEq, Debug, Clone, Copy)]
+#[serde(untagged)]
+pub enum RawAction {
+    Known(RawOpType),
+    Unknown(u64),
+}
+
 impl<'de> Deserialize<'de> for Op {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: Deserializer<'de>,
     {
-        const FIELDS: &[&str] = &["ops", "deps", "message", "seq", "actor", "requestType"];
+        const FIELDS: &[&str] = &[
+            "action",
+            "obj",
+            "key",
+            "elemId",
+            "pred",
+            "insert",
+            "datatype",
+            "value",
+            "valueType",
+            "rawValue",
+        ];
         struct OperationVisitor;
         impl<'de> Visitor<'de> for OperationVisitor {
             type Value = Op;
@@ -88,13 +115,15 @@ impl<'de> Deserialize<'de> for Op {
             where
                 V: MapAccess<'de>,
             {
-                let mut action: Option<RawOpType> = None;
+                let mut action: Option<RawAction> = None;
                 let mut obj: Option<ObjectID> = None;
                 let mut key: Option<Key> = None;
                 let mut pred: Option<Vec<OpID>> = None;
                 let mut insert: Option<bool> = None;
                 let mut datatype: Option<DataType> = None;
                 let mut value: Option<Option<ScalarValue>> = None;
+                let mut value_type: Option<u8> = None;
+                let mut raw_value: Option<Vec<u8>> = None;
                 while let Some(field) = map.next_key::<String>()? {
                     match field.as_ref() {
                         "action" => read_field("action", &mut action, &mut map)?,
@@ -105,6 +134,8 @@ impl<'de> Deserialize<'de> for Op {
                         "insert" => read_field("insert", &mut insert, &mut map)?,
                         "datatype" => read_field("datatype", &mut datatype, &mut map)?,
                         "value" => read_field("value", &mut value, &mut map)?,
+                        "valueType" => read_field("valueType", &mut value_type, &mut map)?,
+                        "rawValue" => read_field("rawValue", &mut raw_value, &mut map)?,
                         _ => return Err(Error::unknown_field(&field, FIELDS)),
                     }
                 }
@@ -113,6 +144,22 @@ impl<'de> Deserialize<'de> for Op {
                 let key = key.ok_or_else(|| Error::missing_field("key"))?;
                 let pred = pred.ok_or_else(|| Error::missing_field("pred"))?;
                 let insert = insert.unwrap_or(false);
+                let action = match action {
+                    RawAction::Known(action) => action,
+                    RawAction::Unknown(action) => {
+                        return Ok(Op {
+                            action: OpType::Unknown {
+                                action,
+                                value_type: value_type.unwrap_or(0),
+                                value: raw_value.unwrap_or_default(),
+                            },
+                            obj,
+                            key,
+                            insert,
+                            pred,
+                        })
+                    }
+                };
                 let action = match action {
                     RawOpType::MakeMap => OpType::Make(ObjType::Map(MapType::Map)),
                     RawOpType::MakeTable => OpType::Make(ObjType::Map(MapType::Table)),
@@ -369,6 +416,28 @@ mod tests {
                 }),
                 expected: Err(serde_json::Error::missing_field("value")),
             },
+            Scenario {
+                name: "Unknown action",
+                json: serde_json::json!({
+                    "action": 17,
+                    "obj": "_root",
+                    "key": "somekey",
+                    "valueType": 12,
+                    "rawValue": [1, 2, 3],
+                    "pred": []
+                }),
+                expected: Ok(Op {
+                    action: OpType::Unknown {
+                        action: 17,
+                        value_type: 12,
+                        value: vec![1, 2, 3],
+                    },
+                    obj: ObjectID::Root,
+                    key: "somekey".into(),
+                    insert: false,
+                    pred: Vec::new(),
+                }),
+            },
             Scenario {
                 name: "Set with null",
                 json: serde_json::json!({
@@ -521,6 +590,17 @@ mod tests {
                 insert: false,
                 pred: vec![OpID::from_str("1@7ef48769b04d47e9a88e98a134d62716").unwrap()],
             },
+            Op {
+                action: OpType::Unknown {
+                    action: 17,
+                    value_type: 12,
+                    value: vec![1, 2, 3],
+                },
+                obj: ObjectID::Root,
+                key: "somekey".into(),
+                insert: true,
+                pred: Vec::new(),
+            },
         ];
         for (testcase_num, testcase) in testcases.iter().enumerate() {
             #[allow(clippy::expect_fun_call)]
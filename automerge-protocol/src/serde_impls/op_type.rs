@@ -14,6 +14,7 @@ impl Serialize for OpType {
             OpType::Del => "del",
             OpType::Inc(_) => "inc",
             OpType::Set(_) => "set",
+            OpType::Unknown { action, .. } => return serializer.serialize_u64(*action),
         };
         serializer.serialize_str(s)
     }
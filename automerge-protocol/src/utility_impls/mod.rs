@@ -5,4 +5,5 @@ mod element_id;
 mod key;
 mod object_id;
 mod opid;
+mod path_element;
 mod scalar_value;
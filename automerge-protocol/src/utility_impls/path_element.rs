@@ -0,0 +1,29 @@
+use crate::PathElement;
+use std::fmt;
+
+impl From<u32> for PathElement {
+    fn from(index: u32) -> Self {
+        PathElement::Index(index)
+    }
+}
+
+impl From<&str> for PathElement {
+    fn from(key: &str) -> Self {
+        PathElement::Key(key.to_string())
+    }
+}
+
+impl From<String> for PathElement {
+    fn from(key: String) -> Self {
+        PathElement::Key(key)
+    }
+}
+
+impl fmt::Display for PathElement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathElement::Key(key) => write!(f, "{}", key),
+            PathElement::Index(index) => write!(f, "{}", index),
+        }
+    }
+}
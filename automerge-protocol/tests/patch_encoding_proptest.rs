@@ -0,0 +1,182 @@
+extern crate automerge_protocol as amp;
+use proptest::prelude::*;
+use std::collections::HashMap;
+
+fn arb_maptype() -> impl Strategy<Value = amp::MapType> {
+    prop_oneof![Just(amp::MapType::Map), Just(amp::MapType::Table),]
+}
+
+fn arb_seqtype() -> impl Strategy<Value = amp::SequenceType> {
+    prop_oneof![Just(amp::SequenceType::List), Just(amp::SequenceType::Text),]
+}
+
+fn arb_objtype() -> impl Strategy<Value = amp::ObjType> {
+    prop_oneof![
+        arb_maptype().prop_map(amp::ObjType::Map),
+        arb_seqtype().prop_map(amp::ObjType::Sequence),
+    ]
+}
+
+fn arb_scalar_value() -> impl Strategy<Value = amp::ScalarValue> {
+    prop_oneof![
+        any::<String>().prop_map(amp::ScalarValue::Str),
+        any::<i64>().prop_map(amp::ScalarValue::Int),
+        any::<u64>().prop_map(amp::ScalarValue::Uint),
+        // NaN is not equal to itself, so would never round trip
+        proptest::num::f64::NORMAL.prop_map(amp::ScalarValue::F64),
+        proptest::num::f32::NORMAL.prop_map(amp::ScalarValue::F32),
+        any::<i64>().prop_map(amp::ScalarValue::Counter),
+        any::<i64>().prop_map(amp::ScalarValue::Timestamp),
+        any::<bool>().prop_map(amp::ScalarValue::Boolean),
+        Just(amp::ScalarValue::Null),
+    ]
+}
+
+fn arb_actorid() -> impl Strategy<Value = amp::ActorID> {
+    // A small pool of actors so that actor IDs are shared between op IDs
+    (0..4u8).prop_map(|i| amp::ActorID::from_bytes(&[i; 16]))
+}
+
+fn arb_opid() -> impl Strategy<Value = amp::OpID> {
+    (any::<u64>(), arb_actorid()).prop_map(|(seq, actor)| amp::OpID::new(seq, &actor))
+}
+
+fn arb_objid() -> impl Strategy<Value = amp::ObjectID> {
+    prop_oneof![
+        Just(amp::ObjectID::Root),
+        arb_opid().prop_map(amp::ObjectID::ID),
+    ]
+}
+
+fn arb_elemid() -> impl Strategy<Value = amp::ElementID> {
+    prop_oneof![
+        Just(amp::ElementID::Head),
+        arb_opid().prop_map(amp::ElementID::ID),
+    ]
+}
+
+fn arb_changehash() -> impl Strategy<Value = amp::ChangeHash> {
+    any::<[u8; 32]>().prop_map(amp::ChangeHash)
+}
+
+fn arb_diff_edit() -> impl Strategy<Value = amp::DiffEdit> {
+    prop_oneof![
+        (any::<usize>(), arb_elemid())
+            .prop_map(|(index, elem_id)| amp::DiffEdit::Insert { index, elem_id }),
+        any::<usize>().prop_map(|index| amp::DiffEdit::Remove { index }),
+    ]
+}
+
+fn arb_diff() -> impl Strategy<Value = amp::Diff> {
+    let leaf = prop_oneof![
+        arb_scalar_value().prop_map(amp::Diff::Value),
+        (arb_objid(), arb_objtype()).prop_map(|(object_id, obj_type)| {
+            amp::Diff::Unchanged(amp::ObjDiff {
+                object_id,
+                obj_type,
+            })
+        }),
+    ];
+    leaf.prop_recursive(4, 32, 4, |inner| {
+        let props = move || proptest::collection::hash_map(arb_opid(), inner.clone(), 0..3);
+        prop_oneof![
+            (
+                arb_objid(),
+                arb_maptype(),
+                proptest::collection::hash_map(any::<String>(), props(), 0..3)
+            )
+                .prop_map(|(object_id, obj_type, props)| {
+                    amp::Diff::Map(amp::MapDiff {
+                        object_id,
+                        obj_type,
+                        props,
+                    })
+                }),
+            (
+                arb_objid(),
+                arb_seqtype(),
+                proptest::collection::vec(arb_diff_edit(), 0..4),
+                proptest::collection::hash_map(any::<usize>(), props(), 0..3)
+            )
+                .prop_map(|(object_id, obj_type, edits, props)| {
+                    amp::Diff::Seq(amp::SeqDiff {
+                        object_id,
+                        obj_type,
+                        edits,
+                        props,
+                    })
+                }),
+        ]
+    })
+}
+
+prop_compose! {
+    fn arb_patch()
+            (actor in proptest::option::of(arb_actorid()),
+             seq in proptest::option::of(any::<u64>()),
+             clock in proptest::collection::hash_map(arb_actorid(), any::<u64>(), 0..4),
+             deps in proptest::collection::vec(arb_changehash(), 0..4),
+             max_op in any::<u64>(),
+             diffs in proptest::option::of(arb_diff())) -> amp::Patch {
+            amp::Patch {
+                actor,
+                seq,
+                clock,
+                deps,
+                max_op,
+                diffs,
+            }
+    }
+}
+
+proptest! {
+    #[test]
+    fn test_patch_encoding_round_trip(patch in arb_patch()) {
+        let decoded = amp::decode_patch(&amp::encode_patch(&patch))?;
+        prop_assert_eq!(serde_json::to_value(&decoded)?, serde_json::to_value(&patch)?);
+        prop_assert_eq!(decoded, patch);
+    }
+
+    #[test]
+    fn test_diff_encoding_round_trip(diff in arb_diff()) {
+        let decoded = amp::decode_diff(&amp::encode_diff(&diff))?;
+        prop_assert_eq!(decoded, diff);
+    }
+
+    #[test]
+    fn test_truncated_patch_is_an_error(patch in arb_patch()) {
+        let encoded = amp::encode_patch(&patch);
+        for len in 0..encoded.len() {
+            prop_assert!(amp::decode_patch(&encoded[..len]).is_err());
+        }
+    }
+}
+
+#[test]
+fn test_patch_encoding_is_smaller_than_json() {
+    let actor = amp::ActorID::random();
+    let mut props = HashMap::new();
+    for i in 0..100 {
+        let mut values = HashMap::new();
+        values.insert(
+            amp::OpID::new(i + 1, &actor),
+            amp::Diff::Value(amp::ScalarValue::Str(format!("value {}", i))),
+        );
+        props.insert(format!("key {}", i), values);
+    }
+    let patch = amp::Patch {
+        actor: Some(actor.clone()),
+        seq: Some(1),
+        clock: vec![(actor, 1)].into_iter().collect(),
+        deps: Vec::new(),
+        max_op: 100,
+        diffs: Some(amp::Diff::Map(amp::MapDiff {
+            object_id: amp::ObjectID::Root,
+            obj_type: amp::MapType::Map,
+            props,
+        })),
+    };
+    let encoded = amp::encode_patch(&patch);
+    assert!(encoded.len() < serde_json::to_vec(&patch).unwrap().len() / 2);
+    assert_eq!(amp::decode_patch(&encoded).unwrap(), patch);
+}
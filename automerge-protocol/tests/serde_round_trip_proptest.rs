@@ -38,6 +38,16 @@ fn arb_optype() -> impl Strategy<Value = amp::OpType> {
         Just(amp::OpType::Del),
         any::<i64>().prop_map(amp::OpType::Inc),
         arb_scalar_value().prop_map(amp::OpType::Set),
+        (
+            any::<u64>(),
+            any::<u8>(),
+            proptest::collection::vec(any::<u8>(), 0..10)
+        )
+            .prop_map(|(action, value_type, value)| amp::OpType::Unknown {
+                action,
+                value_type,
+                value
+            }),
     ]
 }
 
@@ -91,6 +101,11 @@ prop_compose! {
     }
 }
 
+fn arb_unknown_column() -> impl Strategy<Value = amp::UnknownColumn> {
+    (any::<u32>(), proptest::collection::vec(any::<u8>(), 0..10))
+        .prop_map(|(id, data)| amp::UnknownColumn { id, data })
+}
+
 prop_compose! {
     fn arb_change()
             (seq in any::<u64>(),
@@ -100,6 +115,7 @@ prop_compose! {
              message in proptest::option::of(any::<String>()),
              deps in proptest::collection::vec(arb_changehash(), 0..10),
              extra_bytes in proptest::collection::vec(any::<u8>(), 0..10),
+             unknown_columns in proptest::collection::vec(arb_unknown_column(), 0..3),
              operations in proptest::collection::vec(arb_op(), 0..10)) -> amp::UncompressedChange {
             amp::UncompressedChange{
                 seq,
@@ -109,7 +125,8 @@ prop_compose! {
                 message,
                 deps,
                 operations,
-                extra_bytes
+                extra_bytes,
+                unknown_columns,
             }
     }
 }